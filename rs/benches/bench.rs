@@ -1,7 +1,7 @@
 #![feature(test)]
 extern crate test;
 
-use aoc::prelude::*;
+use aoc::{prelude::*, scan::Scanner};
 use rand::{rngs::SmallRng, Rng, SeedableRng};
 use test::Bencher;
 
@@ -80,3 +80,94 @@ fn bench_astar(b: &mut Bencher) {
         .unwrap()
     });
 }
+
+/// The largest example inputs concatenated and repeated to get measurable
+/// timings.
+fn example_text() -> String {
+    [
+        include_str!("../../examples/2122.txt"),
+        include_str!("../../examples/2119.txt"),
+        include_str!("../../examples/2210.txt"),
+    ]
+    .concat()
+    .repeat(100)
+}
+
+/// Grid part of the largest grid example.
+fn example_grid() -> &'static str {
+    let text = include_str!("../../examples/2323.txt");
+    text.split("\n%").next().unwrap()
+}
+
+#[bench]
+fn bench_scanner_ints(b: &mut Bencher) {
+    let input = example_text();
+    b.iter(|| {
+        Scanner::new(input.as_bytes())
+            .ints::<i64>()
+            .fold(0, i64::wrapping_add)
+    });
+}
+
+#[bench]
+fn bench_numbers(b: &mut Bencher) {
+    let input = example_text();
+    b.iter(|| {
+        input
+            .lines()
+            .flat_map(numbers::<i64>)
+            .fold(0, i64::wrapping_add)
+    });
+}
+
+#[bench]
+fn bench_regex_numbers(b: &mut Bencher) {
+    // The regex based extractor `numbers` used before the scanner.
+    let re = regex::Regex::new(r"(-?\d+)(.|$)").unwrap();
+    let input = example_text();
+    b.iter(|| {
+        input
+            .lines()
+            .flat_map(|line| re.captures_iter(line))
+            .map(|caps| caps[1].parse::<i64>().unwrap())
+            .fold(0, i64::wrapping_add)
+    });
+}
+
+#[bench]
+fn bench_scanner_lines(b: &mut Bencher) {
+    let input = example_text();
+    b.iter(|| {
+        let mut scanner = Scanner::new(input.as_bytes());
+        let mut n = 0;
+        while let Some(line) = scanner.line() {
+            n += line.len();
+        }
+        n
+    });
+}
+
+#[bench]
+fn bench_scanner_words(b: &mut Bencher) {
+    let input = example_text();
+    b.iter(|| {
+        let mut scanner = Scanner::new(input.as_bytes());
+        let mut n = 0;
+        while let Some(word) = scanner.word_bytes() {
+            n += word.len();
+        }
+        n
+    });
+}
+
+#[bench]
+fn bench_scanner_grid(b: &mut Bencher) {
+    let input = example_grid();
+    b.iter(|| Scanner::new(input.as_bytes()).grid());
+}
+
+#[bench]
+fn bench_grid(b: &mut Bencher) {
+    let input = example_grid();
+    b.iter(|| grid(input));
+}
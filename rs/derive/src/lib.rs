@@ -0,0 +1,235 @@
+//! Derive macros for the `aoc` crate.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{
+    parse_macro_input, spanned::Spanned, Attribute, Data, DeriveInput, Error,
+    Fields, GenericArgument, LitStr, PathArguments, Type,
+};
+
+/// Derive `RegexParseable` and `FromStr` from regular expressions.
+///
+/// Structs take the regex from a `#[re("...")]` attribute on the type and
+/// enums from one on each variant. Enums parse as the first variant whose
+/// regex matches.
+///
+/// A field reads the capture group with the field's name if the regex has
+/// one and the group at the field's position otherwise. Fields can be any
+/// `FromStr` type. `Option` fields are `None` when their group doesn't
+/// participate in the match.
+///
+/// The `RegexParseable` impl of a struct reads the fields from whatever regex
+/// it's given, the `FromStr` impl uses the attribute regex. Enums parse the
+/// first group of the given regex, or the whole match if it has no groups,
+/// with their variant regexes.
+#[proc_macro_derive(RegexParse, attributes(re))]
+pub fn derive_regex_parse(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match regex_parse(&input) {
+        Ok(ts) => ts.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn regex_parse(input: &DeriveInput) -> Result<TokenStream2, Error> {
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) =
+        input.generics.split_for_impl();
+
+    let (parse_body, from_str_body) = match &input.data {
+        Data::Struct(data) => {
+            let build = build(quote!(#name), &data.fields);
+            let caps = caps_binding(&data.fields);
+            let parse = quote! {
+                let #caps = ::aoc::parse::captures(re, input)?;
+                Ok(#build)
+            };
+            let from_str = regex_attr(&input.attrs)?.map(|re| {
+                quote! {
+                    #re
+                    <Self as ::aoc::prelude::RegexParseable>::parse(&RE, input)
+                }
+            });
+            (parse, from_str)
+        }
+        Data::Enum(data) => {
+            let mut arms = Vec::new();
+            let mut patterns = Vec::new();
+            for v in &data.variants {
+                let Some(re) = regex_attr(&v.attrs)? else {
+                    return Err(Error::new(
+                        v.span(),
+                        "RegexParse: variant needs a #[re(\"...\")] attribute",
+                    ));
+                };
+                let ident = &v.ident;
+                let build = build(quote!(Self::#ident), &v.fields);
+                let caps = caps_binding(&v.fields);
+                patterns.push(regex_lit(&v.attrs)?.unwrap().value());
+                arms.push(quote! {{
+                    #re
+                    let re: &::aoc::parse::Regex = &RE;
+                    if let Some(#caps) = re.captures(input) {
+                        return Ok(#build);
+                    }
+                }});
+            }
+            let expected = format!(
+                "match for one of {}",
+                patterns
+                    .iter()
+                    .map(|p| format!("/{p}/"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+            let parse = quote! {
+                let caps = ::aoc::parse::captures(re, input)?;
+                let m = caps.get(1).or_else(|| caps.get(0)).unwrap();
+                <Self as ::std::str::FromStr>::from_str(m.as_str())
+                    .map_err(|e| e.within(input, m.start()))
+            };
+            let from_str = quote! {
+                #(#arms)*
+                Err(::aoc::parse::ParseError::new(input, #expected).at(1, 1))
+            };
+            (parse, Some(from_str))
+        }
+        Data::Union(_) => {
+            return Err(Error::new(
+                input.span(),
+                "RegexParse: unions are not supported",
+            ))
+        }
+    };
+
+    let from_str = from_str_body.map(|body| {
+        quote! {
+            impl #impl_generics ::std::str::FromStr for #name #ty_generics
+            #where_clause
+            {
+                type Err = ::aoc::parse::ParseError;
+
+                fn from_str(input: &str) -> Result<Self, Self::Err> {
+                    #body
+                }
+            }
+        }
+    });
+
+    Ok(quote! {
+        impl #impl_generics ::aoc::prelude::RegexParseable for #name #ty_generics
+        #where_clause
+        {
+            type Error = ::aoc::parse::ParseError;
+
+            fn parse(
+                re: &::aoc::parse::Regex,
+                input: &str,
+            ) -> Result<Self, Self::Error> {
+                #parse_body
+            }
+        }
+
+        #from_str
+    })
+}
+
+/// Expression that builds the struct or variant from `caps` for `input`
+/// matched by `re`.
+fn build(path: TokenStream2, fields: &Fields) -> TokenStream2 {
+    let values = fields
+        .iter()
+        .enumerate()
+        .map(|(i, f)| {
+            let group = i + 1;
+            let name = match &f.ident {
+                Some(id) => {
+                    let s = id.to_string();
+                    let s = s.strip_prefix("r#").unwrap_or(&s).to_owned();
+                    quote!(Some(#s))
+                }
+                None => quote!(None),
+            };
+            if let Some(inner) = option_type(&f.ty) {
+                quote! {
+                    ::aoc::parse::optional_capture::<#inner>(
+                        re, &caps, #name, #group, input)?
+                }
+            } else {
+                let ty = &f.ty;
+                quote! {
+                    ::aoc::parse::capture::<#ty>(
+                        re, &caps, #name, #group, input)?
+                }
+            }
+        })
+        .collect::<Vec<_>>();
+
+    match fields {
+        Fields::Named(_) => {
+            let names = fields.iter().map(|f| f.ident.as_ref().unwrap());
+            quote!(#path { #(#names: #values),* })
+        }
+        Fields::Unnamed(_) => quote!(#path(#(#values),*)),
+        Fields::Unit => path,
+    }
+}
+
+/// Name to bind the captures to, `_` if there are no fields to read them.
+fn caps_binding(fields: &Fields) -> TokenStream2 {
+    if fields.is_empty() {
+        quote!(_)
+    } else {
+        quote!(caps)
+    }
+}
+
+fn regex_lit(attrs: &[Attribute]) -> Result<Option<LitStr>, Error> {
+    let mut ret = None;
+    for attr in attrs.iter().filter(|a| a.path().is_ident("re")) {
+        if ret.is_some() {
+            return Err(Error::new(
+                attr.span(),
+                "RegexParse: more than one #[re] attribute",
+            ));
+        }
+        ret = Some(attr.parse_args::<LitStr>()?);
+    }
+    Ok(ret)
+}
+
+/// Static `RE` with the compiled regex from the attributes, checked at
+/// compile time.
+fn regex_attr(attrs: &[Attribute]) -> Result<Option<TokenStream2>, Error> {
+    let Some(lit) = regex_lit(attrs)? else {
+        return Ok(None);
+    };
+    if let Err(e) = regex::Regex::new(&lit.value()) {
+        return Err(Error::new(lit.span(), format!("RegexParse: {e}")));
+    }
+    Ok(Some(quote! {
+        static RE: ::std::sync::LazyLock<::aoc::parse::Regex> =
+            ::std::sync::LazyLock::new(|| {
+                ::aoc::parse::Regex::new(#lit).unwrap()
+            });
+    }))
+}
+
+/// The `T` in `Option<T>`.
+fn option_type(ty: &Type) -> Option<&Type> {
+    let Type::Path(p) = ty else {
+        return None;
+    };
+    let seg = p.path.segments.last()?;
+    if seg.ident != "Option" {
+        return None;
+    }
+    let PathArguments::AngleBracketed(args) = &seg.arguments else {
+        return None;
+    };
+    match args.args.first()? {
+        GenericArgument::Type(t) if args.args.len() == 1 => Some(t),
+        _ => None,
+    }
+}
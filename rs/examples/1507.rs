@@ -1,109 +1,60 @@
-use aoc::prelude::*;
+use aoc::{
+    circuit::{Arg, Circuit, Op},
+    parsers::{ident, int, lines, parse_all},
+    prelude::*,
+};
 use nom::{
     branch::alt,
-    bytes::complete::{tag, take_while1},
+    bytes::complete::tag,
+    combinator::map,
     sequence::{preceded, separated_pair},
-    Finish, Parser,
+    IResult,
 };
 
-enum Term {
-    Int(u16),
-    Sym(String),
-}
-
-impl Term {
-    fn eval(
-        &self,
-        s: &HashMap<String, Op>,
-        m: &mut HashMap<String, u16>,
-    ) -> u16 {
-        match self {
-            Int(n) => *n,
-            Sym(x) => eval(s, m, x),
-        }
-    }
-}
+use Arg::*;
 
-enum Op {
-    Id(Term),
-    Not(Term),
-    Or(Term, Term),
-    And(Term, Term),
-    Lshift(Term, u16),
-    Rshift(Term, u16),
+fn term(input: &str) -> IResult<&str, Arg<u16>> {
+    alt((map(int, Const), map(ident, |s| Node(s.to_string()))))(input)
 }
 
-use Op::*;
-use Term::*;
-
-fn eval(
-    s: &HashMap<String, Op>,
-    m: &mut HashMap<String, u16>,
-    sym: &str,
-) -> u16 {
-    if let Some(&n) = m.get(sym) {
-        return n;
-    }
-    let ret = match &s[sym] {
-        Id(t) => t.eval(s, m),
-        Not(t) => !t.eval(s, m),
-        Or(t, u) => t.eval(s, m) | u.eval(s, m),
-        And(t, u) => t.eval(s, m) & u.eval(s, m),
-        Lshift(t, n) => t.eval(s, m) << n,
-        Rshift(t, n) => t.eval(s, m) >> n,
-    };
-    m.insert(sym.to_string(), ret);
-    ret
+fn op(input: &str) -> IResult<&str, Op<u16>> {
+    alt((
+        map(preceded(tag("NOT "), term), Op::Not),
+        map(separated_pair(term, tag(" OR "), term), |(a, b)| {
+            Op::Or(a, b)
+        }),
+        map(separated_pair(term, tag(" AND "), term), |(a, b)| {
+            Op::And(a, b)
+        }),
+        map(separated_pair(term, tag(" LSHIFT "), int), |(a, b)| {
+            Op::Shl(a, Const(b))
+        }),
+        map(separated_pair(term, tag(" RSHIFT "), int), |(a, b)| {
+            Op::Shr(a, Const(b))
+        }),
+        map(term, Op::Id),
+    ))(input)
 }
 
-fn main() {
-    let num = || {
-        take_while1::<_, _, ()>(|c: char| c.is_numeric())
-            .map(|s: &str| s.parse::<u16>().unwrap())
-    };
-
-    let term = || {
-        alt((
-            num().map(Int),
-            take_while1(|c: char| c.is_alphabetic())
-                .map(|s: &str| Sym(s.to_string())),
-        ))
-    };
-
-    let op = || {
-        alt::<_, _, (), _>((
-            preceded(tag("NOT "), term()).map(Not),
-            separated_pair(term(), tag(" OR "), term()).map(|(a, b)| Or(a, b)),
-            separated_pair(term(), tag(" AND "), term())
-                .map(|(a, b)| And(a, b)),
-            separated_pair(term(), tag(" LSHIFT "), num())
-                .map(|(a, b)| Lshift(a, b)),
-            separated_pair(term(), tag(" RSHIFT "), num())
-                .map(|(a, b)| Rshift(a, b)),
-            term().map(Id),
-        ))
-    };
-
-    let mut wires = HashMap::default();
+fn main() -> anyhow::Result<()> {
+    let mut wires = Circuit::new();
 
     let input = stdin_string();
-    for line in input.lines() {
-        match separated_pair(op(), tag(" -> "), term())(line).finish() {
-            Ok((_, (o, Sym(s)))) => {
-                wires.insert(s.to_string(), o);
-            }
-            _ => panic!("Bad input"),
-        }
+    for (o, name) in
+        parse_all(&input, lines(separated_pair(op, tag(" -> "), ident)))?
+    {
+        wires.insert(name, o);
     }
 
     // Part 1
 
-    let a1 = eval(&wires, &mut HashMap::default(), "a");
+    let a1 = wires.eval("a").unwrap();
     println!("{a1}");
 
     // Part 2
 
-    wires.insert("b".to_string(), Id(Int(a1)));
-    let a2 = eval(&wires, &mut HashMap::default(), "a");
+    wires.set("b", a1);
+    let a2 = wires.eval("a").unwrap();
     println!("{a2}");
+    Ok(())
 }
@@ -3,7 +3,7 @@ use aoc::prelude::*;
 mod assembunny;
 
 fn main() {
-    let prog: assembunny::Program = idm::from_str(&stdin_string()).unwrap();
+    let prog = assembunny::Program(stdin_program());
 
     let mut regs = [7, 0, 0, 0];
     prog.clone().run(&mut regs);
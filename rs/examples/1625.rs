@@ -3,12 +3,11 @@ use aoc::prelude::*;
 mod assembunny;
 
 fn main() {
-    let prog: assembunny::Program = idm::from_str(&stdin_string()).unwrap();
+    let prog = assembunny::Program(stdin_program());
 
     'calibration: for i in 0.. {
-        let mut p = prog.clone();
-        let cpu = assembunny::Cpu::new(&mut p, [i, 0, 0, 0]);
-        for (x, y) in cpu.flatten().enumerate() {
+        let mut cpu = prog.cpu([i, 0, 0, 0]);
+        for (x, y) in std::iter::from_fn(|| cpu.next_output()).enumerate() {
             if y != (x as i64) % 2 {
                 break;
             }
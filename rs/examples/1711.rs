@@ -1,8 +1,8 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 use aoc::prelude::*;
 
-#[derive(Copy, Clone, Debug, Deserialize)]
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "lowercase")]
 enum HexDir {
     N,
@@ -37,11 +37,9 @@ fn hex_abs(vec: IVec2) -> i32 {
 }
 
 fn main() {
-    let input = stdin_string();
-    let dirs: Vec<HexDir> = input
-        .split(',')
-        .map(|a| idm::from_str(a).unwrap())
-        .collect();
+    // The steps are a single comma separated line, put each on its own.
+    let dirs: Vec<HexDir> = parse_program(&stdin_string().replace(',', "\n"))
+        .unwrap_or_else(|e| panic!("{e}"));
 
     println!(
         "{}",
@@ -56,3 +54,16 @@ fn main() {
             .0
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let text = "n\nne\nse\ns\nsw\nnw\n";
+        let dirs: Vec<HexDir> = parse_program(text).unwrap();
+        assert_eq!(dirs, [N, Ne, Se, S, Sw, Nw]);
+        assert_eq!(format_program(&dirs), text);
+    }
+}
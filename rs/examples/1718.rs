@@ -1,10 +1,12 @@
-use std::collections::VecDeque;
+use serde::{Deserialize, Serialize};
 
-use serde::Deserialize;
+use aoc::{
+    prelude::*,
+    vm::{Cpu, Flow, InstructionSet, Link, Network},
+    Operand,
+};
 
-use aoc::{prelude::*, Operand};
-
-#[derive(Copy, Clone, Debug, Deserialize)]
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "lowercase")]
 enum Opcode {
     Snd(char),
@@ -16,103 +18,83 @@ enum Opcode {
     Jgz(Operand, Operand),
 }
 
-struct Cpu<'a> {
-    pc: i64,
-    regs: [i64; 26],
-    input: VecDeque<i64>,
-    prog: &'a [Opcode],
-}
-
-impl<'a> Cpu<'a> {
-    pub fn new(id: i64, prog: &'a [Opcode]) -> Cpu<'a> {
-        let mut ret = Cpu {
-            pc: 0,
-            regs: [0; 26],
-            input: Default::default(),
-            prog,
-        };
-        ret.regs[(b'p' - b'a') as usize] = id;
-        ret
-    }
-
-    pub fn can_run(&self) -> bool {
-        // CPU can't run if either it's program has terminated or it's
-        // awaiting input. (We don't care which, terminating programs can just
-        // be treated as a deadlock.)
-        self.pc >= 0
-            && self.pc < self.prog.len() as i64
-            && !(matches!(self.prog[self.pc as usize], Opcode::Rcv(_))
-                && self.input.is_empty())
-    }
+impl InstructionSet for Opcode {
+    type Word = i64;
 
-    /// Return number of sends done during run.
-    pub fn run(&mut self, output: &mut VecDeque<i64>) -> usize {
-        let n = output.len();
-        while self.step(output) {}
-        output.len() - n
-    }
+    const REGISTERS: usize = 26;
 
     // Correct P2 instructions.
-    /// Return whether run can continue.
-    fn step(&mut self, output: &mut VecDeque<i64>) -> bool {
-        if self.pc < 0 || self.pc > self.prog.len() as i64 {
-            return false;
-        }
-        match self.prog[self.pc as usize] {
-            Opcode::Snd(a) => output.push_back(self.regs[reg(a)]),
-            Opcode::Set(r, a) => self.regs[reg(r)] = a.val(&self.regs),
-            Opcode::Add(r, a) => self.regs[reg(r)] += a.val(&self.regs),
-            Opcode::Mul(r, a) => self.regs[reg(r)] *= a.val(&self.regs),
-            Opcode::Mod(r, a) => self.regs[reg(r)] %= a.val(&self.regs),
-            Opcode::Rcv(r) => {
-                if let Some(a) = self.input.pop_front() {
-                    self.regs[reg(r)] = a
-                } else {
-                    return false;
-                }
-            }
+    fn exec(&self, cpu: &mut Cpu<Self>) -> Flow {
+        match *self {
+            Opcode::Snd(a) => cpu.output.push_back(cpu.regs[a]),
+            Opcode::Set(r, a) => cpu.regs[r] = a.val(&cpu.regs),
+            Opcode::Add(r, a) => cpu.regs[r] += a.val(&cpu.regs),
+            Opcode::Mul(r, a) => cpu.regs[r] *= a.val(&cpu.regs),
+            Opcode::Mod(r, a) => cpu.regs[r] %= a.val(&cpu.regs),
+            Opcode::Rcv(r) => match cpu.input.pop_front() {
+                Some(a) => cpu.regs[r] = a,
+                None => return Flow::Block,
+            },
             Opcode::Jgz(a, b) => {
-                if a.val(&self.regs) > 0 {
-                    self.pc += b.val(&self.regs);
-                    return true;
+                if a.val(&cpu.regs) > 0 {
+                    return Flow::Jump(b.val(&cpu.regs));
                 }
             }
         }
-        self.pc += 1;
-
-        true
+        Flow::Next
     }
 }
 
 fn main() {
-    let prog: Vec<Opcode> = idm::from_str(&stdin_string()).unwrap();
+    let prog: Vec<Opcode> = stdin_program();
+    let cpu = |id| {
+        let mut cpu = Cpu::new(prog.clone());
+        cpu.regs['p'] = id;
+        cpu
+    };
 
-    let mut a = Cpu::new(0, &prog);
-    // Emulate the bad instruction set in P1.
-    let mut sent = VecDeque::new();
-    while a.can_run() {
-        a.step(&mut sent);
-        if let Opcode::Rcv(r) = a.prog[a.pc as usize] {
-            if a.regs[reg(r)] != 0 {
-                println!("{}", sent.pop_back().unwrap());
-                break;
-            } else {
-                a.pc += 1;
-            }
+    // Emulate the bad instruction set in P1. Rcv with a zero register is a
+    // no-op, so feed it the zero it already has. Break on nonzero rcv.
+    let mut a = cpu(0);
+    a.set_hook(|cpu, op| match *op {
+        Opcode::Rcv(r) if cpu.regs[r] == 0 => {
+            cpu.input.push_back(0);
+            true
         }
-    }
+        Opcode::Rcv(_) => false,
+        _ => true,
+    });
+    a.run_until_blocked();
+    println!("{}", a.output.pop_back().unwrap());
 
     // P2
+    let mut net = Network::new([cpu(0), cpu(1)])
+        .with_channel("a", [0], Link::Direct(1))
+        .with_channel("b", [1], Link::Direct(0));
+    net.run();
+    println!("{}", net.channel("b").sent);
+}
 
-    let mut a = Cpu::new(0, &prog);
-    let mut b = Cpu::new(1, &prog);
-    let mut b_sends = 0;
-    while a.can_run() || b.can_run() {
-        if a.can_run() {
-            a.run(&mut b.input);
-        } else {
-            b_sends += b.run(&mut a.input);
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let text = "\
+set a 1
+add a 2
+mul a a
+mod a 5
+snd a
+set a 0
+rcv a
+jgz a -1
+set a 1
+jgz a -2
+";
+        let prog: Vec<Opcode> = parse_program(text).unwrap();
+        assert_eq!(prog[7], Opcode::Jgz(Operand::Reg('a'), Operand::Int(-1)));
+        assert_eq!(format_program(&prog), text);
     }
-    println!("{b_sends}");
 }
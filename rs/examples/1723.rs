@@ -1,12 +1,14 @@
-// NB. This solution involves a reverse-engineered optimized function from
-// eyeballing the input assembly code. It might not generalize to other
-// inputs.
+use std::{cell::Cell, rc::Rc};
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
-use aoc::{prelude::*, Operand};
+use aoc::{
+    prelude::*,
+    vm::{BinOp, Cpu, Flow, InstructionSet, Micro},
+    Operand,
+};
 
-#[derive(Copy, Clone, Debug, Deserialize)]
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "lowercase")]
 enum Opcode {
     Set(char, Operand),
@@ -15,56 +17,82 @@ enum Opcode {
     Jnz(Operand, Operand),
 }
 
-impl Opcode {
-    /// Return relative PC jump after eval.
-    pub fn eval(&self, regs: &mut [i64]) -> i32 {
-        match self {
-            Opcode::Set(r, a) => regs[reg(*r)] = a.val(regs),
-            Opcode::Sub(r, a) => regs[reg(*r)] -= a.val(regs),
-            Opcode::Mul(r, a) => regs[reg(*r)] *= a.val(regs),
+impl InstructionSet for Opcode {
+    type Word = i64;
+
+    const REGISTERS: usize = 8;
+
+    fn exec(&self, cpu: &mut Cpu<Self>) -> Flow {
+        match *self {
+            Opcode::Set(r, a) => cpu.regs[r] = a.val(&cpu.regs),
+            Opcode::Sub(r, a) => cpu.regs[r] -= a.val(&cpu.regs),
+            Opcode::Mul(r, a) => cpu.regs[r] *= a.val(&cpu.regs),
             Opcode::Jnz(a, b) => {
-                if a.val(regs) != 0 {
-                    return b.val(regs) as i32;
+                if a.val(&cpu.regs) != 0 {
+                    return Flow::Jump(b.val(&cpu.regs));
                 }
             }
         }
-        1
+        Flow::Next
+    }
+
+    fn micro(&self) -> Micro {
+        match *self {
+            Opcode::Set(r, a) => Micro::Set(r, a),
+            Opcode::Sub(r, a) => Micro::apply(r, BinOp::Sub, a),
+            Opcode::Mul(r, a) => Micro::apply(r, BinOp::Mul, a),
+            Opcode::Jnz(a, b) => Micro::Jnz(a, b),
+        }
     }
 }
 
 fn main() {
-    let prog: Vec<Opcode> = idm::from_str(&stdin_string()).unwrap();
+    let prog: Vec<Opcode> = stdin_program();
 
     // P1
     {
-        let mut pc = 0;
-        let mut regs = [0; 8];
-        let mut muls = 0;
-        while (0..prog.len() as i32).contains(&pc) {
-            if matches!(prog[pc as usize], Opcode::Mul(_, _)) {
-                muls += 1;
+        let muls = Rc::new(Cell::new(0));
+        let mut cpu = Cpu::new(prog.clone());
+        cpu.set_hook({
+            let muls = muls.clone();
+            move |_, op| {
+                if matches!(op, Opcode::Mul(_, _)) {
+                    muls.set(muls.get() + 1);
+                }
+                true
             }
-            pc += prog[pc as usize].eval(&mut regs);
-        }
+        });
+        cpu.run();
 
-        println!("{muls}");
+        println!("{}", muls.get());
     }
 
     // P2
     {
-        // Run the start of the program to extract initial parameters.
-        let mut pc = 0;
-        let mut regs = [0; 8];
-        regs[0] = 1;
-        // Once it starts touching registers beyond b and c, stop.
-        while regs[3] == 0 {
-            pc += prog[pc as usize].eval(&mut regs);
-        }
-        let (b, c) = (regs[1], regs[2]);
+        // The program counts composite numbers with a quadratic divisor
+        // search, the loop optimizer solves the search loops.
+        let mut cpu = Cpu::new(prog).with_regs([1]).optimize();
+        cpu.run();
+        println!("{}", cpu.regs['h']);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        // At this point you stare at the assembly code until you figure out
-        // it's running a very ineffective primality test. Then just write out
-        // that:
-        println!("{}", (b..=c).step_by(17).filter(|&a| !is_prime(a)).count());
+    #[test]
+    fn round_trip() {
+        let text = "\
+set b 81
+mul b 100
+sub b -100000
+set f 1
+jnz g 2
+jnz 1 -23
+";
+        let prog: Vec<Opcode> = parse_program(text).unwrap();
+        assert_eq!(prog[2], Opcode::Sub('b', Operand::Int(-100000)));
+        assert_eq!(format_program(&prog), text);
     }
 }
@@ -21,52 +21,43 @@ fn main() {
 
     // Part 2
 
-    todo!("FIXME: Get z3 working again");
-    /*
-    let pos = {
-        // Forbidden technique: SMT Solver no jutsu.
+    println!("{}", closest_best(&bots));
+}
 
-        use z3::{ast::Int, *};
-        let cfg = Config::new();
-        let ctx = Context::new(&cfg);
-        let opt = Optimize::new(&ctx);
+/// Distance to origin from the closest point in range of the most bots.
+fn closest_best(bots: &[(IVec3, i32)]) -> i64 {
+    let balls: Vec<L1Ball<3>> = bots
+        .iter()
+        .map(|&(p, r)| L1Ball::new(p.as_i64vec3(), r as i64))
+        .collect();
 
-        let _x = Int::new_const(&ctx, "x");
-        let _y = Int::new_const(&ctx, "y");
-        let _z = Int::new_const(&ctx, "z");
-        let (x, y, z) = (&_x, &_y, &_z);
+    // The best point can be outside the hull of the bot centers, search the
+    // union of the bot ranges.
+    let bounds = balls
+        .iter()
+        .map(|b| b.bounding_box())
+        .reduce(|a, b| a.union(&b))
+        .unwrap();
 
-        for (vec, r) in &bots {
-            let _cx = ast::Int::from_i64(&ctx, vec.x as i64);
-            let _cy = ast::Int::from_i64(&ctx, vec.y as i64);
-            let _cz = ast::Int::from_i64(&ctx, vec.z as i64);
-            let _r = ast::Int::from_i64(&ctx, *r as i64);
-            let (cx, cy, cz, r) = (&_cx, &_cy, &_cz, &_r);
+    // Index bots by the bounding boxes of their ranges.
+    let index = SpatialIndex::from_boxes(
+        balls.into_iter().map(|b| (b.bounding_box(), b)),
+    );
 
-            // Use `ite` ternary operations to get absolute values.
-            opt.assert_soft(
-                &(x.le(cx).ite(&(cx - x), &(x - cx))
-                    + y.le(cy).ite(&(cy - y), &(y - cy))
-                    + z.le(cz).ite(&(cz - z), &(z - cz)))
-                .le(r),
-                1,
-                None,
-            );
-        }
+    let (pos, _) = index
+        .max_overlap(&bounds, |region, ball| ball.intersects_box(region))
+        .unwrap();
 
-        let SatResult::Sat = opt.check(&[]) else {
-            panic!("Solver failed");
-        };
-        let model = opt.get_model().unwrap();
-        let (x, y, z) = (
-            model.eval(x, true).unwrap().as_i64().unwrap() as i32,
-            model.eval(y, true).unwrap().as_i64().unwrap() as i32,
-            model.eval(z, true).unwrap().as_i64().unwrap() as i32,
-        );
+    Metric::Taxicab.dist(pos, [0; 3])
+}
 
-        ivec3(x, y, z)
-    };
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    println!("{}", pos.taxi_len());
-    */
+    #[test]
+    fn best_point_outside_centers() {
+        let bots = [(ivec3(10, 0, 0), 10), (ivec3(12, 0, 0), 10)];
+        assert_eq!(closest_best(&bots), 2);
+    }
 }
@@ -9,22 +9,13 @@ struct GameState {
 }
 
 impl FromStr for GameState {
-    type Err = ();
+    type Err = ParseError;
 
     fn from_str(input: &str) -> Result<GameState, Self::Err> {
-        let mut decks = [VecDeque::new(), VecDeque::new()];
-        let mut i = 0;
-        for line in input.lines() {
-            if let Ok(num) = line.parse() {
-                decks[i].push_back(num);
-            }
-            if line == "" {
-                i = 1;
-            }
-        }
+        let (a, b): (Vec<u8>, Vec<u8>) = parse_sections(input)?;
 
         Ok(GameState {
-            decks,
+            decks: [a.into(), b.into()],
             mode: Mode::REGULAR,
             seen: HashSet::default(),
         })
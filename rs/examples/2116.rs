@@ -1,9 +1,11 @@
-use aoc::prelude::*;
+use aoc::{
+    parsers::{bits_left, chunked, field, marker, Bits},
+    prelude::*,
+};
 use nom::{
-    bits::complete::{tag, take},
     branch::alt,
     combinator::map_opt,
-    multi::many_till,
+    multi::count,
     sequence::{pair, preceded},
     IResult, Parser,
 };
@@ -63,20 +65,13 @@ enum Op {
 }
 
 /// Parse packets given a buffer length in bits.
-fn length_packets(
-    input: (&[u8], usize),
-) -> IResult<(&[u8], usize), Vec<Packet>> {
-    fn bits((array, offset): (&[u8], usize)) -> usize {
-        array.len() * 8 - offset
-    }
-
-    let (mut input, n_bits): (_, usize) =
-        preceded(tag(0, 1usize), take(15usize))(input)?;
-    //                                                 ^ marker for bit count
-    let target_bits = bits(input) - n_bits;
+fn length_packets(input: Bits) -> IResult<Bits, Vec<Packet>> {
+    // Marker for bit count.
+    let (mut input, n_bits) = preceded(marker(0, 1), field(15))(input)?;
+    let target_bits = bits_left(input) - n_bits as usize;
     let mut ret = Vec::new();
 
-    while bits(input) > target_bits {
+    while bits_left(input) > target_bits {
         let (rest, packet) = packet(input)?;
         input = rest;
         ret.push(packet);
@@ -86,39 +81,13 @@ fn length_packets(
 }
 
 /// Parse packets given a packet count.
-fn count_packets(
-    input: (&[u8], usize),
-) -> IResult<(&[u8], usize), Vec<Packet>> {
-    let (mut input, count): (_, usize) =
-        preceded(tag(1, 1usize), take(11usize))(input)?;
-    //                                                ^ marker for packet count
-    let mut ret = Vec::new();
-
-    for _ in 0..count {
-        let (rest, packet) = packet(input)?;
-        input = rest;
-        ret.push(packet);
-    }
-
-    Ok((input, ret))
+fn count_packets(input: Bits) -> IResult<Bits, Vec<Packet>> {
+    // Marker for packet count.
+    let (input, n) = preceded(marker(1, 1), field(11))(input)?;
+    count(packet, n as usize)(input)
 }
 
-fn packet(input: (&[u8], usize)) -> IResult<(&[u8], usize), Packet> {
-    // Version is a 3-bit integer.
-    let version = take(3usize);
-
-    // Parse 1-prefixed 5-bit chunks until we hit the terminal 0-bit prefixed
-    // chunk. Then merge the non-marker nybbles into the number.
-    let number = many_till(
-        preceded(tag(1, 1usize), take(4usize)),
-        preceded(tag(0, 1usize), take(4usize)),
-    )
-    .map(|(xs, x): (Vec<u8>, u8)| {
-        xs.iter()
-            .chain(std::iter::once(&x))
-            .fold(0u64, |a, &x| (a << 4) + x as u64)
-    });
-
+fn packet(input: Bits) -> IResult<Bits, Packet> {
     // Must be a closure or we hit borrow checker badness.
     let packet_list = || alt((length_packets, count_packets));
 
@@ -143,19 +112,20 @@ fn packet(input: (&[u8], usize)) -> IResult<(&[u8], usize), Packet> {
 
     // Op parsing switchboard.
     let op = alt((
-        preceded(tag(0, 3usize), packet_list()).map(|ps| Op::Sum(ps)),
-        preceded(tag(1, 3usize), packet_list()).map(|ps| Op::Product(ps)),
-        preceded(tag(2, 3usize), packet_list()).map(|ps| Op::Minimum(ps)),
-        preceded(tag(3, 3usize), packet_list()).map(|ps| Op::Maximum(ps)),
-        preceded(tag(4, 3usize), number).map(|n| Op::Literal(n)),
-        preceded(tag(5, 3usize), packet_pair()).map(|(a, b)| Op::Greater(a, b)),
-        preceded(tag(6, 3usize), packet_pair()).map(|(a, b)| Op::Less(a, b)),
-        preceded(tag(7, 3usize), packet_pair()).map(|(a, b)| Op::Equal(a, b)),
-        //           ^ op marker
+        preceded(marker(0, 3), packet_list()).map(Op::Sum),
+        preceded(marker(1, 3), packet_list()).map(Op::Product),
+        preceded(marker(2, 3), packet_list()).map(Op::Minimum),
+        preceded(marker(3, 3), packet_list()).map(Op::Maximum),
+        // Number in 4-bit chunks.
+        preceded(marker(4, 3), chunked(4)).map(Op::Literal),
+        preceded(marker(5, 3), packet_pair()).map(|(a, b)| Op::Greater(a, b)),
+        preceded(marker(6, 3), packet_pair()).map(|(a, b)| Op::Less(a, b)),
+        preceded(marker(7, 3), packet_pair()).map(|(a, b)| Op::Equal(a, b)),
+        //                 ^ op marker
     ));
 
-    // Bring it all together into a packet.
-    pair(version, op)(input).map(|(a, (x, y))| (a, Packet::new(x, y)))
+    // Bring it all together into a packet, version is a 3-bit integer.
+    pair(field(3), op)(input).map(|(a, (x, y))| (a, Packet::new(x as u8, y)))
 }
 
 fn parse(input: &[u8]) -> IResult<&[u8], Packet> {
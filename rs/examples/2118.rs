@@ -0,0 +1,33 @@
+use aoc::prelude::*;
+
+type Snail = Nested<u32>;
+
+fn add(a: &Snail, b: &Snail) -> Snail {
+    let mut ret = Nested::List(vec![a.clone(), b.clone()]);
+    ret.reduce(4, 10);
+    ret
+}
+
+fn magnitude(n: &Snail) -> u32 {
+    match n {
+        Nested::Atom(a) => *a,
+        Nested::List(a) => 3 * magnitude(&a[0]) + 2 * magnitude(&a[1]),
+    }
+}
+
+fn main() {
+    let input: Vec<Snail> = stdin_lines_as().collect();
+
+    let sum = input[1..].iter().fold(input[0].clone(), |a, b| add(&a, b));
+    println!("{}", magnitude(&sum));
+
+    let mut best = 0;
+    for (i, a) in input.iter().enumerate() {
+        for (j, b) in input.iter().enumerate() {
+            if i != j {
+                best = best.max(magnitude(&add(a, b)));
+            }
+        }
+    }
+    println!("{best}");
+}
@@ -1,4 +1,9 @@
-use aoc::prelude::*;
+use aoc::{
+    numeral,
+    prelude::*,
+    vm::{self, BinOp, Cpu, Flow, InstructionSet, Micro},
+    Operand,
+};
 use std::fmt;
 
 // Length of digit-handling chunk in code.
@@ -9,21 +14,11 @@ const N_DIGITS: usize = 14;
 type Params = [[i64; 3]; N_DIGITS];
 
 fn explode(n: i64) -> Vec<i64> {
-    format!("{}", n)
-        .chars()
-        .rev()
-        .map(|c| c.to_digit(10).unwrap() as i64)
-        .collect()
+    numeral::digits(n, 10).collect()
 }
 
-fn implode(digits: &Vec<i64>) -> i64 {
-    digits
-        .iter()
-        .rev()
-        .map(|c| char::from_digit(*c as u32, 10).unwrap())
-        .collect::<String>()
-        .parse::<i64>()
-        .unwrap()
+fn implode(digits: &[i64]) -> i64 {
+    numeral::from_digits(digits.iter().copied(), 10)
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -60,8 +55,13 @@ impl fmt::Display for Var {
 }
 
 impl Var {
-    fn eval(self, mem: &[i64; 4]) -> i64 {
-        mem[self as usize]
+    fn name(self) -> char {
+        match self {
+            Var::X => 'x',
+            Var::Y => 'y',
+            Var::Z => 'z',
+            Var::W => 'w',
+        }
     }
 }
 
@@ -96,10 +96,19 @@ impl fmt::Display for Val {
 }
 
 impl Val {
-    fn eval(self, mem: &[i64; 4]) -> i64 {
+    fn eval(self, mem: &[i64]) -> i64 {
         match self {
             Lit(n) => n,
-            Ptr(p) => p.eval(mem),
+            Ptr(p) => mem[p as usize],
+        }
+    }
+}
+
+impl From<Val> for Operand {
+    fn from(val: Val) -> Self {
+        match val {
+            Lit(n) => Operand::Int(n),
+            Ptr(p) => Operand::Reg(p.name()),
         }
     }
 }
@@ -131,23 +140,46 @@ impl FromStr for Op {
     }
 }
 
-impl Op {
-    fn eval(self, mem: &mut [i64; 4], input: &mut Vec<i64>) {
-        match self {
-            Inp(v) => mem[v as usize] = input.pop().expect("Out of input!"),
-            Add(v, n) => mem[v as usize] = v.eval(mem) + n.eval(mem),
-            Mul(v, n) => mem[v as usize] = v.eval(mem) * n.eval(mem),
+impl InstructionSet for Op {
+    type Word = i64;
+
+    const REGISTERS: usize = 4;
+
+    fn exec(&self, cpu: &mut Cpu<Self>) -> Flow {
+        let mem = &mut cpu.regs;
+        match *self {
+            Inp(v) => match cpu.input.pop_front() {
+                Some(n) => mem[v as usize] = n,
+                None => return Flow::Block,
+            },
+            Add(v, n) => mem[v as usize] += n.eval(mem),
+            Mul(v, n) => mem[v as usize] *= n.eval(mem),
             Div(v, n) => {
                 debug_assert!(n.eval(mem) != 0);
-                mem[v as usize] = v.eval(mem) / n.eval(mem);
+                mem[v as usize] /= n.eval(mem);
             }
             Mod(v, n) => {
-                debug_assert!(v.eval(mem) >= 0);
+                debug_assert!(mem[v as usize] >= 0);
                 debug_assert!(n.eval(mem) > 0);
-                mem[v as usize] = v.eval(mem) % n.eval(mem);
+                mem[v as usize] %= n.eval(mem);
+            }
+            Eql(v, n) => {
+                mem[v as usize] = (mem[v as usize] == n.eval(mem)) as i64
             }
-            Eql(v, n) => mem[v as usize] = (v.eval(mem) == n.eval(mem)) as i64,
         }
+        Flow::Next
+    }
+
+    fn micro(&self) -> Micro {
+        let (v, op, n) = match *self {
+            Inp(v) => return Micro::Input(v.name()),
+            Add(v, n) => (v, BinOp::Add, n),
+            Mul(v, n) => (v, BinOp::Mul, n),
+            Div(v, n) => (v, BinOp::Div, n),
+            Mod(v, n) => (v, BinOp::Mod, n),
+            Eql(v, n) => (v, BinOp::Eql, n),
+        };
+        Micro::apply(v.name(), op, n.into())
     }
 }
 
@@ -190,7 +222,7 @@ fn jet(params: &Params, mut input: Vec<i64>) -> i64 {
 }
 
 /// Extract differing parameters from pattern picked up using
-/// `per_digit_printout`.
+/// `print_pseudocode`.
 fn extract_params(prog: &Vec<Op>) -> Result<Params, ()> {
     assert!(prog.len() == CHUNK_LEN * N_DIGITS);
     let mut ret: [[i64; 3]; 14] = Default::default();
@@ -287,25 +319,14 @@ fn main() {
     }
 }
 
-/// Print out the mostly-same columns.
+/// Print out the program as pseudocode, one chunk per digit.
 #[allow(dead_code)]
-fn per_digit_printout(prog: &Vec<Op>) {
-    let mut bins: [Vec<Op>; 14] = Default::default();
-    let mut current_bin = 0;
-    for &op in prog.iter().skip(1) {
-        if matches!(op, Inp(_)) {
-            current_bin += 1;
-            continue;
-        }
-        bins[current_bin].push(op);
-    }
-
-    for row in 0..17 {
-        for bin in &bins {
-            let s = format!("{}", bin[row]);
-            eprint!("{:<10}", s);
-        }
-        eprintln!();
+fn print_pseudocode(prog: &[Op]) {
+    for chunk in prog.chunks(CHUNK_LEN) {
+        let code = vm::Decompiler::new(chunk)
+            .with_regs([('w', 0), ('x', 0), ('y', 0)])
+            .with_input_range(1..=9);
+        eprintln!("{code}");
     }
 }
 
@@ -343,12 +364,10 @@ fn model_numbers(params: &Params) -> impl Iterator<Item = Vec<i64>> + '_ {
 }
 
 #[allow(dead_code)]
-fn run(prog: &Vec<Op>, mut input: Vec<i64>) -> i64 {
-    let mut mem = [0i64; 4];
-    for op in prog {
-        op.eval(&mut mem, &mut input);
-    }
-    mem[2]
+fn run(prog: &[Op], input: Vec<i64>) -> i64 {
+    let mut cpu = Cpu::new(prog).with_input(input.into_iter().rev());
+    cpu.run();
+    cpu.regs[Var::Z as usize]
 }
 
 #[cfg(test)]
@@ -380,4 +399,36 @@ mod tests {
             assert_eq!(jet(&params, n.clone()), run(&prog, n.clone()));
         }
     }
+
+    #[test]
+    fn pseudocode_matches_params() {
+        let prog = prog();
+        for (chunk, [a, b, c]) in prog.chunks(CHUNK_LEN).zip(params()) {
+            let code = vm::Decompiler::new(chunk)
+                .with_regs([('w', 0), ('x', 0), ('y', 0)])
+                .with_input_range(1..=9)
+                .to_string();
+            let z = if a == 1 { "z" } else { "z / 26" };
+            let b = if b < 0 {
+                format!("- {}", -b)
+            } else {
+                format!("+ {b}")
+            };
+            assert_eq!(
+                code,
+                format!(
+                    "\
+L0:
+    in0 = input()
+    t0 = z % 26 {b} != in0
+    t1 = (in0 + {c}) * t0
+    w = in0
+    x = t0
+    y = t1
+    z = {z} * (x * 25 + 1) + y
+"
+                )
+            );
+        }
+    }
 }
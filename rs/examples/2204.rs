@@ -1,21 +1,9 @@
-use std::convert::TryInto;
-
 use aoc::prelude::*;
-use regex::Regex;
 
 fn main() {
-    // Can't use prelude's fixed_numbers because the hyphens confuse it.
-    let number = Regex::new(r"\d+").unwrap();
-
+    // Hyphens are range separators, not minus signs.
     let pairs: Vec<[u32; 4]> = stdin_lines()
-        .map(|line| {
-            number
-                .find_iter(&line)
-                .map(|s| s.as_str().parse().unwrap())
-                .collect::<Vec<u32>>()
-                .try_into()
-                .unwrap()
-        })
+        .map(|line| Numbers::unsigned().fixed(&line).unwrap())
         .collect();
 
     println!(
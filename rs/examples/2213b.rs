@@ -1,16 +1,6 @@
-use std::cmp::Ordering;
-
 use aoc::prelude::*;
-use serde::Deserialize;
-
-#[derive(Clone, Deserialize)]
-#[serde(untagged)]
-enum Item {
-    Atom(u32),
-    List(Vec<Item>),
-}
 
-use Item::*;
+type Item = Nested<u32>;
 
 fn main() {
     let mut packets = vec![Vec::new()];
@@ -18,7 +8,7 @@ fn main() {
         if item.trim().is_empty() {
             packets.push(Vec::new());
         } else {
-            let item = serde_json::from_str(&item).unwrap();
+            let item: Item = serde_json::from_str(&item).unwrap();
             let i = packets.len() - 1;
             packets[i].push(item);
         }
@@ -43,8 +33,8 @@ fn main() {
 
     // Part 2
 
-    let p2: Item = serde_json::from_str(&"[[2]]").unwrap();
-    let p6: Item = serde_json::from_str(&"[[6]]").unwrap();
+    let p2: Item = serde_json::from_str("[[2]]").unwrap();
+    let p6: Item = serde_json::from_str("[[6]]").unwrap();
 
     let mut list = vec![p2.clone(), p6.clone()];
 
@@ -62,41 +52,3 @@ fn main() {
             .product::<usize>()
     );
 }
-
-impl Ord for Item {
-    fn cmp(&self, other: &Self) -> Ordering {
-        let mut atom_list = Vec::new();
-
-        // Convert to slices, using atom_list as scratch space for atomic
-        // items.
-        let (a, b): (&[Item], &[Item]) = match (self, other) {
-            (Atom(a), Atom(b)) => return a.cmp(b), // Early exit
-            (a @ Atom(_), List(b)) => {
-                atom_list.push(a.clone());
-                (atom_list.as_slice(), b.as_slice())
-            }
-            (List(a), b @ Atom(_)) => {
-                atom_list.push(b.clone());
-                (a.as_slice(), atom_list.as_slice())
-            }
-            (List(a), List(b)) => (a.as_slice(), b.as_slice()),
-        };
-
-        // Built-in lexical comparison for slices can take it from here.
-        a.cmp(b)
-    }
-}
-
-impl PartialOrd for Item {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(other))
-    }
-}
-
-impl PartialEq for Item {
-    fn eq(&self, other: &Self) -> bool {
-        self.cmp(other) == Ordering::Equal
-    }
-}
-
-impl Eq for Item {}
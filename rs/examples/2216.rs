@@ -2,18 +2,22 @@ use std::collections::BTreeMap;
 
 use aoc::prelude::*;
 
+#[derive(RegexParse)]
+#[re(r"^Valve (.*) has flow rate=(\d+); tunnels? leads? to valves? (.*)$")]
+struct Valve {
+    name: String,
+    rate: usize,
+    outs: String,
+}
+
 fn main() {
-    let parser = re_parser::<(String, usize, String)>(
-        r"^Valve (.*) has flow rate=(\d+); tunnels? leads? to valves? (.*)$",
-    );
-    let mut input = Vec::new();
-    for line in stdin_lines() {
-        let (valve, rate, outs) = parser(&line).unwrap();
-        let outs: Vec<String> =
-            outs.split(", ").map(|a| a.to_owned()).collect();
-
-        input.push((valve, rate, outs));
-    }
+    let input: Vec<_> = stdin_lines_as::<Valve>()
+        .map(|v| {
+            let outs: Vec<String> =
+                v.outs.split(", ").map(|a| a.to_owned()).collect();
+            (v.name, v.rate, outs)
+        })
+        .collect();
 
     // Rates of live valves.
     let raw_rates: BTreeMap<_, _> = input
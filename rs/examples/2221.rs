@@ -40,7 +40,8 @@ impl<T: FromStr> FromStr for Eqn<T> {
         if let Ok(n) = s.parse::<i64>() {
             Ok(N(n))
         } else {
-            let (a, op, b) = <(T, char, T) as RegexParseable>::parse(&EQN, s)?;
+            let (a, op, b) = <(T, char, T) as RegexParseable>::parse(&EQN, s)
+                .map_err(|_| ())?;
             Ok(Op(op, a, b))
         }
     }
@@ -103,25 +103,21 @@ fn apply_map(set: &mut Vec<Range<i64>>, map: &[(Range<i64>, i64)]) {
     }
 }
 
-fn main() {
-    let mut seeds: Vec<i64> = Vec::new();
+fn main() -> anyhow::Result<()> {
+    let input = stdin_string();
+    let sections = split_sections(&input);
+    let seeds: Vec<i64> = numbers(sections[0].body);
 
     // (input range, delta)
+    // Maps are always in same order, don't bother with headers.
     let mut maps: Vec<Vec<(Range<i64>, i64)>> = Vec::new();
-
-    for line in stdin_lines() {
-        if line.trim().is_empty() {
-            continue;
-        } else if line.contains("seeds") {
-            seeds = numbers(line);
-        } else if line.contains(':') {
-            // Maps are always in same order, don't bother with labels.
-            maps.push(Vec::new())
-        } else {
-            let n = maps.len() - 1;
-            let [a, b, len] = fixed_numbers::<i64, 3>(line);
-            maps[n].push((b..(b + len), (a - b)));
-        }
+    for s in &sections[1..] {
+        let Rows(map): Rows<Vec<[i64; 3]>> = s.parse()?;
+        maps.push(
+            map.into_iter()
+                .map(|[a, b, len]| (b..(b + len), (a - b)))
+                .collect(),
+        );
     }
 
     let mut p1: Vec<Range<i64>> = seeds.iter().map(|&a| a..(a + 1)).collect();
@@ -137,4 +133,5 @@ fn main() {
         }
         println!("{}", set[0].start);
     }
+    Ok(())
 }
@@ -1,5 +1,4 @@
 use aoc::prelude::*;
-use glam::{i64vec2, I64Vec2};
 
 fn survey(ops: &[(i64, I64Vec2)]) -> i64 {
     let mut left = Vec::new();
@@ -46,31 +45,31 @@ fn main() {
     let mut p1 = Vec::new();
     let mut p2 = Vec::new();
 
-    for (dir1, n1, n2, dir2) in parsed_stdin_lines::<(char, i64, String, String)>(
-        r"(.*) (.*) \(#(.....)(.)\)",
-    ) {
+    // The color is a hex number with the distance in the first five digits
+    // and the direction in the last one.
+    let hex = Numbers::unsigned().with_prefixes();
+    for line in stdin_lines() {
+        let [n1, color] = hex.fixed::<i64, 2>(&line).unwrap();
         p1.push((
             n1,
-            match dir1 {
+            match line.chars().next().unwrap() {
                 'U' => DIR_4[UP],
                 'L' => DIR_4[LEFT],
                 'R' => DIR_4[RIGHT],
                 'D' => DIR_4[DOWN],
                 _ => panic!(),
-            }
-            .as_i64vec2(),
+            },
         ));
 
         p2.push((
-            i64::from_str_radix(&n2, 16).unwrap(),
-            match u8::from_str_radix(&dir2, 16).unwrap() {
+            color >> 4,
+            match color & 0xf {
                 0 => DIR_4[RIGHT],
                 1 => DIR_4[DOWN],
                 2 => DIR_4[LEFT],
                 3 => DIR_4[UP],
                 _ => panic!(),
-            }
-            .as_i64vec2(),
+            },
         ));
     }
 
@@ -8,9 +8,11 @@ fn rim(
 ) -> usize {
     bfs(
         |&p| {
-            neighbors_4(p).filter(|&p| {
-                !blocks.contains(&bounds.get(bounds.idx(p)).into())
-            })
+            neighbors_4(p.as_i64vec2())
+                .map(|p| p.as_ivec2())
+                .filter(|&p| {
+                    !blocks.contains(&bounds.get(bounds.idx(p)).into())
+                })
         },
         &start,
     )
@@ -48,12 +50,7 @@ fn main() {
         }
     }
 
-    let n = if bounds.width() == 11 {
-        // Example
-        6
-    } else {
-        64
-    };
+    let n = param("steps", 64);
 
     println!("{}", area(&bounds, &blocks, start, n));
 
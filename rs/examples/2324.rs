@@ -80,16 +80,9 @@ fn main() {
     let ps_flat = ps.iter().map(|&a| a * FLAT).collect::<Vec<_>>();
     let vs_flat = vs.iter().map(|&a| a * FLAT).collect::<Vec<_>>();
 
-    let is_example = ps.len() == 5;
-
-    let bounds = if is_example {
-        Rect::new([7.0, 7.0], [27.0, 27.0])
-    } else {
-        Rect::new(
-            [200000000000000.0, 200000000000000.0],
-            [400000000000000.0, 400000000000000.0],
-        )
-    };
+    let min = param("min", 200000000000000.0);
+    let max = param("max", 400000000000000.0);
+    let bounds = Rect::new([min, min], [max, max]);
 
     let mut p1 = 0;
 
@@ -113,12 +106,16 @@ fn main() {
 
     const N: i64 = 300;
 
-    for [x, y] in Rect::new([-N, -N], [N, N]) {
+    // Search outwards from zero velocity, small velocities are more likely.
+    for [x, y] in Metric::Chessboard
+        .points([0, 0])
+        .take_while(|&p| Metric::Chessboard.dist(p, [0, 0]) < N)
+    {
         // Filter out invalid 2D slices to narrow search space.
         if solve(&ps_flat, &vs_flat, i64vec3(x, y, 0)).is_none() {
             continue;
         }
-        for z in -N..N {
+        for [z] in Metric::Taxicab.points([0]).take_while(|&[z]| z.abs() < N) {
             let v = i64vec3(x, y, z);
             if let Some(p) = solve(&ps, &vs, v) {
                 println!("{}", p.x + p.y + p.z);
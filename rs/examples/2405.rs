@@ -21,18 +21,10 @@ fn fix(seq: &[i32], rules: &[[i32; 2]]) -> Vec<i32> {
     }
 }
 
-fn main() {
-    let mut iter = stdin_lines();
-
-    let mut rules: Vec<[i32; 2]> = Vec::new();
-    for line in iter.by_ref().take_while(|a| !a.is_empty()) {
-        rules.push(fixed_numbers(line));
-    }
-
-    let mut updates: Vec<Vec<i32>> = Vec::new();
-    for line in iter {
-        updates.push(numbers(line));
-    }
+fn main() -> anyhow::Result<()> {
+    type Rules = Rows<Vec<[i32; 2]>>;
+    let (Rows(rules), Rows(updates)): (Rules, Rows<Vec<Vec<i32>>>) =
+        sections()?;
 
     let mut p1 = 0;
     let mut p2 = 0;
@@ -49,4 +41,5 @@ fn main() {
 
     println!("{p1}");
     println!("{p2}");
+    Ok(())
 }
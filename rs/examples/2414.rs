@@ -8,13 +8,7 @@ fn main() {
         vs.push(ivec2(vx, vy));
     }
 
-    let mut bounds = area(101, 103);
-
-    // Example override
-    let is_example = ps.len() == 12;
-    if is_example {
-        bounds = area(11, 7);
-    }
+    let bounds = area(param("width", 101), param("height", 103));
 
     let mut scores = HashMap::default();
     for i in 0..ps.len() {
@@ -1,82 +1,108 @@
-use aoc::prelude::*;
-
-const ADV: u64 = 0;
-const BXL: u64 = 1;
-const BST: u64 = 2;
-const JNZ: u64 = 3;
-const BCX: u64 = 4;
-const OUT: u64 = 5;
-const BDV: u64 = 6;
-const CDV: u64 = 7;
-
-#[derive(Clone, Debug)]
-struct Machine {
-    a: u64,
-    b: u64,
-    c: u64,
-    pc: usize,
-    prog: Vec<u64>,
-    pub stdout: Vec<u64>,
+use aoc::{
+    prelude::*,
+    vm::{bit_search, BinOp, Cpu, Flow, InstructionSet, Micro},
+    Operand::{self, Int, Reg},
+};
+
+#[derive(Copy, Clone, Debug)]
+enum Opcode {
+    Adv(Operand),
+    Bxl(i64),
+    Bst(Operand),
+    /// Jump target is an instruction index, not a word index.
+    Jnz(usize),
+    Bxc,
+    Out(Operand),
+    Bdv(Operand),
+    Cdv(Operand),
 }
 
-impl Machine {
-    fn combo(&self, op: u64) -> u64 {
-        match op {
-            4 => self.a,
-            5 => self.b,
-            6 => self.c,
-            7 => panic!(),
-            x => x,
-        }
+use Opcode::*;
+
+impl Opcode {
+    fn new(op: i64, arg: i64) -> Result<Self, &'static str> {
+        let combo = match arg {
+            0..=3 => Ok(Int(arg)),
+            4 => Ok(Reg('a')),
+            5 => Ok(Reg('b')),
+            6 => Ok(Reg('c')),
+            _ => Err("bad combo operand"),
+        };
+        Ok(match op {
+            0 => Adv(combo?),
+            1 => Bxl(arg),
+            2 => Bst(combo?),
+            3 => Jnz(arg as usize / 2),
+            4 => Bxc,
+            5 => Out(combo?),
+            6 => Bdv(combo?),
+            7 => Cdv(combo?),
+            _ => return Err("bad opcode"),
+        })
     }
+}
 
-    fn step(&mut self) -> bool {
-        if self.pc >= self.prog.len() {
-            return false;
-        }
+impl InstructionSet for Opcode {
+    type Word = i64;
 
-        let arg = self.prog[self.pc + 1];
-        match self.prog[self.pc] {
-            ADV => {
-                //self.a /= 1 << self.combo(arg);
-                self.a >>= self.combo(arg);
-                self.pc += 2;
-            }
-            BXL => {
-                self.b ^= arg;
-                self.pc += 2;
-            }
-            BST => {
-                self.b = self.combo(arg) & 7;
-                self.pc += 2;
-            }
-            JNZ => {
-                if self.a != 0 {
-                    self.pc = arg as usize;
-                } else {
-                    self.pc += 2;
+    const REGISTERS: usize = 3;
+
+    fn exec(&self, cpu: &mut Cpu<Self>) -> Flow {
+        let regs = &mut cpu.regs;
+        match *self {
+            Adv(x) => regs['a'] >>= x.val(regs),
+            Bxl(n) => regs['b'] ^= n,
+            Bst(x) => regs['b'] = x.val(regs) & 7,
+            Jnz(t) => {
+                if regs['a'] != 0 {
+                    return Flow::Goto(t as i64);
                 }
             }
-            BCX => {
-                self.b ^= self.c;
-                self.pc += 2;
-            }
-            OUT => {
-                self.stdout.push(self.combo(arg) & 7);
-                self.pc += 2;
-            }
-            BDV => {
-                self.b = self.a / (1 << self.combo(arg));
-                self.pc += 2;
-            }
-            CDV => {
-                self.c = self.a / (1 << self.combo(arg));
-                self.pc += 2;
-            }
+            Bxc => regs['b'] ^= regs['c'],
+            Out(x) => cpu.output.push_back(x.val(regs) & 7),
+            Bdv(x) => regs['b'] = regs['a'] >> x.val(regs),
+            Cdv(x) => regs['c'] = regs['a'] >> x.val(regs),
+        }
+        Flow::Next
+    }
 
-            _ => panic!(),
+    fn micro(&self) -> Micro {
+        match *self {
+            Adv(x) => Micro::apply('a', BinOp::Shr, x),
+            Bxl(n) => Micro::apply('b', BinOp::Xor, Int(n)),
+            Bst(x) => Micro::Bin('b', BinOp::And, x, Int(7)),
+            Jnz(t) => Micro::Goto(Reg('a'), t),
+            Bxc => Micro::apply('b', BinOp::Xor, Reg('c')),
+            // Output is masked, which can't be expressed as a micro-op.
+            Out(_) => Micro::Opaque,
+            Bdv(x) => Micro::Bin('b', BinOp::Shr, Reg('a'), x),
+            Cdv(x) => Micro::Bin('c', BinOp::Shr, Reg('a'), x),
         }
-        true
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Machine {
+    regs: [i64; 3],
+    prog: Vec<Opcode>,
+    /// The program as numbers.
+    code: Vec<i64>,
+}
+
+impl Machine {
+    /// Run with register a set to the given value and return the output.
+    fn run(&self, a: i64) -> Vec<i64> {
+        let [_, b, c] = self.regs;
+        let mut cpu = Cpu::new(self.prog.clone()).with_regs([a, b, c]);
+        cpu.run();
+        cpu.output.into()
+    }
+
+    /// Output of the first round with register a set to the given value.
+    fn first_output(&self, a: i64) -> i64 {
+        let [_, b, c] = self.regs;
+        let mut cpu = Cpu::new(self.prog.clone()).with_regs([a, b, c]);
+        cpu.next_output().expect("no output")
     }
 }
 
@@ -86,18 +112,22 @@ impl FromStr for Machine {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let mut lines = s.lines();
 
-        let a = fixed_numbers::<u64, 1>(lines.next().ok_or("err")?)[0];
-        let b = fixed_numbers::<u64, 1>(lines.next().ok_or("err")?)[0];
-        let c = fixed_numbers::<u64, 1>(lines.next().ok_or("err")?)[0];
+        let a = fixed_numbers::<i64, 1>(lines.next().ok_or("err")?)[0];
+        let b = fixed_numbers::<i64, 1>(lines.next().ok_or("err")?)[0];
+        let c = fixed_numbers::<i64, 1>(lines.next().ok_or("err")?)[0];
         lines.next().ok_or("err")?;
-        let prog: Vec<u64> = numbers(lines.next().ok_or("err")?);
+        let code: Vec<i64> = numbers(lines.next().ok_or("err")?);
+        let prog = code
+            .chunks(2)
+            .map(|p| match p {
+                [op, arg] => Opcode::new(*op, *arg),
+                _ => Err("odd program length"),
+            })
+            .collect::<Result<_, _>>()?;
         Ok(Machine {
-            a,
-            b,
-            c,
-            pc: 0,
+            regs: [a, b, c],
             prog,
-            stdout: Default::default(),
+            code,
         })
     }
 }
@@ -105,82 +135,25 @@ impl FromStr for Machine {
 fn main() {
     let machine: Machine = stdin_string().parse().unwrap();
 
-    let mut p1 = machine.clone();
-    while p1.step() {}
     println!(
         "{}",
-        p1.stdout
+        machine
+            .run(machine.regs[0])
             .iter()
             .map(|a| a.to_string())
             .collect::<Vec<_>>()
             .join(",")
     );
 
-    // Special case for the example, we can just brute-force P2
-    if machine.a == 2024 {
-        for a in 0.. {
-            let mut p2 = machine.clone();
-            p2.a = a;
-
-            while p2.step() {}
-            if p2.stdout == machine.prog {
-                println!("{a}");
-                return;
-            }
-        }
-    }
-
-    // P2 solution is custom made for main input, don't try it on examples.
-    if machine.a == 729 {
+    // P2: Register a is shifted right by an octal every round and each
+    // output only depends on the octals left in a, so the input can be found
+    // with a bit search. This only works for programs that do the shift.
+    if !machine.prog.iter().any(|op| matches!(op, Adv(Int(3)))) {
         return;
     }
 
-    // Basic idea for P2: Register a is shifted right by an octal every round.
-    // The last output octal only depends on the highest octal in a (only
-    // thing left by then), but the previous ones are mangled with the higher
-    // octals. Keep looking for good high octals that produce the correct
-    // output suffix, and backtrack to increment them when it's not possible
-    // to proceed with the current suffix.
-
-    // Reversed octals for a.
-    let mut octals = vec![0];
-
-    loop {
-        // We failed with current branch, backtrack.
-        if octals[octals.len() - 1] == 8 {
-            octals.pop();
-            let n = octals.len() - 1;
-            octals[n] += 1;
-            continue;
-        }
-
-        let a = octals.iter().fold(0, |acc, n| (acc << 3) + n);
-        let mut p2 = machine.clone();
-        p2.a = a;
-        while p2.step() {}
-
-        let shared_suffix = p2
-            .stdout
-            .iter()
-            .rev()
-            .zip(machine.prog.iter().rev())
-            .take_while(|(a, b)| a == b)
-            .count();
-        match shared_suffix {
-            x if x == machine.prog.len() => {
-                // Solution found.
-                println!("{a}");
-                break;
-            }
-            x if x >= octals.len() => {
-                // Entire local suffix matches, go deeper.
-                octals.push(0);
-            }
-            _ => {
-                // Mismatch, increment top item.
-                let n = octals.len() - 1;
-                octals[n] += 1;
-            }
-        }
-    }
+    let a = bit_search(3, &machine.code, |a| machine.first_output(a as i64))
+        .find(|&a| machine.run(a as i64) == machine.code)
+        .expect("no solution");
+    println!("{a}");
 }
@@ -10,7 +10,9 @@ fn path(mem: &[IVec2], bounds: &Rect<i32>, t: usize) -> Option<Vec<IVec2>> {
         .collect::<HashSet<IVec2>>();
 
     grid_astar(&start, &end, |&pos| {
-        neighbors_4(pos).filter(|&p| bounds.contains(p) && !blocks.contains(&p))
+        neighbors_4(pos.as_i64vec2())
+            .map(|p| p.as_ivec2())
+            .filter(|&p| bounds.contains(p) && !blocks.contains(&p))
     })
 }
 
@@ -18,10 +20,9 @@ fn main() {
     let input: Vec<IVec2> = stdin_lines()
         .map(|a| IVec2::from(fixed_numbers(a)))
         .collect();
-    let bounds = Rect::from_points_inclusive(input.iter().copied());
-
-    // Example vs main input.
-    let p1_t = if input.len() == 25 { 12 } else { 1024 };
+    let size = param("size", 71);
+    let bounds = area(size, size);
+    let p1_t = param("bytes", 1024);
 
     println!("{}", path(&input, &bounds, p1_t).unwrap().len() - 1);
 
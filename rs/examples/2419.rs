@@ -39,11 +39,9 @@ impl Builder {
     }
 }
 
-fn main() {
-    let mut lines = stdin_lines();
-    let mut builder: Builder = lines.next().unwrap().parse().unwrap();
-    lines.next().unwrap();
-    let designs: Vec<String> = lines.map(|a| a.to_string()).collect();
+fn main() -> anyhow::Result<()> {
+    let (towels, designs): (String, Vec<String>) = sections()?;
+    let mut builder: Builder = towels.parse().unwrap();
 
     println!(
         "{}",
@@ -53,4 +51,5 @@ fn main() {
         "{}",
         designs.iter().map(|d| builder.designs(d)).sum::<usize>()
     );
+    Ok(())
 }
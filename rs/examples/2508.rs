@@ -5,9 +5,7 @@ use aoc::prelude::*;
 fn main() {
     let input: Vec<I64Vec3> =
         stdin_lines().map(|a| fixed_numbers(a).into()).collect();
-    let is_example = input.len() < 100;
-    // Different behavior for example and real input.
-    let num_connections = if is_example { 10 } else { 1000 };
+    let num_connections = param("connections", 1000);
 
     // Enumerate connections by ascending distance.
     let mut pairs = BTreeSet::new();
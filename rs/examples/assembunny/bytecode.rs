@@ -0,0 +1,162 @@
+//! Assembunny compiled into a dense instruction array for fast
+//! interpretation.
+
+use aoc::{prelude::reg, Operand::*};
+
+use super::Opcode;
+
+/// Instruction with register indices and jump targets resolved.
+#[derive(Copy, Clone, Debug)]
+enum Code {
+    Nop,
+    Set(usize, i64),
+    Copy(usize, usize),
+    Inc(usize),
+    Dec(usize),
+    /// Absolute jump.
+    Jmp(i64),
+    /// Absolute jump if register is nonzero.
+    Jnz(usize, i64),
+    /// Run the original instruction. Used for toggles, output and jumps
+    /// with computed offsets.
+    Slow,
+}
+
+impl Code {
+    fn new(op: Opcode, pc: usize) -> Self {
+        let pc = pc as i64;
+        match op {
+            Opcode::Cpy(Int(a), Reg(b)) => Code::Set(reg(b), a),
+            Opcode::Cpy(Reg(a), Reg(b)) => Code::Copy(reg(a), reg(b)),
+            // Invalid instruction made by a toggle.
+            Opcode::Cpy(_, Int(_) | Mem(_)) => Code::Nop,
+            Opcode::Inc(a) => Code::Inc(reg(a)),
+            Opcode::Dec(a) => Code::Dec(reg(a)),
+            Opcode::Jnz(Int(0), _) => Code::Nop,
+            Opcode::Jnz(Int(_), Int(b)) => Code::Jmp(pc + b),
+            Opcode::Jnz(Reg(a), Int(b)) => Code::Jnz(reg(a), pc + b),
+            // Computed jumps, toggles and output.
+            _ => Code::Slow,
+        }
+    }
+}
+
+/// Program compiled for the fast interpreter.
+///
+/// Toggled instructions get recompiled as the program runs.
+#[derive(Clone, Debug)]
+pub struct Compiled {
+    prog: Vec<Opcode>,
+    code: Vec<Code>,
+    pub output: Vec<i64>,
+    /// Number of instructions executed.
+    pub steps: u64,
+}
+
+impl Compiled {
+    pub fn new(prog: Vec<Opcode>) -> Self {
+        let code = prog
+            .iter()
+            .enumerate()
+            .map(|(pc, &op)| Code::new(op, pc))
+            .collect();
+        Compiled {
+            prog,
+            code,
+            output: Vec::new(),
+            steps: 0,
+        }
+    }
+
+    /// Current program, including any toggles.
+    pub fn prog(&self) -> &[Opcode] {
+        &self.prog
+    }
+
+    /// Run the program until it halts.
+    pub fn run(&mut self, regs: &mut [i64; 4]) {
+        let mut pc = 0i64;
+        // Negative pc wraps around to a huge index and halts too.
+        while let Some(&code) = self.code.get(pc as usize) {
+            self.steps += 1;
+            match code {
+                Code::Nop => {}
+                Code::Set(r, x) => regs[r] = x,
+                Code::Copy(a, b) => regs[b] = regs[a],
+                Code::Inc(r) => regs[r] += 1,
+                Code::Dec(r) => regs[r] -= 1,
+                Code::Jmp(t) => {
+                    pc = t;
+                    continue;
+                }
+                Code::Jnz(r, t) => {
+                    if regs[r] != 0 {
+                        pc = t;
+                        continue;
+                    }
+                }
+                Code::Slow => {
+                    pc = self.slow(pc, regs);
+                    continue;
+                }
+            }
+            pc += 1;
+        }
+    }
+
+    /// Execute an uncompiled instruction and return the next pc.
+    fn slow(&mut self, pc: i64, regs: &mut [i64; 4]) -> i64 {
+        match self.prog[pc as usize] {
+            Opcode::Cpy(a, Reg(b)) => regs[reg(b)] = a.val(regs),
+            Opcode::Jnz(a, b) if a.val(regs) != 0 => return pc + b.val(regs),
+            Opcode::Tgl(a) => {
+                let i = pc + regs[reg(a)];
+                if (0..self.prog.len() as i64).contains(&i) {
+                    let i = i as usize;
+                    self.prog[i].toggle();
+                    self.code[i] = Code::new(self.prog[i], i);
+                }
+            }
+            Opcode::Out(a) => self.output.push(a.val(regs)),
+            _ => {}
+        }
+        pc + 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembunny::{Cpu, Program};
+
+    #[test]
+    fn matches_interpreter() {
+        // Toggles, jumps with register offsets and output.
+        let prog: Program = "\
+cpy 2 a
+tgl a
+tgl a
+tgl a
+cpy 1 a
+dec a
+dec a
+out a
+cpy 3 b
+jnz 1 b
+inc a
+inc a"
+            .parse()
+            .unwrap();
+
+        let mut cpu = Cpu::new(prog.0.clone());
+        cpu.run();
+        let mut code = Compiled::new(prog.0.clone());
+        let mut regs = [0; 4];
+        code.run(&mut regs);
+
+        assert_eq!(regs.to_vec(), *cpu.regs);
+        assert_eq!(code.steps, cpu.steps);
+        assert_eq!(code.output, Vec::from(cpu.output));
+        assert_eq!(code.prog(), cpu.prog);
+    }
+}
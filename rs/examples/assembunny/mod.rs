@@ -1,11 +1,24 @@
-use serde::Deserialize;
+// Shared by several examples that each use only part of it.
+#![allow(dead_code)]
 
-use aoc::{prelude::*, Operand};
+use std::{fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+use aoc::{
+    prelude::{format_program, parse_program, ParseError},
+    vm::{self, BinOp, Flow, InstructionSet, Micro},
+    Operand,
+};
 use Operand::*;
 
-#[derive(Copy, Clone, Debug, Deserialize)]
+mod bytecode;
+
+pub use bytecode::Compiled;
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "lowercase")]
-enum Opcode {
+pub enum Opcode {
     Cpy(Operand, Operand),
     Jnz(Operand, Operand),
     Inc(char),
@@ -29,63 +42,122 @@ impl Opcode {
     }
 }
 
-pub struct Cpu<'a> {
-    pc: i64,
-    regs: [i64; 4],
-    prog: &'a mut Program,
-}
+impl InstructionSet for Opcode {
+    type Word = i64;
 
-impl<'a> Cpu<'a> {
-    pub fn new(prog: &'a mut Program, regs: [i64; 4]) -> Self {
-        Cpu { pc: 0, regs, prog }
-    }
-
-    pub fn run(&mut self) {
-        for _ in self {}
-    }
-}
+    const REGISTERS: usize = 4;
 
-impl<'a> Iterator for Cpu<'a> {
-    type Item = Option<i64>;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.pc < 0 || self.pc >= self.prog.0.len() as i64 {
-            return None;
-        }
-
-        self.pc += 1;
-        match self.prog.0[(self.pc - 1) as usize] {
-            Cpy(_, Int(_)) => {}
-            Cpy(a, Reg(b)) => self.regs[reg(b)] = a.val(&self.regs),
+    fn exec(&self, cpu: &mut Cpu) -> Flow {
+        match *self {
+            Cpy(_, Int(_) | Mem(_)) => {}
+            Cpy(a, Reg(b)) => cpu.regs[b] = a.val(&cpu.regs),
             Jnz(a, b) => {
-                if a.val(&self.regs) != 0 {
-                    self.pc += b.val(&self.regs) - 1;
+                if a.val(&cpu.regs) != 0 {
+                    return Flow::Jump(b.val(&cpu.regs));
                 }
             }
-            Inc(a) => self.regs[reg(a)] += 1,
-            Dec(a) => self.regs[reg(a)] -= 1,
+            Inc(a) => cpu.regs[a] += 1,
+            Dec(a) => cpu.regs[a] -= 1,
             Tgl(a) => {
-                let i = self.pc + self.regs[reg(a)] - 1;
-                if i >= 0 && i < self.prog.0.len() as i64 {
-                    Opcode::toggle(&mut self.prog.0[i as usize]);
+                let i = cpu.pc + cpu.regs[a];
+                if i >= 0 && i < cpu.prog.len() as i64 {
+                    cpu.prog[i as usize].toggle();
                 }
             }
-            Out(a) => {
-                return Some(Some(a.val(&self.regs)));
-            }
+            Out(a) => cpu.output.push_back(a.val(&cpu.regs)),
         }
+        Flow::Next
+    }
+
+    fn micro(&self) -> Micro {
+        match *self {
+            Cpy(_, Int(_) | Mem(_)) => Micro::Nop,
+            Cpy(a, Reg(b)) => Micro::Set(b, a),
+            Jnz(a, b) => Micro::Jnz(a, b),
+            Inc(a) => Micro::apply(a, BinOp::Add, Int(1)),
+            Dec(a) => Micro::apply(a, BinOp::Sub, Int(1)),
+            Tgl(_) | Out(_) => Micro::Opaque,
+        }
+    }
+}
+
+pub type Cpu = vm::Cpu<Opcode>;
+
+#[derive(Clone, Debug)]
+pub struct Program(pub Vec<Opcode>);
 
-        Some(None)
+impl FromStr for Program {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_program(s).map(Program)
     }
 }
 
-#[derive(Clone, Debug, Deserialize)]
-pub struct Program(Vec<Opcode>);
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", format_program(&self.0))
+    }
+}
 
 impl Program {
+    pub fn cpu(&self, regs: [i64; 4]) -> Cpu {
+        Cpu::new(self.0.clone()).with_regs(regs).optimize()
+    }
+
     pub fn run(&mut self, regs: &mut [i64; 4]) {
-        let mut cpu = Cpu::new(self, *regs);
+        let mut cpu = self.cpu(*regs);
         cpu.run();
-        *regs = cpu.regs;
+        regs.copy_from_slice(&cpu.regs);
+        self.0 = cpu.prog;
+    }
+
+    /// Run with the bytecode interpreter instead of the loop optimizer.
+    pub fn run_compiled(&mut self, regs: &mut [i64; 4]) {
+        let mut code = Compiled::new(self.0.clone());
+        code.run(regs);
+        self.0 = code.prog().to_vec();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let text = "\
+cpy 41 a
+inc a
+dec a
+jnz a 2
+dec a
+tgl c
+out b
+";
+        let prog: Program = text.parse().unwrap();
+        assert_eq!(prog.to_string(), text);
+
+        let e = "inc a\njnz a\n".parse::<Program>().unwrap_err();
+        assert_eq!((e.line, e.text.as_str()), (2, "jnz a"));
+        let e = "inc a\ncpy 1 aa\n".parse::<Program>().unwrap_err();
+        assert_eq!((e.line, e.col, e.text.as_str()), (2, 7, "aa"));
+        let e = "cpy 1 [c]\n".parse::<Program>().unwrap_err();
+        assert_eq!((e.line, e.col, e.text.as_str()), (1, 7, "[c]"));
+    }
+
+    #[test]
+    fn toggled() {
+        let mut prog: Program =
+            "cpy 2 a\ntgl a\ntgl a\ntgl a\ncpy 1 a\ndec a\ndec a"
+                .parse()
+                .unwrap();
+        let mut regs = [0; 4];
+        prog.run(&mut regs);
+        assert_eq!(regs[0], 3);
+        assert_eq!(
+            prog.to_string(),
+            "cpy 2 a\ntgl a\ntgl a\ninc a\njnz 1 a\ndec a\ndec a\n"
+        );
     }
 }
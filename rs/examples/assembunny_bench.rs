@@ -0,0 +1,48 @@
+//! Compare assembunny interpreters.
+//!
+//! Reads a program from stdin and takes the initial register values as
+//! arguments, eg. for 1612 part 2:
+//!
+//! cargo run --release --example assembunny_bench 0 0 1 0 < input.txt
+
+use std::time::Instant;
+
+use aoc::prelude::*;
+
+mod assembunny;
+
+use assembunny::{Compiled, Cpu, Program};
+
+fn main() {
+    let prog = Program(stdin_program());
+    let mut regs = [0; 4];
+    for (r, arg) in regs.iter_mut().zip(std::env::args().skip(1)) {
+        *r = arg.parse().expect("bad register value");
+    }
+
+    let t = Instant::now();
+    let mut cpu = Cpu::new(prog.0.clone()).with_regs(regs);
+    cpu.run();
+    println!(
+        "plain:     a = {:<12} {:>12} steps {:>10.3?}",
+        cpu.regs[0],
+        cpu.steps,
+        t.elapsed()
+    );
+
+    let t = Instant::now();
+    let mut code = Compiled::new(prog.0.clone());
+    let mut r = regs;
+    code.run(&mut r);
+    println!(
+        "compiled:  a = {:<12} {:>12} steps {:>10.3?}",
+        r[0],
+        code.steps,
+        t.elapsed()
+    );
+
+    let t = Instant::now();
+    let mut r = regs;
+    prog.clone().run(&mut r);
+    println!("optimized: a = {:<12} {:>29.3?}", r[0], t.elapsed());
+}
@@ -250,6 +250,40 @@ impl<T: Element, const N: usize> AxisBox<T, N> {
         )
     }
 
+    /// Return disjoint orthotopes that together cover the parts of `self`
+    /// that are not in `rhs`.
+    ///
+    /// There will be at most `2 * N` pieces.
+    pub fn difference(&self, rhs: &Self) -> Vec<Self> {
+        if !self.intersects(rhs) {
+            return if self.is_empty() {
+                Vec::new()
+            } else {
+                vec![*self]
+            };
+        }
+
+        let mut ret = Vec::new();
+        // Slice off the parts outside rhs one axis at a time, what remains at
+        // the end is the intersection.
+        let mut rest = *self;
+        for i in 0..N {
+            if rest.p0[i] < rhs.p0[i] {
+                let mut piece = rest;
+                piece.p1[i] = rhs.p0[i];
+                ret.push(piece);
+                rest.p0[i] = rhs.p0[i];
+            }
+            if rest.p1[i] > rhs.p1[i] {
+                let mut piece = rest;
+                piece.p0[i] = rhs.p1[i];
+                ret.push(piece);
+                rest.p1[i] = rhs.p1[i];
+            }
+        }
+        ret
+    }
+
     pub fn longest_axis(&self) -> usize {
         let d = self.dim();
         let mut ret = 0;
@@ -562,6 +596,27 @@ mod tests {
         assert_eq!(oct.iter().map(|o| o.volume()).sum::<i32>(), cube.volume());
     }
 
+    #[test]
+    fn difference() {
+        let cube: AxisBox<i32, 3> = volume([4, 4, 4]);
+        let hole = AxisBox::new([1, 1, 1], [3, 3, 3]);
+        let pieces = cube.difference(&hole);
+        assert_eq!(pieces.len(), 6);
+        assert_eq!(
+            pieces.iter().map(|p| p.volume()).sum::<i32>(),
+            cube.volume() - hole.volume()
+        );
+        for (i, a) in pieces.iter().enumerate() {
+            assert!(!a.intersects(&hole));
+            for b in &pieces[i + 1..] {
+                assert!(!a.intersects(b));
+            }
+        }
+
+        assert_eq!(cube.difference(&(cube + [10, 0, 0])), vec![cube]);
+        assert!(hole.difference(&cube).is_empty());
+    }
+
     #[test]
     fn split() {
         let cube: AxisBox<i32, 3> = volume([3, 4, 5]);
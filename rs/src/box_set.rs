@@ -0,0 +1,326 @@
+use std::cmp::Ordering;
+
+use crate::axis_box::{AxisBox, Element};
+
+/// Union of orthotopes stored as a set of disjoint `AxisBox`es.
+///
+/// Operations split boxes along the faces of the other operand, so the cost
+/// depends on the number of boxes and not on the size of the coordinate
+/// space. Volumes of inputs like the 2021-12-22 reactor cubes can be computed
+/// directly without rasterizing anything.
+///
+/// Every face of a stored box lies on a face of some input box, so the set
+/// is never made of more boxes than the cells of a grid compressed to the
+/// input coordinates, at most `(2n)^N` after `n` operations. Boxes that
+/// line up along an axis after splitting are merged back together, which
+/// keeps the set well below the bound even when slabs along different axes
+/// cross.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct BoxSet<T, const N: usize> {
+    boxes: Vec<AxisBox<T, N>>,
+}
+
+impl<T, const N: usize> Default for BoxSet<T, N> {
+    fn default() -> Self {
+        BoxSet { boxes: Vec::new() }
+    }
+}
+
+impl<T: Element, const N: usize> BoxSet<T, N> {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Number of disjoint boxes the set is made of.
+    pub fn len(&self) -> usize {
+        self.boxes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.boxes.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &AxisBox<T, N>> {
+        self.boxes.iter()
+    }
+
+    /// Add the volume of `b` to the set.
+    pub fn insert(&mut self, b: &AxisBox<T, N>) {
+        if b.is_empty() || self.boxes.iter().any(|a| a.contains_other(b)) {
+            return;
+        }
+
+        // Carve out the parts already in the set so that the boxes stay
+        // disjoint.
+        let mut pieces = vec![*b];
+        for a in &self.boxes {
+            if !pieces.iter().any(|p| p.intersects(a)) {
+                continue;
+            }
+            pieces = pieces.iter().flat_map(|p| p.difference(a)).collect();
+            if pieces.is_empty() {
+                return;
+            }
+        }
+        self.boxes.extend(pieces);
+        self.merge();
+    }
+
+    /// Remove the volume of `b` from the set.
+    pub fn subtract(&mut self, b: &AxisBox<T, N>) {
+        if !self.boxes.iter().any(|a| a.intersects(b)) {
+            return;
+        }
+        self.boxes = self.boxes.iter().flat_map(|a| a.difference(b)).collect();
+        self.merge();
+    }
+
+    /// Clip the set to the volume of `b`.
+    pub fn intersect(&mut self, b: &AxisBox<T, N>) {
+        self.boxes = self
+            .boxes
+            .iter()
+            .map(|a| a.intersection(b))
+            .filter(|a| !a.is_empty())
+            .collect();
+        self.merge();
+    }
+
+    /// Add all volume of `other` to the set.
+    pub fn insert_set(&mut self, other: &Self) {
+        for b in &other.boxes {
+            self.insert(b);
+        }
+    }
+
+    /// Remove all volume of `other` from the set.
+    pub fn subtract_set(&mut self, other: &Self) {
+        for b in &other.boxes {
+            self.subtract(b);
+        }
+    }
+
+    /// Clip the set to the volume of `other`.
+    pub fn intersect_set(&mut self, other: &Self) {
+        // Since both sets are disjoint, the pairwise intersections are
+        // disjoint too.
+        self.boxes = self
+            .boxes
+            .iter()
+            .flat_map(|a| other.boxes.iter().map(|b| a.intersection(b)))
+            .filter(|a| !a.is_empty())
+            .collect();
+        self.merge();
+    }
+
+    pub fn contains(&self, p: impl Into<[T; N]>) -> bool {
+        let p = p.into();
+        self.boxes.iter().any(|a| a.contains(p))
+    }
+
+    /// Join boxes that touch face to face, until no more can be joined.
+    fn merge(&mut self) {
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for axis in 0..N {
+                changed |= self.merge_along(axis);
+            }
+        }
+    }
+
+    /// Join boxes that have the same extent on the other axes and touch
+    /// along `axis`, return whether anything was joined.
+    fn merge_along(&mut self, axis: usize) -> bool {
+        // Sort boxes with the same cross-section next to each other and in
+        // order along the axis.
+        let cmp =
+            |a: &T, b: &T| a.partial_cmp(b).expect("incomparable coordinates");
+        let cross = |a: &AxisBox<T, N>, b: &AxisBox<T, N>| {
+            (0..N)
+                .filter(|&i| i != axis)
+                .map(|i| cmp(&a.p0[i], &b.p0[i]).then(cmp(&a.p1[i], &b.p1[i])))
+                .find(|&o| o != Ordering::Equal)
+                .unwrap_or(Ordering::Equal)
+        };
+        self.boxes
+            .sort_by(|a, b| cross(a, b).then(cmp(&a.p0[axis], &b.p0[axis])));
+
+        let n = self.boxes.len();
+        let mut merged: Vec<AxisBox<T, N>> = Vec::with_capacity(n);
+        for b in self.boxes.drain(..) {
+            if let Some(a) = merged.last_mut() {
+                if cross(a, &b) == Ordering::Equal && a.p1[axis] == b.p0[axis] {
+                    a.p1[axis] = b.p1[axis];
+                    continue;
+                }
+            }
+            merged.push(b);
+        }
+        self.boxes = merged;
+        self.boxes.len() < n
+    }
+
+    /// Total volume covered by the set.
+    pub fn volume(&self) -> T {
+        self.boxes.iter().fold(T::zero(), |acc, a| acc + a.volume())
+    }
+
+    /// Smallest orthotope that contains the whole set.
+    pub fn bounds(&self) -> AxisBox<T, N> {
+        let mut it = self.boxes.iter();
+        let Some(first) = it.next() else {
+            return Default::default();
+        };
+        it.fold(*first, |acc, a| acc.union(a))
+    }
+}
+
+impl<T: Element, const N: usize> FromIterator<AxisBox<T, N>> for BoxSet<T, N> {
+    fn from_iter<I: IntoIterator<Item = AxisBox<T, N>>>(iter: I) -> Self {
+        let mut ret = BoxSet::new();
+        for b in iter {
+            ret.insert(&b);
+        }
+        ret
+    }
+}
+
+impl<T: Element, const N: usize> Extend<AxisBox<T, N>> for BoxSet<T, N> {
+    fn extend<I: IntoIterator<Item = AxisBox<T, N>>>(&mut self, iter: I) {
+        for b in iter {
+            self.insert(&b);
+        }
+    }
+}
+
+impl<T, const N: usize> IntoIterator for BoxSet<T, N> {
+    type Item = AxisBox<T, N>;
+
+    type IntoIter = std::vec::IntoIter<AxisBox<T, N>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.boxes.into_iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+    use super::*;
+    use crate::prelude::*;
+
+    fn random_box(rng: &mut SmallRng) -> Cube<i32> {
+        let p0: [i32; 3] = std::array::from_fn(|_| rng.gen_range(0..8));
+        let p1: [i32; 3] = std::array::from_fn(|i| p0[i] + rng.gen_range(1..6));
+        Cube::new(p0, p1)
+    }
+
+    #[test]
+    fn matches_rasterized() {
+        let mut rng = SmallRng::seed_from_u64(123);
+        let space: Cube<i32> = volume([16, 16, 16]);
+
+        let mut set = BoxSet::new();
+        let mut cells = HashSet::default();
+
+        for i in 0..100 {
+            let b = random_box(&mut rng);
+            match i % 3 {
+                0 | 1 => {
+                    set.insert(&b);
+                    cells.extend(b);
+                }
+                _ => {
+                    set.subtract(&b);
+                    for p in b {
+                        cells.remove(&p);
+                    }
+                }
+            }
+
+            assert_eq!(set.volume() as usize, cells.len());
+            for (j, a) in set.iter().enumerate() {
+                for b in set.iter().skip(j + 1) {
+                    assert!(!a.intersects(b));
+                }
+            }
+        }
+
+        for p in space {
+            assert_eq!(set.contains(p), cells.contains(&p));
+        }
+
+        let clip = Cube::new([2, 3, 4], [10, 9, 8]);
+        set.intersect(&clip);
+        cells.retain(|&p| clip.contains(p));
+        assert_eq!(set.volume() as usize, cells.len());
+    }
+
+    #[test]
+    fn fragmentation_bound() {
+        let mut rng = SmallRng::seed_from_u64(123);
+        let space: Cube<i32> = volume([24, 24, 24]);
+
+        let mut set = BoxSet::new();
+        let mut cells = HashSet::default();
+        // Coordinates of the input faces along each axis.
+        let mut faces: [Vec<i32>; 3] = Default::default();
+
+        for i in 0..300 {
+            // Thin slabs across the whole space along a random axis, crossing
+            // slabs along the other axes cut each other into many pieces.
+            let axis = rng.gen_range(0..3);
+            let mut b = space;
+            b.p0[axis] = rng.gen_range(0..22);
+            b.p1[axis] = b.p0[axis] + rng.gen_range(1..3);
+            if i % 5 == 4 {
+                b = random_box(&mut rng) + [4, 4, 4];
+                set.subtract(&b);
+                for p in b {
+                    cells.remove(&p);
+                }
+            } else {
+                set.insert(&b);
+                cells.extend(b);
+            }
+
+            for (j, f) in faces.iter_mut().enumerate() {
+                f.extend([b.p0[j], b.p1[j]]);
+                f.sort();
+                f.dedup();
+            }
+            let grid_cells: usize = faces.iter().map(|f| f.len() - 1).product();
+            assert!(set.len() <= grid_cells);
+            assert_eq!(set.volume() as usize, cells.len());
+        }
+
+        for p in space {
+            assert_eq!(set.contains(p), cells.contains(&p));
+        }
+    }
+
+    #[test]
+    fn set_operations() {
+        let a: BoxSet<i64, 2> =
+            [area(4, 4), area(4, 4) + [2, 2]].into_iter().collect();
+        assert_eq!(a.volume(), 28);
+
+        let b: BoxSet<i64, 2> = [area(2, 2) + [5, 5]].into_iter().collect();
+
+        let mut u = a.clone();
+        u.insert_set(&b);
+        assert_eq!(u.volume(), 31);
+
+        let mut d = a.clone();
+        d.subtract_set(&b);
+        assert_eq!(d.volume(), 27);
+
+        let mut i = a.clone();
+        i.intersect_set(&b);
+        assert_eq!(i.volume(), 1);
+
+        assert_eq!(a.bounds(), area(6, 6));
+    }
+}
@@ -0,0 +1,410 @@
+//! Circuits of named nodes computed from other nodes.
+
+use std::fmt::{self, Write};
+
+use num_traits::PrimInt;
+
+use crate::prelude::*;
+
+mod template;
+pub use template::{Deviation, Report, Template};
+
+/// Operation that computes a node's value from other nodes.
+pub trait Gate {
+    type Value: Clone;
+
+    /// Names of the nodes the gate reads, in the order `eval` gets their
+    /// values.
+    fn inputs(&self) -> Vec<&str>;
+
+    fn eval(&self, inputs: &[Self::Value]) -> Self::Value;
+
+    /// Short description of the operation for graph export.
+    fn label(&self) -> String;
+}
+
+/// Gate argument.
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub enum Arg<T> {
+    Const(T),
+    Node(String),
+}
+
+impl<T: FromStr> FromStr for Arg<T> {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(n) = s.parse() {
+            Ok(Arg::Const(n))
+        } else if !s.is_empty() && s.chars().all(|c| c.is_alphanumeric()) {
+            Ok(Arg::Node(s.to_owned()))
+        } else {
+            Err("bad argument")
+        }
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for Arg<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Arg::Const(n) => write!(f, "{n}"),
+            Arg::Node(s) => write!(f, "{s}"),
+        }
+    }
+}
+
+/// Standard integer gates.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub enum Op<T> {
+    Id(Arg<T>),
+    Not(Arg<T>),
+    And(Arg<T>, Arg<T>),
+    Or(Arg<T>, Arg<T>),
+    Xor(Arg<T>, Arg<T>),
+    Add(Arg<T>, Arg<T>),
+    Sub(Arg<T>, Arg<T>),
+    Mul(Arg<T>, Arg<T>),
+    Div(Arg<T>, Arg<T>),
+    Shl(Arg<T>, Arg<T>),
+    Shr(Arg<T>, Arg<T>),
+}
+
+impl<T> Op<T> {
+    pub fn args(&self) -> Vec<&Arg<T>> {
+        use Op::*;
+        match self {
+            Id(a) | Not(a) => vec![a],
+            And(a, b)
+            | Or(a, b)
+            | Xor(a, b)
+            | Add(a, b)
+            | Sub(a, b)
+            | Mul(a, b)
+            | Div(a, b)
+            | Shl(a, b)
+            | Shr(a, b) => vec![a, b],
+        }
+    }
+
+    /// Operator symbol.
+    pub fn name(&self) -> &'static str {
+        use Op::*;
+        match self {
+            Id(_) => "=",
+            Not(_) => "NOT",
+            And(..) => "AND",
+            Or(..) => "OR",
+            Xor(..) => "XOR",
+            Add(..) => "+",
+            Sub(..) => "-",
+            Mul(..) => "*",
+            Div(..) => "/",
+            Shl(..) => "<<",
+            Shr(..) => ">>",
+        }
+    }
+
+    /// Whether the order of the arguments doesn't matter.
+    pub fn is_commutative(&self) -> bool {
+        use Op::*;
+        matches!(self, And(..) | Or(..) | Xor(..) | Add(..) | Mul(..))
+    }
+}
+
+impl<T: PrimInt + fmt::Display> Gate for Op<T> {
+    type Value = T;
+
+    fn inputs(&self) -> Vec<&str> {
+        self.args()
+            .into_iter()
+            .filter_map(|a| match a {
+                Arg::Node(s) => Some(s.as_str()),
+                Arg::Const(_) => None,
+            })
+            .collect()
+    }
+
+    fn eval(&self, inputs: &[T]) -> T {
+        use Op::*;
+
+        let mut inputs = inputs.iter();
+        let mut args = self.args().into_iter().map(|a| match a {
+            Arg::Const(n) => *n,
+            Arg::Node(_) => *inputs.next().expect("Op::eval: missing input"),
+        });
+        let mut arg = || args.next().unwrap();
+        let shift = |n: T| n.to_usize().expect("Op::eval: bad shift");
+        match self {
+            Id(_) => arg(),
+            Not(_) => !arg(),
+            And(..) => arg() & arg(),
+            Or(..) => arg() | arg(),
+            Xor(..) => arg() ^ arg(),
+            Add(..) => arg() + arg(),
+            Sub(..) => arg() - arg(),
+            Mul(..) => arg() * arg(),
+            Div(..) => arg() / arg(),
+            Shl(..) => arg() << shift(arg()),
+            Shr(..) => arg() >> shift(arg()),
+        }
+    }
+
+    fn label(&self) -> String {
+        let mut ret = self.name().to_owned();
+        for a in self.args() {
+            if let Arg::Const(n) = a {
+                write!(ret, " {n}").unwrap();
+            }
+        }
+        ret
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum CircuitError {
+    /// Nodes that depend on themselves, first node repeated at the end.
+    Cycle(Vec<String>),
+    /// A node was read but has no gate or value.
+    Missing(String),
+}
+
+impl fmt::Display for CircuitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CircuitError::Cycle(path) => {
+                write!(f, "cycle: {}", path.join(" -> "))
+            }
+            CircuitError::Missing(name) => write!(f, "missing node {name}"),
+        }
+    }
+}
+
+impl std::error::Error for CircuitError {}
+
+/// Circuit of named nodes with lazily evaluated values.
+///
+/// Nodes can be overridden with fixed values, which also works for giving
+/// values to input nodes that have no gates.
+#[derive(Clone, Debug)]
+pub struct Circuit<G: Gate> {
+    nodes: IndexMap<String, G>,
+    overrides: HashMap<String, G::Value>,
+    cache: HashMap<String, G::Value>,
+}
+
+impl<G: Gate> Default for Circuit<G> {
+    fn default() -> Self {
+        Circuit {
+            nodes: Default::default(),
+            overrides: Default::default(),
+            cache: Default::default(),
+        }
+    }
+}
+
+impl<G: Gate> FromIterator<(String, G)> for Circuit<G> {
+    fn from_iter<I: IntoIterator<Item = (String, G)>>(iter: I) -> Self {
+        Circuit {
+            nodes: iter.into_iter().collect(),
+            ..Default::default()
+        }
+    }
+}
+
+impl<G: Gate> Circuit<G> {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn insert(&mut self, name: impl Into<String>, gate: G) {
+        self.nodes.insert(name.into(), gate);
+        self.cache.clear();
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<G> {
+        self.cache.clear();
+        self.nodes.shift_remove(name)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&G> {
+        self.nodes.get(name)
+    }
+
+    /// Nodes with gates in insertion order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &G)> {
+        self.nodes.iter().map(|(k, g)| (k.as_str(), g))
+    }
+
+    /// Swap the gates of two nodes, ie. swap the wires the gates output to.
+    pub fn swap(&mut self, a: &str, b: &str) -> Result<(), CircuitError> {
+        for n in [a, b] {
+            if !self.nodes.contains_key(n) {
+                return Err(CircuitError::Missing(n.to_owned()));
+            }
+        }
+        let mut gates = self
+            .nodes
+            .iter_mut()
+            .filter(|(k, _)| *k == a || *k == b)
+            .map(|(_, g)| g);
+        if let (Some(x), Some(y)) = (gates.next(), gates.next()) {
+            std::mem::swap(x, y);
+        }
+        self.cache.clear();
+        Ok(())
+    }
+
+    /// Fix a node to a value regardless of its gate.
+    pub fn set(&mut self, name: impl Into<String>, value: G::Value) {
+        self.overrides.insert(name.into(), value);
+        self.cache.clear();
+    }
+
+    pub fn clear_overrides(&mut self) {
+        self.overrides.clear();
+        self.cache.clear();
+    }
+
+    /// Value of a node. Values are cached until the circuit is changed.
+    pub fn eval(&mut self, name: &str) -> Result<G::Value, CircuitError> {
+        self.eval_inner(name, &mut Vec::new())
+    }
+
+    fn eval_inner(
+        &mut self,
+        name: &str,
+        path: &mut Vec<String>,
+    ) -> Result<G::Value, CircuitError> {
+        if let Some(x) = self.overrides.get(name) {
+            return Ok(x.clone());
+        }
+        if let Some(x) = self.cache.get(name) {
+            return Ok(x.clone());
+        }
+        if let Some(i) = path.iter().position(|n| n == name) {
+            let mut cycle = path[i..].to_vec();
+            cycle.push(name.to_owned());
+            return Err(CircuitError::Cycle(cycle));
+        }
+        let Some(gate) = self.nodes.get(name) else {
+            return Err(CircuitError::Missing(name.to_owned()));
+        };
+
+        let inputs: Vec<String> =
+            gate.inputs().into_iter().map(|s| s.to_owned()).collect();
+        path.push(name.to_owned());
+        let values = inputs
+            .iter()
+            .map(|n| self.eval_inner(n, path))
+            .collect::<Result<Vec<_>, _>>()?;
+        path.pop();
+
+        let ret = self.nodes[name].eval(&values);
+        self.cache.insert(name.to_owned(), ret.clone());
+        Ok(ret)
+    }
+
+    /// Graphviz description of the circuit. Overridden nodes are filled.
+    pub fn to_dot(&self) -> String {
+        let mut ret = String::from("digraph {\n");
+        for (name, gate) in &self.nodes {
+            write!(ret, "    {name:?} [label=\"{name}\\n{}\"", gate.label())
+                .unwrap();
+            if self.overrides.contains_key(name) {
+                write!(ret, " style=filled").unwrap();
+            }
+            writeln!(ret, "];").unwrap();
+        }
+        for name in self.overrides.keys() {
+            if !self.nodes.contains_key(name) {
+                writeln!(ret, "    {name:?} [style=filled];").unwrap();
+            }
+        }
+        for (name, gate) in &self.nodes {
+            for input in gate.inputs() {
+                writeln!(ret, "    {input:?} -> {name:?};").unwrap();
+            }
+        }
+        ret.push_str("}\n");
+        ret
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Arg::*;
+
+    fn node<T>(s: &str) -> Arg<T> {
+        Node(s.into())
+    }
+
+    fn example() -> Circuit<Op<u16>> {
+        [
+            ("x", Op::Id(Const(123))),
+            ("y", Op::Id(Const(456))),
+            ("d", Op::And(node("x"), node("y"))),
+            ("e", Op::Or(node("x"), node("y"))),
+            ("f", Op::Shl(node("x"), Const(2))),
+            ("g", Op::Shr(node("y"), Const(2))),
+            ("h", Op::Not(node("x"))),
+            ("i", Op::Not(node("y"))),
+        ]
+        .into_iter()
+        .map(|(n, g)| (n.to_owned(), g))
+        .collect()
+    }
+
+    #[test]
+    fn eval() {
+        let mut c = example();
+        let values: Vec<u16> = "defghixy"
+            .chars()
+            .map(|n| c.eval(&n.to_string()).unwrap())
+            .collect();
+        assert_eq!(values, [72, 507, 492, 114, 65412, 65079, 123, 456]);
+
+        c.set("x", 1);
+        assert_eq!(c.eval("d"), Ok(0));
+        assert_eq!(c.eval("f"), Ok(4));
+        c.clear_overrides();
+        assert_eq!(c.eval("d"), Ok(72));
+
+        c.insert("j", Op::Add(node("d"), node("z")));
+        assert_eq!(c.eval("j"), Err(CircuitError::Missing("z".into())));
+        c.set("z", 28);
+        assert_eq!(c.eval("j"), Ok(100));
+    }
+
+    #[test]
+    fn cycle() {
+        let mut c = example();
+        c.insert("x", Op::Xor(node("d"), Const(1)));
+        let err = c.eval("e").unwrap_err();
+        assert_eq!(
+            err,
+            CircuitError::Cycle(vec!["x".into(), "d".into(), "x".into()])
+        );
+        assert_eq!(err.to_string(), "cycle: x -> d -> x");
+        // Cycle is broken by overriding a node in it.
+        c.set("d", 0);
+        assert_eq!(c.eval("e"), Ok(457));
+    }
+
+    #[test]
+    fn dot() {
+        let mut c: Circuit<Op<u16>> = Circuit::new();
+        c.insert("a", Op::Shl(node("b"), Const(1)));
+        c.set("b", 3);
+        assert_eq!(
+            c.to_dot(),
+            "\
+digraph {
+    \"a\" [label=\"a\\n<< 1\"];
+    \"b\" [style=filled];
+    \"b\" -> \"a\";
+}
+"
+        );
+    }
+}
@@ -0,0 +1,544 @@
+//! Structural matching of netlists against known circuit shapes.
+
+use std::{fmt::Display, hash::Hash};
+
+use num_traits::PrimInt;
+
+use super::{Arg, Circuit, Gate, Op};
+use crate::prelude::*;
+
+/// Circuit with a known shape to look for in netlists.
+///
+/// Template nodes are matched to netlist wires by structure: a node
+/// corresponds to the netlist gate that has the same operation and reads the
+/// wires that correspond to the node's arguments. Nodes the template reads
+/// but doesn't define are inputs and correspond to netlist wires with the
+/// same name. Outputs must also have the same names in the netlist, names of
+/// other nodes don't matter.
+#[derive(Clone, Debug)]
+pub struct Template<T: PrimInt + Display> {
+    circuit: Circuit<Op<T>>,
+    outputs: Vec<String>,
+    /// Nodes in evaluation order.
+    order: Vec<String>,
+    /// Index of the first output that depends on each node.
+    output_of: HashMap<String, usize>,
+}
+
+/// Difference between a netlist and a template at one template node.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Deviation {
+    pub node: String,
+    /// Index of the first template output that depends on the node, or the
+    /// number of outputs if none does.
+    pub output: usize,
+    /// Netlist wires whose gates should be swapped to fix the deviation, if
+    /// a fix could be inferred.
+    pub swap: Option<(String, String)>,
+}
+
+/// Result of matching a netlist against a template.
+#[derive(Clone, Default, Debug)]
+pub struct Report {
+    /// Netlist wire matched to each template node.
+    ///
+    /// Nodes that depend on a deviation without a fix are left out.
+    pub wires: HashMap<String, String>,
+    /// Deviations in template evaluation order.
+    pub deviations: Vec<Deviation>,
+}
+
+impl Report {
+    /// Whether the netlist has the shape of the template.
+    pub fn is_match(&self) -> bool {
+        self.deviations.is_empty()
+    }
+
+    /// Indices of the outputs where deviations first show up.
+    pub fn outputs(&self) -> Vec<usize> {
+        let mut ret: Vec<usize> =
+            self.deviations.iter().map(|d| d.output).collect();
+        ret.sort();
+        ret.dedup();
+        ret
+    }
+
+    /// Distinct inferred fixes.
+    pub fn swaps(&self) -> Vec<(String, String)> {
+        let mut ret: Vec<_> = self
+            .deviations
+            .iter()
+            .filter_map(|d| d.swap.clone())
+            .collect();
+        ret.sort();
+        ret.dedup();
+        ret
+    }
+}
+
+impl<T: PrimInt + Display + Hash> Template<T> {
+    /// Build a template from a circuit and the names of its outputs.
+    ///
+    /// Panics if the circuit has a cycle or an output isn't in the circuit.
+    pub fn new(
+        circuit: Circuit<Op<T>>,
+        outputs: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        let outputs: Vec<String> =
+            outputs.into_iter().map(Into::into).collect();
+
+        fn visit<T: PrimInt + Display>(
+            circuit: &Circuit<Op<T>>,
+            name: &str,
+            done: &mut HashMap<String, bool>,
+            order: &mut Vec<String>,
+        ) {
+            match done.get(name) {
+                Some(true) => return,
+                Some(false) => panic!("Template: cycle at {name}"),
+                None => {}
+            }
+            let Some(gate) = circuit.get(name) else {
+                return;
+            };
+            done.insert(name.to_owned(), false);
+            for input in gate.inputs() {
+                visit(circuit, input, done, order);
+            }
+            done.insert(name.to_owned(), true);
+            order.push(name.to_owned());
+        }
+
+        let mut order = Vec::new();
+        let mut done = HashMap::default();
+        for (name, _) in circuit.iter() {
+            visit(&circuit, name, &mut done, &mut order);
+        }
+
+        let mut output_of = HashMap::default();
+        for (i, out) in outputs.iter().enumerate() {
+            assert!(
+                circuit.get(out).is_some(),
+                "Template: output {out} not in circuit"
+            );
+            let mut stack = vec![out.as_str()];
+            while let Some(name) = stack.pop() {
+                let Some(gate) = circuit.get(name) else {
+                    continue;
+                };
+                if output_of.contains_key(name) {
+                    continue;
+                }
+                output_of.insert(name.to_owned(), i);
+                stack.extend(gate.inputs());
+            }
+        }
+        for name in &order {
+            if !output_of.contains_key(name) {
+                output_of.insert(name.clone(), outputs.len());
+            }
+        }
+
+        Template {
+            circuit,
+            outputs,
+            order,
+            output_of,
+        }
+    }
+
+    pub fn circuit(&self) -> &Circuit<Op<T>> {
+        &self.circuit
+    }
+
+    pub fn outputs(&self) -> &[String] {
+        &self.outputs
+    }
+
+    /// Adder of `bits` bit numbers from inputs `x00`, `x01`, ... and `y00`,
+    /// `y01`, ... into outputs `z00`, `z01`, ..., the last output being the
+    /// carry out.
+    pub fn ripple_carry_adder(bits: usize) -> Self {
+        assert!(bits > 0, "Template: empty adder");
+        let mut c = Circuit::new();
+        let carry = |i| {
+            if i + 1 == bits {
+                bit('z', bits)
+            } else {
+                format!("carry{i:02}")
+            }
+        };
+
+        c.insert("z00", Op::Xor(node(bit('x', 0)), node(bit('y', 0))));
+        c.insert(carry(0), Op::And(node(bit('x', 0)), node(bit('y', 0))));
+        for i in 1..bits {
+            let (x, y) = (node(bit('x', i)), node(bit('y', i)));
+            let half = format!("half{i:02}");
+            let gen = format!("gen{i:02}");
+            let prop = format!("prop{i:02}");
+            c.insert(&half, Op::Xor(x.clone(), y.clone()));
+            c.insert(&gen, Op::And(x, y));
+            c.insert(bit('z', i), Op::Xor(node(&half), node(carry(i - 1))));
+            c.insert(&prop, Op::And(node(&half), node(carry(i - 1))));
+            c.insert(carry(i), Op::Or(node(gen), node(prop)));
+        }
+
+        Template::new(c, (0..=bits).map(|i| bit('z', i)))
+    }
+
+    /// Comparator with output `lt` that is 1 when the `bits` bit number `x`
+    /// is less than `y`. Bits are named like for `ripple_carry_adder` and
+    /// compared from the least significant end.
+    pub fn less_than(bits: usize) -> Self {
+        assert!(bits > 0, "Template: empty comparator");
+        let mut c = Circuit::new();
+        let lt = |i| {
+            if i + 1 == bits {
+                "lt".to_owned()
+            } else {
+                format!("lt{i:02}")
+            }
+        };
+
+        for i in 0..bits {
+            let (x, y) = (node(bit('x', i)), node(bit('y', i)));
+            let not_x = format!("not_x{i:02}");
+            let less = format!("less{i:02}");
+            c.insert(&not_x, Op::Not(x.clone()));
+            if i == 0 {
+                c.insert(lt(0), Op::And(node(not_x), y));
+                continue;
+            }
+            let diff = format!("diff{i:02}");
+            let eq = format!("eq{i:02}");
+            let keep = format!("keep{i:02}");
+            c.insert(&less, Op::And(node(not_x), y.clone()));
+            c.insert(&diff, Op::Xor(x, y));
+            c.insert(&eq, Op::Not(node(diff)));
+            c.insert(&keep, Op::And(node(eq), node(lt(i - 1))));
+            c.insert(lt(i), Op::Or(node(less), node(keep)));
+        }
+
+        Template::new(c, ["lt"])
+    }
+
+    /// Multiplexer that outputs `x` to `z` when `s` is 0 and `y` when it's
+    /// 1, for `bits` bit numbers named like for `ripple_carry_adder`.
+    pub fn mux(bits: usize) -> Self {
+        let mut c = Circuit::new();
+        c.insert("not_s", Op::Not(node("s")));
+        for i in 0..bits {
+            let pick_x = format!("pick_x{i:02}");
+            let pick_y = format!("pick_y{i:02}");
+            c.insert(&pick_x, Op::And(node(bit('x', i)), node("not_s")));
+            c.insert(&pick_y, Op::And(node(bit('y', i)), node("s")));
+            c.insert(bit('z', i), Op::Or(node(pick_x), node(pick_y)));
+        }
+
+        Template::new(c, (0..bits).map(|i| bit('z', i)))
+    }
+
+    /// Match a netlist against the template.
+    ///
+    /// When no netlist gate matches a node exactly but exactly one gate with
+    /// the same operation differs from it by one argument, the gate is taken
+    /// as the match and swapping the expected and the found argument wires
+    /// is reported as the fix. Outputs found under a different name are
+    /// fixed by swapping them with the expected name.
+    pub fn check(&self, netlist: &Circuit<Op<T>>) -> Report {
+        // Netlist gates as wire, operation and normalized arguments.
+        let gates: Vec<_> = netlist
+            .iter()
+            .map(|(w, g)| (w, g, normalize(g, g.args().into_iter().cloned())))
+            .collect();
+        let mut exact: HashMap<_, Vec<usize>> = HashMap::default();
+        let mut partial: HashMap<_, Vec<usize>> = HashMap::default();
+        for (i, (_, op, args)) in gates.iter().enumerate() {
+            exact.entry((op.name(), args.clone())).or_default().push(i);
+            for a in args.iter().filter(|a| matches!(a, Arg::Node(_))) {
+                let e = partial.entry((op.name(), a.clone())).or_default();
+                if e.last() != Some(&i) {
+                    e.push(i);
+                }
+            }
+        }
+        let is_wire = |w: &str| netlist.get(w).is_some();
+
+        let mut report = Report::default();
+        for name in &self.order {
+            let gate = self.circuit.get(name).unwrap();
+            let output = self.output_of[name];
+
+            // Netlist arguments, skip the node if one wasn't found.
+            let Some(args) = gate
+                .args()
+                .into_iter()
+                .map(|a| match a {
+                    Arg::Node(n) if self.circuit.get(n).is_some() => {
+                        report.wires.get(n).map(|w| Arg::Node(w.clone()))
+                    }
+                    a => Some(a.clone()),
+                })
+                .collect::<Option<Vec<_>>>()
+            else {
+                continue;
+            };
+            let args = normalize(gate, args);
+
+            let wire =
+                if let Some(found) = exact.get(&(gate.name(), args.clone())) {
+                    // Prefer the expected name if there are duplicate gates.
+                    found
+                        .iter()
+                        .map(|&i| gates[i].0)
+                        .find(|w| w == name)
+                        .unwrap_or(gates[found[0]].0)
+                } else {
+                    let mut near: Vec<(usize, (String, String))> = args
+                        .iter()
+                        .filter_map(|a| partial.get(&(gate.name(), a.clone())))
+                        .flatten()
+                        .filter_map(|&i| {
+                            differ(gate.is_commutative(), &args, &gates[i].2)
+                                .map(|d| (i, d))
+                        })
+                        .collect();
+                    near.sort();
+                    near.dedup();
+                    match &near[..] {
+                        [(i, (a, b))] if is_wire(a) && is_wire(b) => {
+                            report.deviations.push(Deviation {
+                                node: name.clone(),
+                                output,
+                                swap: Some(ordered(a, b)),
+                            });
+                            gates[*i].0
+                        }
+                        _ => {
+                            report.deviations.push(Deviation {
+                                node: name.clone(),
+                                output,
+                                swap: None,
+                            });
+                            continue;
+                        }
+                    }
+                };
+
+            if wire != name && self.outputs.contains(name) {
+                report.deviations.push(Deviation {
+                    node: name.clone(),
+                    output,
+                    swap: is_wire(name).then(|| ordered(name, wire)),
+                });
+            }
+            report.wires.insert(name.clone(), wire.to_owned());
+        }
+        report
+    }
+
+    /// Find gate output swaps that make the netlist match the template.
+    ///
+    /// Fixes are applied one at a time and the netlist is rechecked after
+    /// each, since one swap can disturb the matching of several nodes.
+    /// Returns the swaps in sorted order, or `None` if a deviation has no
+    /// fix or more than `max_swaps` swaps would be needed.
+    pub fn repair(
+        &self,
+        netlist: &Circuit<Op<T>>,
+        max_swaps: usize,
+    ) -> Option<Vec<(String, String)>> {
+        let mut netlist = netlist.clone();
+        let mut ret = Vec::new();
+        loop {
+            let report = self.check(&netlist);
+            if report.is_match() {
+                ret.sort();
+                return Some(ret);
+            }
+            let swap = report.deviations.iter().find_map(|d| d.swap.clone())?;
+            if ret.len() == max_swaps || ret.contains(&swap) {
+                return None;
+            }
+            netlist.swap(&swap.0, &swap.1).ok()?;
+            ret.push(swap);
+        }
+    }
+}
+
+fn bit(prefix: char, i: usize) -> String {
+    format!("{prefix}{i:02}")
+}
+
+fn node<T>(name: impl Into<String>) -> Arg<T> {
+    Arg::Node(name.into())
+}
+
+fn ordered(a: &str, b: &str) -> (String, String) {
+    let (a, b) = if a <= b { (a, b) } else { (b, a) };
+    (a.to_owned(), b.to_owned())
+}
+
+/// Arguments in canonical order.
+fn normalize<T: Ord>(
+    op: &Op<T>,
+    args: impl IntoIterator<Item = Arg<T>>,
+) -> Vec<Arg<T>> {
+    let mut args: Vec<Arg<T>> = args.into_iter().collect();
+    if op.is_commutative() {
+        args.sort();
+    }
+    args
+}
+
+/// Expected and actual wire when the argument lists differ by exactly one
+/// wire.
+fn differ<T: PartialEq>(
+    commutative: bool,
+    expected: &[Arg<T>],
+    actual: &[Arg<T>],
+) -> Option<(String, String)> {
+    let pair = |a: &Arg<T>, b: &Arg<T>| match (a, b) {
+        (Arg::Node(a), Arg::Node(b)) if a != b => Some((a.clone(), b.clone())),
+        _ => None,
+    };
+
+    match (expected, actual) {
+        ([a, b], [c, d]) if commutative => {
+            if a == c {
+                pair(b, d)
+            } else if a == d {
+                pair(b, c)
+            } else if b == c {
+                pair(a, d)
+            } else if b == d {
+                pair(a, c)
+            } else {
+                None
+            }
+        }
+        _ if expected.len() == actual.len() => {
+            let mut diffs = expected.iter().zip(actual).filter(|(a, b)| a != b);
+            match (diffs.next(), diffs.next()) {
+                (Some((a, b)), None) => pair(a, b),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Template circuit with non-output nodes renamed and commutative
+    /// arguments flipped, like a netlist would have it.
+    fn netlist(t: &Template<u8>) -> Circuit<Op<u8>> {
+        let rename = |n: &str| -> String {
+            match t.circuit.iter().position(|(k, _)| k == n) {
+                Some(i) if !t.outputs.iter().any(|o| o == n) => {
+                    format!("w{i:03}")
+                }
+                _ => n.to_owned(),
+            }
+        };
+        let arg = |a: &Arg<u8>| match a {
+            Arg::Node(n) => Arg::Node(rename(n)),
+            a => a.clone(),
+        };
+        t.circuit
+            .iter()
+            .map(|(k, g)| {
+                let g = match g {
+                    Op::Not(a) => Op::Not(arg(a)),
+                    Op::And(a, b) => Op::And(arg(b), arg(a)),
+                    Op::Or(a, b) => Op::Or(arg(b), arg(a)),
+                    Op::Xor(a, b) => Op::Xor(arg(b), arg(a)),
+                    _ => unreachable!(),
+                };
+                (rename(k), g)
+            })
+            .collect()
+    }
+
+    fn set(c: &mut Circuit<Op<u8>>, prefix: char, bits: usize, n: u32) {
+        for i in 0..bits {
+            c.set(bit(prefix, i), (n >> i & 1) as u8);
+        }
+    }
+
+    fn get(c: &mut Circuit<Op<u8>>, names: &[String]) -> u32 {
+        names
+            .iter()
+            .enumerate()
+            .map(|(i, n)| (c.eval(n).unwrap() as u32 & 1) << i)
+            .sum()
+    }
+
+    #[test]
+    fn templates() {
+        let adder = Template::ripple_carry_adder(4);
+        let comparator = Template::less_than(4);
+        let mux = Template::mux(4);
+        for t in [&adder, &comparator, &mux] {
+            let report = t.check(&netlist(t));
+            assert!(report.is_match());
+            assert_eq!(report.wires.len(), t.circuit.iter().count());
+        }
+        // Different shapes don't match.
+        assert!(!adder.check(&netlist(&mux)).is_match());
+
+        for x in 0..16 {
+            for y in 0..16 {
+                for (t, expected) in
+                    [(&adder, x + y), (&comparator, (x < y) as u32), (&mux, x)]
+                {
+                    let mut c = netlist(t);
+                    set(&mut c, 'x', 4, x);
+                    set(&mut c, 'y', 4, y);
+                    c.set("s", 0);
+                    assert_eq!(get(&mut c, &t.outputs), expected);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn swaps() {
+        let adder = Template::ripple_carry_adder(8);
+        let good = netlist(&adder);
+        let wire = |n: &str| netlist_name(&adder, n);
+
+        let swaps = [
+            // Output and carry of the same bit.
+            (wire("carry01"), "z01".to_owned()),
+            // Gates reading the same inputs.
+            (wire("half03"), wire("gen03")),
+            // Output and a gate of another bit.
+            (wire("prop06"), "z04".to_owned()),
+            // Carry and a gate of another bit.
+            (wire("carry05"), wire("gen02")),
+        ];
+        let mut bad = good.clone();
+        for (a, b) in &swaps {
+            bad.swap(a, b).unwrap();
+        }
+
+        let report = adder.check(&bad);
+        // Swaps also break the gates that read the swapped wires.
+        assert_eq!(report.outputs(), [1, 2, 3, 4, 6, 7]);
+        let mut expected: Vec<_> =
+            swaps.iter().map(|(a, b)| ordered(a, b)).collect();
+        expected.sort();
+        assert_eq!(report.swaps(), expected);
+        assert_eq!(adder.repair(&bad, 4), Some(expected));
+        assert_eq!(adder.repair(&bad, 3), None);
+        assert_eq!(adder.repair(&good, 0), Some(vec![]));
+    }
+
+    fn netlist_name(t: &Template<u8>, n: &str) -> String {
+        let i = t.circuit.iter().position(|(k, _)| k == n).unwrap();
+        format!("w{i:03}")
+    }
+}
@@ -0,0 +1,218 @@
+use std::ops::Range;
+
+use crate::{axis_box::AxisBox, lattice::Metric};
+
+/// Set of integer points within a taxicab distance from a center point.
+///
+/// A diamond in 2D, an octahedron in 3D.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct L1Ball<const N: usize> {
+    pub center: [i64; N],
+    pub radius: i64,
+}
+
+impl<const N: usize> L1Ball<N> {
+    pub fn new(center: impl Into<[i64; N]>, radius: i64) -> Self {
+        L1Ball {
+            center: center.into(),
+            radius,
+        }
+    }
+
+    /// Taxicab distance of a point from the center.
+    pub fn dist(&self, p: impl Into<[i64; N]>) -> i64 {
+        let p = p.into();
+        (0..N).map(|i| (p[i] - self.center[i]).abs()).sum()
+    }
+
+    pub fn contains(&self, p: impl Into<[i64; N]>) -> bool {
+        self.dist(p) <= self.radius
+    }
+
+    pub fn intersects(&self, other: &Self) -> bool {
+        self.dist(other.center) <= self.radius + other.radius
+    }
+
+    /// Return whether the ball has points in common with an integer box.
+    pub fn intersects_box(&self, b: &AxisBox<i64, N>) -> bool {
+        !b.is_empty() && self.contains(b.clamp(self.center))
+    }
+
+    /// Smallest integer box containing the ball.
+    pub fn bounding_box(&self) -> AxisBox<i64, N> {
+        AxisBox::new(
+            self.center.map(|x| x - self.radius),
+            self.center.map(|x| x + self.radius + 1),
+        )
+    }
+
+    /// Range of coordinates along `axis` where the axis-aligned line going
+    /// through `p` is inside the ball.
+    pub fn slice(&self, axis: usize, p: impl Into<[i64; N]>) -> Range<i64> {
+        let mut p = p.into();
+        p[axis] = self.center[axis];
+        let d = self.radius - self.dist(p);
+        if d < 0 {
+            return 0..0;
+        }
+        (self.center[axis] - d)..(self.center[axis] + d + 1)
+    }
+
+    /// Iterate points at exactly `radius` distance from center.
+    pub fn boundary(&self) -> impl Iterator<Item = [i64; N]> {
+        self.shell(self.radius..self.radius + 1)
+    }
+
+    /// Iterate points strictly inside the ball, not on the boundary.
+    pub fn interior(&self) -> impl Iterator<Item = [i64; N]> {
+        self.shell(0..self.radius)
+    }
+
+    /// Iterate all points in the ball.
+    pub fn points(&self) -> impl Iterator<Item = [i64; N]> {
+        self.shell(0..self.radius + 1)
+    }
+
+    /// Iterate points whose distance from center is in range.
+    fn shell(&self, range: Range<i64>) -> impl Iterator<Item = [i64; N]> {
+        let c = self.center;
+        range.flat_map(move |r| Metric::Taxicab.shell(c, r))
+    }
+}
+
+impl L1Ball<2> {
+    /// Rotate a point into the u = x + y, v = x - y coordinate space where
+    /// diamonds are axis-aligned squares.
+    pub fn rotate([x, y]: [i64; 2]) -> [i64; 2] {
+        [x + y, x - y]
+    }
+
+    /// Map a rotated point back to regular coordinates. Returns `None` if the
+    /// rotated point does not correspond to an integer point.
+    pub fn unrotate([u, v]: [i64; 2]) -> Option<[i64; 2]> {
+        if (u - v) % 2 != 0 {
+            return None;
+        }
+        Some([(u + v) / 2, (u - v) / 2])
+    }
+
+    /// The ball as an axis-aligned box in the rotated coordinate space.
+    ///
+    /// The box also contains the rotated points with mismatched parities
+    /// that do not map back to integer points.
+    pub fn rotated(&self) -> AxisBox<i64, 2> {
+        let [u, v] = Self::rotate(self.center);
+        let r = self.radius;
+        AxisBox::new([u - r, v - r], [u + r + 1, v + r + 1])
+    }
+}
+
+/// Find the parts of the axis-aligned line through `p` along `axis` that are
+/// covered by balls, limited to `span`.
+///
+/// Returns sorted, disjoint, non-adjacent ranges.
+pub fn line_cover<'a, const N: usize>(
+    balls: impl IntoIterator<Item = &'a L1Ball<N>>,
+    axis: usize,
+    p: impl Into<[i64; N]>,
+    span: Range<i64>,
+) -> Vec<Range<i64>> {
+    let p = p.into();
+    let mut ranges: Vec<Range<i64>> = balls
+        .into_iter()
+        .map(|b| {
+            let r = b.slice(axis, p);
+            r.start.max(span.start)..r.end.min(span.end)
+        })
+        .filter(|r| !r.is_empty())
+        .collect();
+    ranges.sort_by_key(|r| r.start);
+
+    let mut ret: Vec<Range<i64>> = Vec::new();
+    for r in ranges {
+        match ret.last_mut() {
+            Some(last) if r.start <= last.end => {
+                last.end = last.end.max(r.end);
+            }
+            _ => ret.push(r),
+        }
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+
+    #[test]
+    fn enumeration() {
+        let ball = L1Ball::new([1, -2, 3], 4);
+        let cube = ball.bounding_box();
+
+        let brute: HashSet<[i64; 3]> =
+            cube.into_iter().filter(|&p| ball.contains(p)).collect();
+        let points: Vec<[i64; 3]> = ball.points().collect();
+        assert_eq!(points.len(), brute.len());
+        assert_eq!(points.iter().copied().collect::<HashSet<_>>(), brute);
+
+        let boundary: Vec<[i64; 3]> = ball.boundary().collect();
+        assert!(boundary.iter().all(|&p| ball.dist(p) == 4));
+        assert_eq!(
+            boundary.len(),
+            brute.iter().filter(|&&p| ball.dist(p) == 4).count()
+        );
+        assert_eq!(ball.interior().count() + boundary.len(), points.len());
+
+        assert_eq!(L1Ball::new([5], 2).points().count(), 5);
+        assert_eq!(L1Ball::new([0, 0], 0).boundary().count(), 1);
+        assert_eq!(L1Ball::new([0, 0], 0).interior().count(), 0);
+    }
+
+    #[test]
+    fn intersection() {
+        let a = L1Ball::new([0, 0], 3);
+        let b = L1Ball::new([4, 3], 4);
+        let c = L1Ball::new([4, 3], 3);
+        assert!(a.intersects(&b));
+        assert!(!a.intersects(&c));
+
+        assert!(a.intersects_box(&AxisBox::new([2, 1], [10, 10])));
+        assert!(!a.intersects_box(&AxisBox::new([2, 2], [10, 10])));
+        assert!(a.intersects_box(&AxisBox::new([-10, -10], [10, 10])));
+    }
+
+    #[test]
+    fn rotation() {
+        let ball = L1Ball::new([3, -1], 2);
+        let rotated = ball.rotated();
+        for p in rotated {
+            match L1Ball::unrotate(p) {
+                Some(q) => {
+                    assert!(ball.contains(q));
+                    assert_eq!(L1Ball::rotate(q), p);
+                }
+                None => assert!((p[0] - p[1]) % 2 != 0),
+            }
+        }
+        assert_eq!(
+            rotated.into_iter().filter_map(L1Ball::unrotate).count(),
+            ball.points().count()
+        );
+    }
+
+    #[test]
+    fn cover() {
+        let balls = [
+            L1Ball::new([0, 0], 2),
+            L1Ball::new([3, 1], 1),
+            L1Ball::new([10, 0], 3),
+        ];
+        assert_eq!(
+            line_cover(&balls, 0, [0, 1], -100..100),
+            vec![-1..5, 8..13]
+        );
+        assert_eq!(line_cover(&balls, 0, [0, 1], 0..10), vec![0..5, 8..10]);
+        assert_eq!(line_cover(&balls, 1, [0, 0], -100..100), vec![-2..3]);
+    }
+}
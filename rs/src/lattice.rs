@@ -0,0 +1,268 @@
+use crate::axis_box::{pmax, AxisBox, Element};
+
+/// Distance metrics for lattice enumeration and nearest neighbor queries.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum Metric {
+    Taxicab,
+    /// Squared Euclidean distance, so that the value stays in `T`.
+    Euclidean,
+    Chessboard,
+}
+
+impl Metric {
+    /// Combine per-axis distances into a metric distance.
+    fn combine<T: Element>(self, d: impl IntoIterator<Item = T>) -> T {
+        match self {
+            Metric::Taxicab => d.into_iter().fold(T::zero(), |a, b| a + b),
+            Metric::Euclidean => {
+                d.into_iter().fold(T::zero(), |a, b| a + b * b)
+            }
+            Metric::Chessboard => d.into_iter().fold(T::zero(), pmax),
+        }
+    }
+
+    pub fn dist<T: Element, const N: usize>(
+        self,
+        a: impl Into<[T; N]>,
+        b: impl Into<[T; N]>,
+    ) -> T {
+        let (a, b) = (a.into(), b.into());
+        self.combine((0..N).map(|i| {
+            if a[i] < b[i] {
+                b[i] - a[i]
+            } else {
+                a[i] - b[i]
+            }
+        }))
+    }
+
+    /// Distance from a point to the nearest point of a closed box.
+    pub fn box_dist<T: Element, const N: usize>(
+        self,
+        p: impl Into<[T; N]>,
+        b: &AxisBox<T, N>,
+    ) -> T {
+        let p = p.into();
+        self.combine((0..N).map(|i| {
+            if p[i] < b.p0[i] {
+                b.p0[i] - p[i]
+            } else if p[i] > b.p1[i] {
+                p[i] - b.p1[i]
+            } else {
+                T::zero()
+            }
+        }))
+    }
+}
+
+impl Metric {
+    /// Iterate integer points at exactly distance `r` from `center`.
+    ///
+    /// For `Euclidean`, `r` is the squared distance like everywhere else.
+    pub fn shell<const N: usize>(
+        self,
+        center: impl Into<[i64; N]>,
+        r: i64,
+    ) -> Shell<N> {
+        // Bound for the individual axis coordinates.
+        let bound = match self {
+            Metric::Euclidean => isqrt(r),
+            _ => r,
+        };
+        Shell {
+            metric: self,
+            center: center.into(),
+            r,
+            bound,
+            prefix: [-bound; N],
+            pending: Vec::new(),
+            done: N == 0 || r < 0,
+        }
+    }
+
+    /// Iterate all integer points in order of increasing distance from
+    /// `center`.
+    ///
+    /// ```
+    /// # use aoc::prelude::*;
+    /// let points: Vec<[i64; 2]> =
+    ///     Metric::Chessboard.points([0, 0]).take(9).collect();
+    /// assert_eq!(points[0], [0, 0]);
+    /// assert!(points.iter().all(|&p| Metric::Chessboard.dist(p, [0, 0]) <= 1));
+    /// ```
+    pub fn points<const N: usize>(
+        self,
+        center: impl Into<[i64; N]>,
+    ) -> impl Iterator<Item = [i64; N]> {
+        let center = center.into();
+        (0..).flat_map(move |r| self.shell(center, r))
+    }
+}
+
+/// Iterator for the points of a lattice shell, see `Metric::shell`.
+#[derive(Clone, Debug)]
+pub struct Shell<const N: usize> {
+    metric: Metric,
+    center: [i64; N],
+    r: i64,
+    bound: i64,
+    /// Odometer for the first N - 1 axes, the last axis is solved from the
+    /// remaining distance.
+    prefix: [i64; N],
+    pending: Vec<[i64; N]>,
+    done: bool,
+}
+
+impl<const N: usize> Shell<N> {
+    /// Generate points for the current prefix.
+    fn fill(&mut self) {
+        let prefix = &self.prefix[..N - 1];
+        let r = self.r;
+        let (lo, hi) = match self.metric {
+            Metric::Taxicab => {
+                let s: i64 = prefix.iter().map(|x| x.abs()).sum();
+                (r - s, r - s)
+            }
+            Metric::Euclidean => {
+                let s: i64 = prefix.iter().map(|x| x * x).sum();
+                let q = isqrt(r - s);
+                if q * q != r - s {
+                    return;
+                }
+                (q, q)
+            }
+            Metric::Chessboard => {
+                let m = prefix.iter().map(|x| x.abs()).max().unwrap_or(0);
+                if m == r {
+                    // Already on the shell, last axis can be anything.
+                    (-r, r)
+                } else {
+                    (r, r)
+                }
+            }
+        };
+        if hi < 0 || lo > hi {
+            return;
+        }
+
+        let mut p = self.center;
+        for (a, b) in p.iter_mut().zip(prefix) {
+            *a += b;
+        }
+        let mut push = |x| {
+            p[N - 1] = self.center[N - 1] + x;
+            self.pending.push(p);
+        };
+        if lo == hi {
+            push(-lo);
+            if lo != 0 {
+                push(lo);
+            }
+        } else {
+            for x in (lo..=hi).rev() {
+                push(x);
+            }
+        }
+    }
+}
+
+impl<const N: usize> Iterator for Shell<N> {
+    type Item = [i64; N];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(p) = self.pending.pop() {
+                return Some(p);
+            }
+            if self.done {
+                return None;
+            }
+
+            self.fill();
+
+            // Advance odometer.
+            self.done = true;
+            for x in self.prefix[..N - 1].iter_mut() {
+                if *x < self.bound {
+                    *x += 1;
+                    self.done = false;
+                    break;
+                }
+                *x = -self.bound;
+            }
+        }
+    }
+}
+
+/// Integer square root, rounded down. Negative inputs give -1.
+fn isqrt(n: i64) -> i64 {
+    if n < 0 {
+        return -1;
+    }
+    let mut x = (n as f64).sqrt() as i64;
+    while x * x > n {
+        x -= 1;
+    }
+    while (x + 1) * (x + 1) <= n {
+        x += 1;
+    }
+    x
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+
+    fn check_coverage<const N: usize>(metric: Metric, center: [i64; N]) {
+        let bounds = AxisBox::new(center.map(|x| x - 4), center.map(|x| x + 4));
+
+        // Go far enough to cover the corners of bounds.
+        let max = metric.dist(bounds.min(), center);
+
+        let mut points = HashSet::default();
+        let mut prev = 0;
+        for p in metric
+            .points(center)
+            .take_while(|&p| metric.dist(p, center) <= max)
+        {
+            assert!(!points.contains(&p));
+            // Distances never decrease.
+            let d = metric.dist(p, center);
+            assert!(d >= prev);
+            prev = d;
+            points.insert(p);
+        }
+
+        for p in bounds {
+            assert!(points.contains(&p));
+        }
+    }
+
+    #[test]
+    fn coverage() {
+        for metric in [Metric::Taxicab, Metric::Chessboard, Metric::Euclidean] {
+            check_coverage(metric, [0]);
+            check_coverage(metric, [3, -2]);
+            check_coverage(metric, [0, 0, 0]);
+            check_coverage(metric, [1, 2, 3, 4]);
+        }
+    }
+
+    #[test]
+    fn shells() {
+        let count = |metric: Metric, r| metric.shell([0, 0], r).count();
+        assert_eq!(count(Metric::Taxicab, 0), 1);
+        assert_eq!(count(Metric::Taxicab, 3), 12);
+        assert_eq!(count(Metric::Chessboard, 0), 1);
+        assert_eq!(count(Metric::Chessboard, 3), 24);
+        assert_eq!(count(Metric::Euclidean, 25), 12);
+        assert_eq!(count(Metric::Euclidean, 3), 0);
+
+        assert_eq!(Metric::Taxicab.shell([0, 0, 0], 2).count(), 18);
+        assert_eq!(Metric::Chessboard.shell([0, 0, 0], 1).count(), 26);
+        for p in Metric::Taxicab.shell([5, 5, 5], 7) {
+            assert_eq!(Metric::Taxicab.dist(p, [5, 5, 5]), 7);
+        }
+    }
+}
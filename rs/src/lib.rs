@@ -1,8 +1,25 @@
+// Lets derive macro output refer to `::aoc` inside this crate too.
+extern crate self as aoc;
+
 mod md5;
 mod ocr;
 mod operand;
-pub use operand::Operand;
+pub use operand::{Operand, ParseOperandError, RegisterName};
 
 pub mod axis_box;
+pub mod box_set;
+pub mod circuit;
+pub mod l1_ball;
+pub mod lattice;
+pub mod nested;
+pub mod numbers;
+pub mod numeral;
+pub mod parse;
+pub mod parsers;
+pub mod pulse;
+pub mod rotation;
+pub mod scan;
+pub mod spatial_index;
+pub mod vm;
 
 pub mod prelude;
@@ -0,0 +1,322 @@
+//! Lisp-like nested lists.
+
+use std::{cmp::Ordering, fmt, str::FromStr};
+
+use nom::{bytes::complete::take_while1, combinator::map_res, IResult};
+use num_traits::PrimInt;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    parse::ParseError,
+    parsers::{nested, parse_all},
+};
+
+/// Value that is either an atom or a list of nested values, written like
+/// `[1,[2,[3]],4]`.
+///
+/// Values are ordered by comparing lists element-wise, an atom compared to a
+/// list is promoted to a list of one element. Because of this an atom is
+/// equal to a list that contains only that atom.
+///
+/// Serializes to JSON as numbers and arrays.
+///
+/// ```
+/// # use aoc::prelude::*;
+/// let a: Nested<u32> = "[1,[2,3]]".parse().unwrap();
+/// let b: Nested<u32> = serde_json::from_str("[[1],4]").unwrap();
+/// assert!(a < b);
+/// assert_eq!(a.atoms().sum::<u32>(), 6);
+/// ```
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Nested<T> {
+    Atom(T),
+    List(Vec<Nested<T>>),
+}
+
+use Nested::*;
+
+impl<T> Nested<T> {
+    pub fn as_atom(&self) -> Option<&T> {
+        match self {
+            Atom(a) => Some(a),
+            List(_) => None,
+        }
+    }
+
+    pub fn as_list(&self) -> Option<&[Nested<T>]> {
+        match self {
+            Atom(_) => None,
+            List(a) => Some(a),
+        }
+    }
+
+    /// Levels of lists around the most deeply nested value, 0 for an atom.
+    pub fn depth(&self) -> usize {
+        match self {
+            Atom(_) => 0,
+            List(a) => 1 + a.iter().map(Nested::depth).max().unwrap_or(0),
+        }
+    }
+
+    /// Iterate the atoms from left to right along with the number of lists
+    /// that contain them.
+    pub fn leaves(&self) -> impl Iterator<Item = (usize, &T)> + '_ {
+        let mut stack = vec![(0, self)];
+        std::iter::from_fn(move || {
+            while let Some((depth, node)) = stack.pop() {
+                match node {
+                    Atom(a) => return Some((depth, a)),
+                    List(a) => {
+                        stack.extend(a.iter().rev().map(|e| (depth + 1, e)))
+                    }
+                }
+            }
+            None
+        })
+    }
+
+    /// Iterate the atoms from left to right.
+    pub fn atoms(&self) -> impl Iterator<Item = &T> + '_ {
+        self.leaves().map(|(_, a)| a)
+    }
+
+    /// Atoms from left to right with the list structure removed.
+    pub fn flatten(self) -> Vec<T> {
+        fn push<T>(ret: &mut Vec<T>, node: Nested<T>) {
+            match node {
+                Atom(a) => ret.push(a),
+                List(a) => a.into_iter().for_each(|e| push(ret, e)),
+            }
+        }
+
+        let mut ret = Vec::new();
+        push(&mut ret, self);
+        ret
+    }
+
+    pub fn map<U>(self, f: &impl Fn(T) -> U) -> Nested<U> {
+        match self {
+            Atom(a) => Atom(f(a)),
+            List(a) => List(a.into_iter().map(|e| e.map(f)).collect()),
+        }
+    }
+
+    fn first_atom_mut(&mut self) -> Option<&mut T> {
+        match self {
+            Atom(a) => Some(a),
+            List(a) => a.iter_mut().find_map(Nested::first_atom_mut),
+        }
+    }
+
+    fn last_atom_mut(&mut self) -> Option<&mut T> {
+        match self {
+            Atom(a) => Some(a),
+            List(a) => a.iter_mut().rev().find_map(Nested::last_atom_mut),
+        }
+    }
+}
+
+impl<T: PrimInt> Nested<T> {
+    /// Explode the leftmost pair of atoms nested inside `depth` lists.
+    ///
+    /// The pair is replaced with a zero and its values are added to the
+    /// nearest atoms on the left and on the right. Returns whether a pair
+    /// exploded.
+    pub fn explode(&mut self, depth: usize) -> bool {
+        self.explode_inner(depth).is_some()
+    }
+
+    /// Explode a pair, return the values that haven't yet been added to a
+    /// neighbor.
+    fn explode_inner(
+        &mut self,
+        depth: usize,
+    ) -> Option<(Option<T>, Option<T>)> {
+        let List(elts) = self else { return None };
+        if depth == 0 {
+            if let [Atom(a), Atom(b)] = elts[..] {
+                *self = Atom(T::zero());
+                return Some((Some(a), Some(b)));
+            }
+        }
+
+        for i in 0..elts.len() {
+            let Some((mut left, mut right)) =
+                elts[i].explode_inner(depth.saturating_sub(1))
+            else {
+                continue;
+            };
+            if let Some(a) = left {
+                if let Some(x) =
+                    elts[..i].iter_mut().rev().find_map(Nested::last_atom_mut)
+                {
+                    *x = *x + a;
+                    left = None;
+                }
+            }
+            if let Some(b) = right {
+                if let Some(x) =
+                    elts[i + 1..].iter_mut().find_map(Nested::first_atom_mut)
+                {
+                    *x = *x + b;
+                    right = None;
+                }
+            }
+            return Some((left, right));
+        }
+        None
+    }
+
+    /// Split the leftmost atom that is at least `limit` into a pair of its
+    /// halves, rounding the left one down and the right one up. Returns
+    /// whether an atom was split.
+    pub fn split(&mut self, limit: T) -> bool {
+        match self {
+            Atom(a) if *a >= limit => {
+                let half = *a / (T::one() + T::one());
+                *self = List(vec![Atom(half), Atom(*a - half)]);
+                true
+            }
+            Atom(_) => false,
+            List(a) => a.iter_mut().any(|e| e.split(limit)),
+        }
+    }
+
+    /// Reduce like a snailfish number, explode pairs nested `depth` deep
+    /// and split atoms from `limit` up until neither applies. Explosions go
+    /// before splits.
+    pub fn reduce(&mut self, depth: usize, limit: T) {
+        while self.explode(depth) || self.split(limit) {}
+    }
+}
+
+/// Atom text, anything up to a bracket, comma or whitespace.
+fn atom<T: FromStr>(input: &str) -> IResult<&str, T> {
+    map_res(
+        take_while1(|c: char| {
+            !matches!(c, '[' | ']' | ',') && !c.is_whitespace()
+        }),
+        str::parse,
+    )(input)
+}
+
+impl<T: FromStr> FromStr for Nested<T> {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_all(s, nested(atom, Atom, List))
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for Nested<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Atom(a) => write!(f, "{a}"),
+            List(a) => {
+                write!(f, "[")?;
+                for (i, e) in a.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{e}")?;
+                }
+                write!(f, "]")
+            }
+        }
+    }
+}
+
+impl<T: Ord> Ord for Nested<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Atom(a), Atom(b)) => a.cmp(b),
+            (Atom(_), List(b)) => std::slice::from_ref(self).cmp(b),
+            (List(a), Atom(_)) => a[..].cmp(std::slice::from_ref(other)),
+            (List(a), List(b)) => a.cmp(b),
+        }
+    }
+}
+
+impl<T: Ord> PartialOrd for Nested<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T: Ord> PartialEq for Nested<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<T: Ord> Eq for Nested<T> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn n(s: &str) -> Nested<u32> {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn text() {
+        let a = n("[1,[2, [3]],[],4]");
+        assert_eq!(a.to_string(), "[1,[2,[3]],[],4]");
+        assert_eq!(a.depth(), 3);
+        assert_eq!(
+            a.leaves().collect::<Vec<_>>(),
+            [(1, &1), (2, &2), (3, &3), (1, &4)]
+        );
+        assert_eq!(a.clone().flatten(), [1, 2, 3, 4]);
+        assert_eq!(a.as_list().map(|a| a.len()), Some(4));
+        assert_eq!(n("7").as_atom(), Some(&7));
+
+        let json = serde_json::to_string(&a).unwrap();
+        assert_eq!(json, "[1,[2,[3]],[],4]");
+        assert_eq!(serde_json::from_str::<Nested<u32>>(&json).unwrap(), a);
+
+        assert!("[1,[x]]".parse::<Nested<u32>>().is_err());
+    }
+
+    #[test]
+    fn order() {
+        // Pairs from the 2022 day 13 example.
+        for (a, b, ord) in [
+            ("[1,1,3,1,1]", "[1,1,5,1,1]", Ordering::Less),
+            ("[[1],[2,3,4]]", "[[1],4]", Ordering::Less),
+            ("[9]", "[[8,7,6]]", Ordering::Greater),
+            ("[[4,4],4,4]", "[[4,4],4,4,4]", Ordering::Less),
+            ("[7,7,7,7]", "[7,7,7]", Ordering::Greater),
+            ("[]", "[3]", Ordering::Less),
+            ("[[[]]]", "[[]]", Ordering::Greater),
+        ] {
+            assert_eq!(n(a).cmp(&n(b)), ord, "{a} vs {b}");
+        }
+        assert_eq!(n("3"), n("[[3]]"));
+    }
+
+    #[test]
+    fn snailfish() {
+        for (a, b) in [
+            ("[[[[[9,8],1],2],3],4]", "[[[[0,9],2],3],4]"),
+            ("[7,[6,[5,[4,[3,2]]]]]", "[7,[6,[5,[7,0]]]]"),
+            ("[[6,[5,[4,[3,2]]]],1]", "[[6,[5,[7,0]]],3]"),
+        ] {
+            let mut a = n(a);
+            assert!(a.explode(4));
+            assert_eq!(a.to_string(), b);
+        }
+        assert!(!n("[[1,2],[[3,4],5]]").explode(4));
+
+        let mut a = n("[11,3]");
+        assert!(a.split(10));
+        assert_eq!(a.to_string(), "[[5,6],3]");
+        assert!(!a.split(10));
+
+        let mut a = n("[[[[[4,3],4],4],[7,[[8,4],9]]],[1,1]]");
+        a.reduce(4, 10);
+        assert_eq!(a.to_string(), "[[[[0,7],4],[[7,8],[6,0]]],[8,1]]");
+    }
+}
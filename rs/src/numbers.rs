@@ -0,0 +1,354 @@
+//! Configurable number extraction from free-form text.
+
+use std::{borrow::Cow, ops::RangeInclusive};
+
+use fraction::Fraction;
+use num_traits::PrimInt;
+
+use crate::parse::ParseError;
+
+/// Number extractor that skips anything that isn't part of a number.
+///
+/// The default extracts signed decimal integers the same way
+/// [`numbers`](crate::prelude::numbers) does, a minus sign right before the
+/// digits is part of the number unless it directly follows the previous
+/// number.
+///
+/// ```
+/// # use aoc::prelude::*;
+/// let hex = Numbers::unsigned().with_prefixes();
+/// assert_eq!(hex.ints::<u32>("R 6 (#70c710)"), Ok(vec![6, 0x70c710]));
+/// let ranges = Numbers::unsigned().ranges::<u32>("2-4,6-8").unwrap();
+/// assert_eq!(ranges, [2..=4, 6..=8]);
+/// ```
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Numbers {
+    signed: bool,
+    radix: u32,
+    prefixes: bool,
+}
+
+impl Default for Numbers {
+    fn default() -> Self {
+        Numbers::signed()
+    }
+}
+
+/// Position of a number in the text.
+struct Token {
+    /// Start of the number including a minus sign.
+    start: usize,
+    /// Start of the digits after any prefix.
+    digits: usize,
+    end: usize,
+    radix: u32,
+}
+
+impl Token {
+    fn is_negative(&self, s: &str) -> bool {
+        s.as_bytes()[self.start] == b'-'
+    }
+
+    /// The number without its radix prefix.
+    fn text<'a>(&self, s: &'a str) -> Cow<'a, str> {
+        let neg = self.is_negative(s);
+        if self.digits == self.start + neg as usize {
+            Cow::Borrowed(&s[self.start..self.end])
+        } else if !neg {
+            Cow::Borrowed(&s[self.digits..self.end])
+        } else {
+            Cow::Owned(format!("-{}", &s[self.digits..self.end]))
+        }
+    }
+}
+
+impl Numbers {
+    /// Decimal numbers that can have a minus sign.
+    pub fn signed() -> Self {
+        Numbers {
+            signed: true,
+            radix: 10,
+            prefixes: false,
+        }
+    }
+
+    /// Decimal numbers where every `-` is a separator.
+    pub fn unsigned() -> Self {
+        Numbers {
+            signed: false,
+            ..Numbers::signed()
+        }
+    }
+
+    /// Read digits in the given radix, from 2 to 36.
+    pub fn with_radix(mut self, radix: u32) -> Self {
+        assert!((2..=36).contains(&radix), "Numbers: bad radix {radix}");
+        self.radix = radix;
+        self
+    }
+
+    /// Also read hexadecimal numbers prefixed with `0x` or `#`, octal with
+    /// `0o` and binary with `0b`.
+    pub fn with_prefixes(mut self) -> Self {
+        self.prefixes = true;
+        self
+    }
+
+    fn prefix(&self, rest: &[u8]) -> Option<(usize, u32)> {
+        if !self.prefixes {
+            return None;
+        }
+        let (len, radix) = match rest {
+            [b'#', ..] => (1, 16),
+            [b'0', b'x' | b'X', ..] => (2, 16),
+            [b'0', b'o' | b'O', ..] => (2, 8),
+            [b'0', b'b' | b'B', ..] => (2, 2),
+            _ => return None,
+        };
+        rest.get(len)
+            .is_some_and(|&c| (c as char).is_digit(radix))
+            .then_some((len, radix))
+    }
+
+    /// Positions of the numbers in `s`, with `radix` overriding the
+    /// configured one.
+    fn tokens<'a>(
+        &'a self,
+        s: &'a str,
+        radix: Option<u32>,
+    ) -> impl Iterator<Item = Token> + 'a {
+        let bytes = s.as_bytes();
+        let mut i = 0;
+        // End of the previous number, a `-` there is a separator.
+        let mut prev_end = None;
+        std::iter::from_fn(move || {
+            while i < bytes.len() {
+                let (prefix, radix) = match radix {
+                    Some(r) => (0, r),
+                    None => self.prefix(&bytes[i..]).unwrap_or((0, self.radix)),
+                };
+                if prefix == 0 && !(bytes[i] as char).is_digit(radix) {
+                    i += 1;
+                    continue;
+                }
+
+                let start = if self.signed
+                    && i > 0
+                    && bytes[i - 1] == b'-'
+                    && prev_end != Some(i - 1)
+                {
+                    i - 1
+                } else {
+                    i
+                };
+                let digits = i + prefix;
+                let mut end = digits;
+                while end < bytes.len() && (bytes[end] as char).is_digit(radix)
+                {
+                    end += 1;
+                }
+                i = end;
+                prev_end = Some(end);
+                return Some(Token {
+                    start,
+                    digits,
+                    end,
+                    radix,
+                });
+            }
+            None
+        })
+    }
+
+    /// Extract integers from the text.
+    pub fn ints<T: PrimInt>(&self, s: &str) -> Result<Vec<T>, ParseError> {
+        self.tokens(s, None)
+            .map(|t| {
+                T::from_str_radix(&t.text(s), t.radix).map_err(|_| {
+                    ParseError::of_type::<T>(&s[t.start..t.end])
+                        .at_offset(s, t.start)
+                })
+            })
+            .collect()
+    }
+
+    /// Extract a fixed amount of integers into an array.
+    pub fn fixed<T: PrimInt, const N: usize>(
+        &self,
+        s: &str,
+    ) -> Result<[T; N], ParseError> {
+        let elts = self.ints(s)?;
+        let n = elts.len();
+        elts.try_into().map_err(|_| {
+            ParseError::new(s, format!("{N} numbers, got {n}")).at(1, 1)
+        })
+    }
+
+    /// Extract inclusive ranges written as `a-b` or `a..b`.
+    ///
+    /// Every number must be part of a range. Use unsigned numbers for `a-b`
+    /// if the numbers can't be negative, signed numbers read `1--2` as a
+    /// range from 1 to -2.
+    pub fn ranges<T: PrimInt>(
+        &self,
+        s: &str,
+    ) -> Result<Vec<RangeInclusive<T>>, ParseError> {
+        let tokens: Vec<Token> = self.tokens(s, None).collect();
+        let values = self.ints::<T>(s)?;
+        let mut ret = Vec::new();
+        let mut i = 0;
+        while i < tokens.len() {
+            let sep = tokens.get(i + 1).map(|b| &s[tokens[i].end..b.start]);
+            if !matches!(sep, Some("-" | "..")) {
+                let t = &tokens[i];
+                return Err(ParseError::new(&s[t.start..t.end], "range")
+                    .at_offset(s, t.start));
+            }
+            ret.push(values[i]..=values[i + 1]);
+            i += 2;
+        }
+        Ok(ret)
+    }
+
+    /// Token and digits after a decimal point for each decimal number.
+    fn decimal_tokens<'a>(
+        &'a self,
+        s: &'a str,
+    ) -> impl Iterator<Item = (Token, &'a str)> + 'a {
+        let bytes = s.as_bytes();
+        let mut skip_to = 0;
+        self.tokens(s, Some(10)).filter_map(move |mut t| {
+            if t.start < skip_to {
+                // Fraction digits of the previous number.
+                return None;
+            }
+            let mut frac = "";
+            if bytes.get(t.end) == Some(&b'.')
+                && bytes.get(t.end + 1).is_some_and(u8::is_ascii_digit)
+            {
+                let end = t.end
+                    + 1
+                    + bytes[t.end + 1..]
+                        .iter()
+                        .take_while(|c| c.is_ascii_digit())
+                        .count();
+                frac = &s[t.end + 1..end];
+                t.end = end;
+                skip_to = end;
+            }
+            Some((t, frac))
+        })
+    }
+
+    /// Extract decimal numbers like `-1.25` as floats.
+    pub fn decimals(&self, s: &str) -> Result<Vec<f64>, ParseError> {
+        self.decimal_tokens(s)
+            .map(|(t, _)| {
+                let text = &s[t.start..t.end];
+                text.parse().map_err(|_| {
+                    ParseError::of_type::<f64>(text).at_offset(s, t.start)
+                })
+            })
+            .collect()
+    }
+
+    /// Extract decimal numbers like `-1.25` as exact fractions.
+    pub fn fractions(&self, s: &str) -> Result<Vec<Fraction>, ParseError> {
+        self.decimal_tokens(s)
+            .map(|(t, frac)| {
+                let text = &s[t.start..t.end];
+                let err = || {
+                    ParseError::of_type::<Fraction>(text).at_offset(s, t.start)
+                };
+                let int = &s[t.digits..t.end - frac.len()];
+                let int = int.strip_suffix('.').unwrap_or(int);
+                let num: u64 =
+                    format!("{int}{frac}").parse().map_err(|_| err())?;
+                let den =
+                    10u64.checked_pow(frac.len() as u32).ok_or_else(err)?;
+                let ret = Fraction::new(num, den);
+                Ok(if t.is_negative(s) { -ret } else { ret })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ints() {
+        let s = "x=-12, 5-10 0x1f #ff 0b101 -0x10";
+        assert_eq!(
+            Numbers::signed().ints::<i64>(s),
+            Ok(vec![-12, 5, 10, 0, 1, 0, 101, 0, 10])
+        );
+        assert_eq!(
+            Numbers::unsigned().ints::<u64>(s),
+            Ok(vec![12, 5, 10, 0, 1, 0, 101, 0, 10])
+        );
+        assert_eq!(
+            Numbers::signed().with_prefixes().ints::<i64>(s),
+            Ok(vec![-12, 5, 10, 31, 255, 5, -16])
+        );
+        assert_eq!(
+            Numbers::unsigned().with_radix(2).ints::<u8>("10 x 111 2"),
+            Ok(vec![2, 7])
+        );
+        assert_eq!(
+            Numbers::unsigned().with_radix(16).fixed::<u32, 2>("ff:C0"),
+            Ok([255, 192])
+        );
+
+        let e = Numbers::signed().ints::<u8>("1 -2").unwrap_err();
+        assert_eq!((e.col, e.text.as_str()), (3, "-2"));
+        assert!(Numbers::signed().fixed::<i32, 3>("1 2").is_err());
+
+        // Same as the prelude function.
+        for s in ["1-2", "a -3 b--4", "x=5,y=-6", "10..-20"] {
+            assert_eq!(Numbers::default().ints::<i32>(s), Ok(numbers(s)));
+        }
+    }
+
+    fn numbers(s: &str) -> Vec<i32> {
+        crate::prelude::numbers(s)
+    }
+
+    #[test]
+    fn ranges() {
+        assert_eq!(
+            Numbers::unsigned().ranges::<u32>("2-4,6-8"),
+            Ok(vec![2..=4, 6..=8])
+        );
+        assert_eq!(
+            Numbers::signed().ranges::<i32>("x=-20..26,y=5-10,z=1--2"),
+            Ok(vec![-20..=26, 5..=10, RangeInclusive::new(1, -2)])
+        );
+        let e = Numbers::signed().ranges::<i32>("1..2 3 4..5").unwrap_err();
+        assert_eq!(
+            (e.col, e.text.as_str(), e.expected.as_str()),
+            (6, "3", "range")
+        );
+    }
+
+    #[test]
+    fn decimals() {
+        let s = "p=1.5, v=-0.25 x 3..4 7.";
+        assert_eq!(
+            Numbers::signed().decimals(s),
+            Ok(vec![1.5, -0.25, 3.0, 4.0, 7.0])
+        );
+        assert_eq!(
+            Numbers::signed().fractions(s),
+            Ok(vec![
+                Fraction::new(3u64, 2u64),
+                -Fraction::new(1u64, 4u64),
+                Fraction::from(3),
+                Fraction::from(4),
+                Fraction::from(7),
+            ])
+        );
+        assert_eq!(Numbers::unsigned().decimals("1-2.5"), Ok(vec![1.0, 2.5]));
+    }
+}
@@ -0,0 +1,379 @@
+//! Positional numeral systems.
+
+use num_traits::PrimInt;
+
+use crate::parse::ParseError;
+
+/// Digits of `n` in `base`, least significant first.
+///
+/// Zero has no digits and the digits of a negative number are negative.
+pub fn digits<T: PrimInt>(n: T, base: T) -> impl Iterator<Item = T> {
+    assert!(base > T::one(), "digits: bad base");
+    let mut n = n;
+    std::iter::from_fn(move || {
+        if n.is_zero() {
+            return None;
+        }
+        let ret = n % base;
+        n = n / base;
+        Some(ret)
+    })
+}
+
+/// Number from its digits in `base`, least significant first.
+///
+/// Panics on overflow.
+pub fn from_digits<T: PrimInt>(
+    digits: impl IntoIterator<Item = T, IntoIter: DoubleEndedIterator>,
+    base: T,
+) -> T {
+    digits.into_iter().rev().fold(T::zero(), |a, d| {
+        a.checked_mul(&base)
+            .and_then(|a| a.checked_add(&d))
+            .expect("from_digits: overflow")
+    })
+}
+
+/// Positional numeral system with a symbol for each digit.
+///
+/// The base is the number of symbols and the digit values are consecutive
+/// starting from the value of the first symbol. A system where the first
+/// digit is zero writes negative numbers with a minus sign, balanced systems
+/// with negative digits write them with the digits alone.
+///
+/// ```
+/// # use aoc::prelude::*;
+/// let snafu = Numeral::balanced("=-012");
+/// assert_eq!(snafu.format(2022), "1=11-2");
+/// assert_eq!(snafu.parse::<i64>("1=11-2"), Ok(2022));
+/// assert_eq!(Numeral::base(16).format(-255), "-ff");
+/// ```
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Numeral {
+    symbols: Vec<char>,
+    /// Value of the first symbol.
+    low: i32,
+}
+
+impl Numeral {
+    /// System with the given symbols where the first one has the value
+    /// `low`.
+    ///
+    /// Zero must be one of the digit values. If there are negative digits,
+    /// there must also be positive ones.
+    pub fn new(symbols: &str, low: i32) -> Self {
+        let symbols: Vec<char> = symbols.chars().collect();
+        let high = low + symbols.len() as i32 - 1;
+        assert!(symbols.len() >= 2, "Numeral: need at least two symbols");
+        assert!(
+            low == 0 || (low < 0 && high > 0),
+            "Numeral: bad digit range {low}..={high}"
+        );
+        Numeral { symbols, low }
+    }
+
+    /// Standard system in base 2 to 36 with digits `0-9` and `a-z`.
+    pub fn base(radix: u32) -> Self {
+        assert!((2..=36).contains(&radix), "Numeral: bad radix {radix}");
+        let symbols: String = (0..radix)
+            .map(|d| char::from_digit(d, radix).unwrap())
+            .collect();
+        Numeral::new(&symbols, 0)
+    }
+
+    /// Balanced system with an odd number of symbols where the middle one is
+    /// zero, like `-0+` for balanced ternary.
+    pub fn balanced(symbols: &str) -> Self {
+        let n = symbols.chars().count() as i32;
+        assert!(n % 2 == 1, "Numeral: balanced system needs odd base");
+        Numeral::new(symbols, -(n / 2))
+    }
+
+    pub fn radix(&self) -> u32 {
+        self.symbols.len() as u32
+    }
+
+    fn is_signed(&self) -> bool {
+        self.low == 0
+    }
+
+    /// Digit values of `n`, least significant first.
+    ///
+    /// Zero has no digits. In a system without negative digits, the digits
+    /// of a negative number are negative.
+    pub fn digits<T: PrimInt>(&self, n: T) -> impl Iterator<Item = i32> {
+        let base = self.radix() as i32;
+        let b = T::from(base).expect("Numeral: base doesn't fit type");
+        let low = self.low;
+        let mut n = n;
+        std::iter::from_fn(move || {
+            if n.is_zero() {
+                return None;
+            }
+            let r = (n % b).to_i32().unwrap();
+            let d = if low == 0 && r < 0 {
+                r
+            } else {
+                (r - low).rem_euclid(base) + low
+            };
+            // The remainder and the digit differ by -1, 0 or 1 times the
+            // base, so the quotient needs a carry step at most.
+            n = n / b;
+            match (r - d) / base {
+                1 => n = n + T::one(),
+                -1 => n = n - T::one(),
+                _ => {}
+            }
+            Some(d)
+        })
+    }
+
+    /// Number from its digit values, least significant first.
+    ///
+    /// Returns `None` if the number doesn't fit in the type.
+    pub fn from_digits<T: PrimInt>(
+        &self,
+        digits: impl IntoIterator<Item = i32, IntoIter: DoubleEndedIterator>,
+    ) -> Option<T> {
+        let b = T::from(self.radix())?;
+        let add = |a: T, d: i32| {
+            if d < 0 {
+                a.checked_sub(&T::from(-d)?)
+            } else {
+                a.checked_add(&T::from(d)?)
+            }
+        };
+        digits.into_iter().rev().try_fold(T::zero(), |a, d| {
+            match a.checked_mul(&b) {
+                Some(a) => add(a, d),
+                // With negative digits the multiplication can overflow when
+                // the result still fits, go through a multiple closer to
+                // zero then.
+                None if a > T::zero() => {
+                    add((a - T::one()).checked_mul(&b)?, d)?.checked_add(&b)
+                }
+                None => {
+                    add((a + T::one()).checked_mul(&b)?, d)?.checked_sub(&b)
+                }
+            }
+        })
+    }
+
+    fn symbol(&self, d: i32) -> char {
+        self.symbols[(d - self.low) as usize]
+    }
+
+    /// Write the number with the system's symbols.
+    pub fn format<T: PrimInt>(&self, n: T) -> String {
+        if n.is_zero() {
+            return self.symbol(0).to_string();
+        }
+        let mut ret = Vec::new();
+        for d in self.digits(n) {
+            ret.push(self.symbol(if self.is_signed() { d.abs() } else { d }));
+        }
+        if self.is_signed() && n < T::zero() {
+            ret.push('-');
+        }
+        ret.into_iter().rev().collect()
+    }
+
+    /// Read a number written with the system's symbols.
+    pub fn parse<T: PrimInt>(&self, s: &str) -> Result<T, ParseError> {
+        let (neg, body) = match s.strip_prefix('-') {
+            Some(body) if self.is_signed() && !self.symbols.contains(&'-') => {
+                (true, body)
+            }
+            _ => (false, s),
+        };
+
+        let mut digits = Vec::new();
+        for (i, c) in body.chars().enumerate() {
+            let Some(idx) = self.symbols.iter().position(|&a| a == c) else {
+                let symbols: String = self.symbols.iter().collect();
+                return Err(ParseError::new(
+                    c,
+                    format!("digit in {symbols:?}"),
+                )
+                .at(1, i + 1 + neg as usize));
+            };
+            let d = idx as i32 + self.low;
+            digits.push(if neg { -d } else { d });
+        }
+        if digits.is_empty() {
+            return Err(ParseError::new(s, "digits").at(1, 1));
+        }
+        self.from_digits(digits.into_iter().rev())
+            .ok_or_else(|| ParseError::of_type::<T>(s).at(1, 1))
+    }
+}
+
+/// Mixed radix system where each position has its own radix, like the
+/// seconds, minutes and hours of a clock.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct MixedRadix<T> {
+    /// Radixes from the least significant position up.
+    radixes: Vec<T>,
+}
+
+impl<T: PrimInt> MixedRadix<T> {
+    /// System with the radixes given from the least significant position
+    /// up.
+    pub fn new(radixes: impl IntoIterator<Item = T>) -> Self {
+        let radixes: Vec<T> = radixes.into_iter().collect();
+        assert!(
+            radixes.iter().all(|&r| r > T::zero()),
+            "MixedRadix: radixes must be positive"
+        );
+        MixedRadix { radixes }
+    }
+
+    /// Number of values before the wheels wrap around, `None` on overflow.
+    pub fn size(&self) -> Option<T> {
+        self.radixes
+            .iter()
+            .try_fold(T::one(), |a, &r| a.checked_mul(&r))
+    }
+
+    /// Digits of `n`, least significant first.
+    ///
+    /// Each digit is in the range of its radix. There is one digit more
+    /// than there are radixes, the last one counts how many times the
+    /// wheels wrapped around and is negative for a negative `n`.
+    pub fn digits(&self, n: T) -> Vec<T> {
+        let mut n = n;
+        let mut ret = Vec::with_capacity(self.radixes.len() + 1);
+        for &r in &self.radixes {
+            let mut d = n % r;
+            n = n / r;
+            if d < T::zero() {
+                d = d + r;
+                n = n - T::one();
+            }
+            ret.push(d);
+        }
+        ret.push(n);
+        ret
+    }
+
+    /// Number from its digits, least significant first.
+    ///
+    /// Missing digits are zero. Digits don't need to be in the range of
+    /// their radix. Panics on overflow.
+    pub fn value(&self, digits: &[T]) -> T {
+        let mut ret = T::zero();
+        let mut scale = Some(T::one());
+        for (i, &d) in digits.iter().enumerate() {
+            if !d.is_zero() {
+                let s = scale.expect("MixedRadix: overflow");
+                ret = d
+                    .checked_mul(&s)
+                    .and_then(|a| a.checked_add(&ret))
+                    .expect("MixedRadix: overflow");
+            }
+            scale = scale.and_then(|s| {
+                s.checked_mul(self.radixes.get(i).unwrap_or(&T::one()))
+            });
+        }
+        ret
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+    use super::*;
+
+    #[test]
+    fn examples() {
+        let snafu = Numeral::balanced("=-012");
+        for (n, s) in [
+            (0, "0"),
+            (3, "1="),
+            (8, "2="),
+            (20, "1-0"),
+            (2022, "1=11-2"),
+            (314159265, "1121-1110-1=0"),
+            (-3, "-2"),
+        ] {
+            assert_eq!(snafu.format(n), s);
+            assert_eq!(snafu.parse::<i64>(s), Ok(n));
+        }
+
+        assert_eq!(Numeral::base(2).format(-6i8), "-110");
+        assert_eq!(Numeral::base(36).parse::<u32>("zz"), Ok(1295));
+        assert_eq!(Numeral::balanced("-0+").format(-5), "-++");
+        assert_eq!(Numeral::new("abc", 0).parse::<u8>("cab"), Ok(19));
+        assert!(Numeral::new("abc", 0).parse::<u8>("-cab").is_err());
+
+        let e = snafu.parse::<i64>("1=3").unwrap_err();
+        assert_eq!((e.col, e.text.as_str()), (3, "3"));
+        assert!(Numeral::base(10).parse::<u8>("256").is_err());
+        assert!(Numeral::base(10).parse::<i32>("-").is_err());
+
+        assert_eq!(digits(1234u32, 10).collect::<Vec<_>>(), [4, 3, 2, 1]);
+        assert_eq!(from_digits([4, 3, 2, 1], 10), 1234);
+
+        let clock = MixedRadix::new([60, 60, 24]);
+        assert_eq!(clock.size(), Some(86400));
+        assert_eq!(clock.digits(90061), [1, 1, 1, 1]);
+        assert_eq!(clock.digits(-1), [59, 59, 23, -1]);
+        assert_eq!(clock.value(&[30, 2]), 150);
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut rng = SmallRng::seed_from_u64(123);
+        let systems = [
+            Numeral::base(2),
+            Numeral::base(10),
+            Numeral::base(36),
+            Numeral::balanced("=-012"),
+            Numeral::balanced("-0+"),
+            Numeral::new("xyz", -1),
+            Numeral::new("!@#$%", -3),
+        ];
+
+        let mut values = vec![0, 1, -1, i64::MIN, i64::MAX];
+        values
+            .extend((0..200).map(|_| rng.gen::<i64>() >> rng.gen_range(0..63)));
+
+        for sys in &systems {
+            for &n in &values {
+                let s = sys.format(n);
+                assert_eq!(sys.parse::<i64>(&s), Ok(n), "{n} as {s}");
+                let digits: Vec<i32> = sys.digits(n).collect();
+                assert_eq!(sys.from_digits::<i64>(digits), Some(n));
+            }
+            for n in [0u8, 1, 127, 200, 255] {
+                let s = sys.format(n);
+                assert_eq!(sys.parse::<u8>(&s), Ok(n), "{n} as {s}");
+            }
+        }
+
+        for &n in &values {
+            let base = rng.gen_range(2..100);
+            let digits: Vec<i64> = digits(n, base).collect();
+            assert!(digits.iter().all(|d| d.abs() < base));
+            assert_eq!(from_digits(digits, base), n);
+
+            let n = n as i128;
+            assert_eq!(
+                from_digits(super::digits(n, 7).collect::<Vec<_>>(), 7),
+                n
+            );
+
+            let radixes: Vec<i64> = (0..rng.gen_range(0..5))
+                .map(|_| rng.gen_range(1..1000))
+                .collect();
+            let sys = MixedRadix::new(radixes.clone());
+            let n = (n as i64) >> 24;
+            let digits = sys.digits(n);
+            for (d, r) in digits.iter().zip(&radixes) {
+                assert!((0..*r).contains(d));
+            }
+            assert_eq!(sys.value(&digits), n);
+        }
+    }
+}
@@ -1,34 +1,221 @@
-use std::str::FromStr;
+use std::{fmt, str::FromStr};
 
-use serde_with::DeserializeFromStr;
+use serde_with::{DeserializeFromStr, SerializeDisplay};
 
 use crate::prelude::*;
 
-#[derive(Copy, Clone, Debug, DeserializeFromStr)]
-/// An assembly code operand that can be either a constant or a register name.
-pub enum Operand {
-    Int(i64),
-    Reg(char),
+/// Register identifier in an assembly language.
+pub trait RegisterName: Copy {
+    /// Whether the machine has memory, so `[r]` operands can be parsed.
+    const MEMORY: bool = false;
+
+    /// Index of the register in a register file.
+    fn index(self) -> usize;
+
+    /// Parse a register name. Must not accept anything that isn't exactly a
+    /// register name.
+    fn from_name(name: &str) -> Option<Self>;
+}
+
+/// Single letter registers, `'a'` is register 0. There is no memory.
+impl RegisterName for char {
+    fn index(self) -> usize {
+        reg(self)
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        match name.as_bytes() {
+            [c @ b'a'..=b'z'] => Some(*c as char),
+            _ => None,
+        }
+    }
+}
+
+/// An assembly code operand that can be a constant, a register or a memory
+/// location addressed by a register.
+///
+/// Memory operands are only parsed for register types that opt in with
+/// `RegisterName::MEMORY`.
+///
+/// The defaults make plain `Operand` the common 64-bit integer operand with
+/// single-letter register names.
+#[derive(
+    Copy,
+    Clone,
+    Eq,
+    PartialEq,
+    Hash,
+    Debug,
+    DeserializeFromStr,
+    SerializeDisplay,
+)]
+pub enum Operand<T = i64, R = char> {
+    Int(T),
+    Reg(R),
+    /// Memory at the address in the register, written `[r]`.
+    Mem(R),
 }
 
 use Operand::*;
 
-impl Operand {
-    pub fn val(&self, mem: &[i64]) -> i64 {
+impl<T: Copy, R: RegisterName> Operand<T, R> {
+    /// Value of a constant or register operand.
+    ///
+    /// Panics on memory operands, use `load` for those.
+    pub fn val(&self, regs: &[T]) -> T {
         match self {
             Int(n) => *n,
-            Reg(c) => mem[reg(*c)],
+            Reg(r) => regs[r.index()],
+            Mem(_) => panic!("Operand::val: memory operand needs memory"),
+        }
+    }
+
+    /// Value of the operand, reading memory operands from `mem`.
+    ///
+    /// Panics if a memory address is out of bounds.
+    pub fn load(&self, regs: &[T], mem: &[T]) -> T
+    where
+        T: TryInto<usize>,
+    {
+        match self {
+            Mem(r) => {
+                let addr = regs[r.index()];
+                match addr.try_into() {
+                    Ok(i) if i < mem.len() => mem[i],
+                    _ => panic!("Operand::load: bad address"),
+                }
+            }
+            _ => self.val(regs),
         }
     }
 }
 
-impl FromStr for Operand {
-    type Err = &'static str;
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum ParseOperandError {
+    Empty,
+    /// Not a number or a known register name.
+    Unknown(String),
+}
+
+impl fmt::Display for ParseOperandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseOperandError::Empty => write!(f, "empty operand"),
+            ParseOperandError::Unknown(s) => write!(f, "bad operand {s:?}"),
+        }
+    }
+}
+
+impl std::error::Error for ParseOperandError {}
+
+impl<T: FromStr, R: RegisterName> FromStr for Operand<T, R> {
+    type Err = ParseOperandError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s.parse::<i64>() {
-            Ok(n) => Ok(Int(n)),
-            _ => Ok(Reg(s.chars().next().unwrap())),
+        if s.is_empty() {
+            return Err(ParseOperandError::Empty);
+        }
+        if let Ok(n) = s.parse() {
+            return Ok(Int(n));
+        }
+        let unknown = || ParseOperandError::Unknown(s.to_owned());
+        match s.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+            Some(name) if R::MEMORY => {
+                R::from_name(name).map(Mem).ok_or_else(unknown)
+            }
+            Some(_) => Err(unknown()),
+            None => R::from_name(s).map(Reg).ok_or_else(unknown),
+        }
+    }
+}
+
+impl<T: fmt::Display, R: fmt::Display> fmt::Display for Operand<T, R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Int(n) => write!(f, "{n}"),
+            Reg(r) => write!(f, "{r}"),
+            Mem(r) => write!(f, "[{r}]"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Copy, Clone, Eq, PartialEq, Debug)]
+    enum Named {
+        Acc,
+        Ip,
+    }
+
+    impl RegisterName for Named {
+        const MEMORY: bool = true;
+
+        fn index(self) -> usize {
+            self as usize
+        }
+
+        fn from_name(name: &str) -> Option<Self> {
+            match name {
+                "acc" => Some(Named::Acc),
+                "ip" => Some(Named::Ip),
+                _ => None,
+            }
         }
     }
+
+    impl fmt::Display for Named {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                Named::Acc => write!(f, "acc"),
+                Named::Ip => write!(f, "ip"),
+            }
+        }
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!("-12".parse::<Operand>(), Ok(Int(-12)));
+        assert_eq!("c".parse::<Operand>(), Ok(Reg('c')));
+        assert_eq!("".parse::<Operand>(), Err(ParseOperandError::Empty));
+        for bad in ["ab", "A", "x1", "[b]", "[1]", "[c", "1.5"] {
+            assert_eq!(
+                bad.parse::<Operand>(),
+                Err(ParseOperandError::Unknown(bad.into())),
+            );
+        }
+
+        assert_eq!("200".parse::<Operand<u8, char>>(), Ok(Int(200)));
+        assert!("300".parse::<Operand<u8, char>>().is_err());
+
+        let op: Operand<i32, Named> = "[ip]".parse().unwrap();
+        assert_eq!(op, Mem(Named::Ip));
+        assert_eq!(op.to_string(), "[ip]");
+        assert!("a".parse::<Operand<i32, Named>>().is_err());
+        assert!("[accip]".parse::<Operand<i32, Named>>().is_err());
+    }
+
+    #[test]
+    fn values() {
+        let regs = [3, 1];
+        let mem = [10, 20, 30, 40];
+        let op: Operand<i32, Named> = "acc".parse().unwrap();
+        assert_eq!(op.val(&regs), 3);
+        let op: Operand<i32, Named> = "[acc]".parse().unwrap();
+        assert_eq!(op.load(&regs, &mem), 40);
+        assert_eq!(Operand::<i32, Named>::Int(5).load(&regs, &mem), 5);
+        assert_eq!(Operand::Reg('b').val(&regs), 1);
+    }
+
+    #[test]
+    fn deserialize() {
+        let ops: Vec<Operand> = idm::from_str("1 b c").unwrap();
+        assert_eq!(ops, [Int(1), Reg('b'), Reg('c')]);
+        assert!(idm::from_str::<Vec<Operand>>("1 bb").is_err());
+        assert!(idm::from_str::<Vec<Operand>>("1 [b]").is_err());
+
+        let ops: Vec<Operand<i32, Named>> = idm::from_str("ip [acc]").unwrap();
+        assert_eq!(ops, [Reg(Named::Ip), Mem(Named::Acc)]);
+    }
 }
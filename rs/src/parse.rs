@@ -0,0 +1,552 @@
+//! Input parsing support.
+
+use std::{fmt, str::FromStr};
+
+use indexmap::IndexMap;
+#[doc(hidden)]
+pub use regex::Regex;
+use regex::{Captures, Match};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::prelude::Matrix;
+
+/// Error from parsing puzzle input.
+///
+/// Positions are relative to the string that was parsed. When that string
+/// is a piece of a larger input, use `offset_lines` to move the error to the
+/// right line.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct ParseError {
+    /// Line number starting from 1, 0 if unknown.
+    pub line: usize,
+    /// Column in characters starting from 1, 0 if unknown.
+    pub col: usize,
+    /// The text that failed to parse.
+    pub text: String,
+    /// Description of what was expected instead.
+    pub expected: String,
+}
+
+impl ParseError {
+    /// Error without a known position.
+    pub fn new(text: impl Into<String>, expected: impl Into<String>) -> Self {
+        ParseError {
+            line: 0,
+            col: 0,
+            text: text.into(),
+            expected: expected.into(),
+        }
+    }
+
+    /// Error for a value that didn't parse as type `T`.
+    pub fn of_type<T: ?Sized>(text: impl Into<String>) -> Self {
+        ParseError::new(text, std::any::type_name::<T>())
+    }
+
+    pub fn at(mut self, line: usize, col: usize) -> Self {
+        self.line = line;
+        self.col = col;
+        self
+    }
+
+    /// Set the position from a byte offset into the input.
+    pub fn at_offset(self, input: &str, offset: usize) -> Self {
+        let before = &input[..offset];
+        let line = before.matches('\n').count() + 1;
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        let col = before[line_start..].chars().count() + 1;
+        self.at(line, col)
+    }
+
+    /// Convert a position in `input[offset..]` into a position in `input`.
+    pub fn within(self, input: &str, offset: usize) -> Self {
+        let start = ParseError::new("", "").at_offset(input, offset);
+        match (self.line, self.col) {
+            (0, _) => self.at(start.line, start.col),
+            (1, 0) => self.at(start.line, 0),
+            (1, col) => self.at(start.line, start.col + col - 1),
+            (line, col) => self.at(start.line + line - 1, col),
+        }
+    }
+
+    /// Move the error down by `n` lines when the parsed text started at line
+    /// `n + 1` of a larger input. An error without a line is placed on the
+    /// first line of the text.
+    pub fn offset_lines(mut self, n: usize) -> Self {
+        self.line = self.line.max(1) + n;
+        self
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.line, self.col) {
+            (0, _) => {}
+            (line, 0) => write!(f, "line {line}: ")?,
+            (line, col) => write!(f, "line {line}, column {col}: ")?,
+        }
+        write!(f, "expected {}, found {:?}", self.expected, self.text)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Match a regex against the whole input.
+pub fn captures<'a>(
+    re: &Regex,
+    input: &'a str,
+) -> Result<Captures<'a>, ParseError> {
+    re.captures(input).ok_or_else(|| {
+        ParseError::new(input, format!("match for /{re}/")).at(1, 1)
+    })
+}
+
+/// Capture group named `name` if the regex has one and group number `n`
+/// otherwise.
+fn group<'a>(
+    re: &Regex,
+    caps: &Captures<'a>,
+    name: Option<&str>,
+    n: usize,
+) -> Option<Match<'a>> {
+    match name {
+        Some(name) if re.capture_names().any(|a| a == Some(name)) => {
+            caps.name(name)
+        }
+        _ => caps.get(n),
+    }
+}
+
+/// Parse a capture group of a match of `re` in `input`, selected like in
+/// `optional_capture`. Fails if the group didn't participate in the match.
+pub fn capture<T: FromStr>(
+    re: &Regex,
+    caps: &Captures,
+    name: Option<&str>,
+    n: usize,
+    input: &str,
+) -> Result<T, ParseError> {
+    optional_capture(re, caps, name, n, input)?.ok_or_else(|| {
+        let group = name.map_or_else(|| n.to_string(), |s| s.to_owned());
+        ParseError::new(input, format!("capture group {group} in /{re}/"))
+            .at(1, 1)
+    })
+}
+
+/// Parse a capture group of a match of `re` in `input`. The group is the
+/// one named `name` if the regex has one and group number `n` otherwise.
+pub fn optional_capture<T: FromStr>(
+    re: &Regex,
+    caps: &Captures,
+    name: Option<&str>,
+    n: usize,
+    input: &str,
+) -> Result<Option<T>, ParseError> {
+    let Some(m) = group(re, caps, name, n) else {
+        return Ok(None);
+    };
+    m.as_str().parse().map(Some).map_err(|_| {
+        ParseError::of_type::<T>(m.as_str()).at_offset(input, m.start())
+    })
+}
+
+/// Part of an input separated from the rest by blank lines.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Section<'a> {
+    /// Text of the first line without the colon if the line ends with one,
+    /// like `Player 1:`.
+    pub header: Option<&'a str>,
+    /// Lines after the header.
+    pub body: &'a str,
+    /// Line number of the first body line in the input, starting from 1.
+    pub line: usize,
+}
+
+impl Section<'_> {
+    /// Parse the section, with error positions in the whole input.
+    pub fn parse<T: FromSection>(&self) -> Result<T, ParseError> {
+        T::from_section(self).map_err(|e| e.offset_lines(self.line - 1))
+    }
+}
+
+/// Split input into sections at blank lines.
+pub fn split_sections(input: &str) -> Vec<Section<'_>> {
+    let mut ret = Vec::new();
+    // Start offset and line number of the current section.
+    let mut start: Option<(usize, usize)> = None;
+    let mut end = 0;
+    let mut offset = 0;
+
+    let mut close = |start: (usize, usize), end: usize| {
+        let (pos, line) = start;
+        let text = &input[pos..end];
+        let (first, rest) = text.split_once('\n').unwrap_or((text, ""));
+        match first.trim_end().strip_suffix(':') {
+            Some(header) => ret.push(Section {
+                header: Some(header),
+                body: rest,
+                line: line + 1,
+            }),
+            None => ret.push(Section {
+                header: None,
+                body: text,
+                line,
+            }),
+        }
+    };
+
+    for (i, line) in input.split_inclusive('\n').enumerate() {
+        if line.trim().is_empty() {
+            if let Some(s) = start.take() {
+                close(s, end);
+            }
+        } else {
+            start.get_or_insert((offset, i + 1));
+            end = offset + line.trim_end().len();
+        }
+        offset += line.len();
+    }
+    if let Some(s) = start {
+        close(s, end);
+    }
+    ret
+}
+
+/// Types that can be parsed from a single input section.
+pub trait FromSection: Sized {
+    /// Parse the section. Errors have line numbers relative to the body.
+    fn from_section(section: &Section) -> Result<Self, ParseError>;
+}
+
+/// Whole body as is.
+impl FromSection for String {
+    fn from_section(section: &Section) -> Result<Self, ParseError> {
+        Ok(section.body.to_owned())
+    }
+}
+
+/// One value for each line.
+impl<T: FromStr> FromSection for Vec<T> {
+    fn from_section(section: &Section) -> Result<Self, ParseError> {
+        section
+            .body
+            .lines()
+            .enumerate()
+            .map(|(i, line)| {
+                line.parse()
+                    .map_err(|_| ParseError::of_type::<T>(line).offset_lines(i))
+            })
+            .collect()
+    }
+}
+
+/// Wrapper for parsing sections of numbers with `Matrix`.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Rows<M>(pub M);
+
+impl<M: Matrix> FromSection for Rows<M> {
+    fn from_section(section: &Section) -> Result<Self, ParseError> {
+        M::try_parse(section.body).map(Rows)
+    }
+}
+
+/// Types that can be parsed from a list of input sections.
+pub trait FromSections: Sized {
+    fn from_sections(sections: &[Section]) -> Result<Self, ParseError>;
+}
+
+/// Any number of sections of the same type.
+impl<T: FromSection> FromSections for Vec<T> {
+    fn from_sections(sections: &[Section]) -> Result<Self, ParseError> {
+        sections.iter().map(|s| s.parse()).collect()
+    }
+}
+
+/// Sections keyed by their headers, every section must have one.
+impl<T: FromSection> FromSections for IndexMap<String, T> {
+    fn from_sections(sections: &[Section]) -> Result<Self, ParseError> {
+        sections
+            .iter()
+            .map(|s| match s.header {
+                Some(h) => Ok((h.to_owned(), s.parse()?)),
+                None => {
+                    let first = s.body.lines().next().unwrap_or("");
+                    Err(ParseError::new(first, "section header").at(s.line, 1))
+                }
+            })
+            .collect()
+    }
+}
+
+macro_rules! tuple_sections {
+    ($n:expr; $($t:ident),+; $($i:tt),+) => {
+        /// One section for each element.
+        impl<$($t: FromSection),+> FromSections for ($($t,)+) {
+            fn from_sections(
+                sections: &[Section],
+            ) -> Result<Self, ParseError> {
+                if sections.len() != $n {
+                    return Err(ParseError::new(
+                        format!("{} sections", sections.len()),
+                        format!("{} sections", $n),
+                    ));
+                }
+                Ok(($(sections[$i].parse()?,)+))
+            }
+        }
+    };
+}
+
+tuple_sections!(1; T1; 0);
+tuple_sections!(2; T1, T2; 0, 1);
+tuple_sections!(3; T1, T2, T3; 0, 1, 2);
+tuple_sections!(4; T1, T2, T3, T4; 0, 1, 2, 3);
+tuple_sections!(5; T1, T2, T3, T4, T5; 0, 1, 2, 3, 4);
+tuple_sections!(6; T1, T2, T3, T4, T5, T6; 0, 1, 2, 3, 4, 5);
+
+/// Split input into sections and parse them.
+pub fn parse_sections<T: FromSections>(input: &str) -> Result<T, ParseError> {
+    T::from_sections(&split_sections(input))
+}
+
+/// Parse an instruction list like `cpy 1 a` with one instruction per line
+/// using `idm`. Lines are trimmed and blank lines skipped.
+///
+/// Errors point at the token the `idm` error names, or at the whole line if
+/// it doesn't name one.
+pub fn parse_program<T: DeserializeOwned>(
+    input: &str,
+) -> Result<Vec<T>, ParseError> {
+    input
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            idm::from_str(line.trim())
+                .map_err(|e| program_error::<T>(line, e).offset_lines(i))
+        })
+        .collect()
+}
+
+fn program_error<T>(line: &str, e: idm::Error) -> ParseError {
+    let msg = e.to_string();
+    let quoted = |t: &str| {
+        ['"', '`', '\'']
+            .iter()
+            .any(|q| msg.contains(&format!("{q}{t}{q}")))
+    };
+    let (offset, text) = line
+        .split_whitespace()
+        .rev()
+        .find(|t| quoted(t))
+        .or_else(|| Some(line.trim()))
+        .map(|t| (t.as_ptr() as usize - line.as_ptr() as usize, t))
+        .unwrap();
+    let expected = format!("{} ({msg})", std::any::type_name::<T>());
+    ParseError::new(text, expected).at_offset(line, offset)
+}
+
+/// Write instructions back out in the format `parse_program` reads.
+///
+/// Panics if an instruction can't be serialized with `idm`.
+pub fn format_program<T: Serialize>(prog: &[T]) -> String {
+    let mut ret = String::new();
+    for op in prog {
+        let line = idm::to_string(op).expect("format_program: bad instruction");
+        ret.push_str(line.trim_end());
+        ret.push('\n');
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::{re_parser, RegexParse};
+
+    #[derive(Eq, PartialEq, Debug, RegexParse)]
+    #[re(r"^(\d+)-(\d+) (\w): (\w+)$")]
+    struct Policy {
+        lo: usize,
+        hi: usize,
+        c: char,
+        pw: String,
+    }
+
+    #[derive(Eq, PartialEq, Debug, RegexParse)]
+    #[re(r"^(?<name>\w+)(?: x(?<count>\d+))?(?: \((?<policy>.+)\))?$")]
+    struct Item {
+        count: Option<u32>,
+        name: String,
+        policy: Option<Policy>,
+    }
+
+    #[derive(Eq, PartialEq, Debug, RegexParse)]
+    enum Cmd {
+        #[re(r"^noop$")]
+        Noop,
+        #[re(r"^addx (-?\d+)$")]
+        Add(i32),
+        #[re(r"^move (?<n>\d+) to (?<to>\w)$")]
+        Move { to: char, n: u8 },
+    }
+
+    #[test]
+    fn position() {
+        let input = "abc\nde fg\nh";
+        let e = ParseError::new("fg", "number").at_offset(input, 7);
+        assert_eq!((e.line, e.col), (2, 4));
+        assert_eq!(
+            e.to_string(),
+            "line 2, column 4: expected number, found \"fg\""
+        );
+
+        let e = e.offset_lines(10);
+        assert_eq!((e.line, e.col), (12, 4));
+
+        let e = ParseError::of_type::<u8>("300");
+        assert_eq!(e.to_string(), "expected u8, found \"300\"");
+        assert_eq!(
+            e.offset_lines(2).to_string(),
+            "line 3: expected u8, found \"300\""
+        );
+
+        let e = ParseError::new("x", "y").at(1, 3).within("ab\ncdef", 4);
+        assert_eq!((e.line, e.col), (2, 4));
+    }
+
+    #[test]
+    fn sections() {
+        let input = "\
+Player 1:
+9
+2
+
+Player 2:
+5
+x
+
+
+seeds: 1 2
+";
+        let s = split_sections(input);
+        assert_eq!(s.len(), 3);
+        assert_eq!(
+            (s[0].header, s[0].body, s[0].line),
+            (Some("Player 1"), "9\n2", 2)
+        );
+        assert_eq!(
+            (s[1].header, s[1].body, s[1].line),
+            (Some("Player 2"), "5\nx", 6)
+        );
+        assert_eq!(
+            (s[2].header, s[2].body, s[2].line),
+            (None, "seeds: 1 2", 10)
+        );
+
+        let e =
+            parse_sections::<(Vec<u8>, Vec<u8>, String)>(input).unwrap_err();
+        assert_eq!((e.line, e.text.as_str()), (7, "x"));
+        let e = parse_sections::<(Vec<u8>, Vec<String>)>(input).unwrap_err();
+        assert_eq!(e.expected, "2 sections");
+
+        let (a, b, c): (Vec<u8>, Vec<String>, String) =
+            parse_sections(input).unwrap();
+        assert_eq!(
+            (a, b, c.as_str()),
+            (vec![9, 2], vec!["5".into(), "x".into()], "seeds: 1 2")
+        );
+
+        let decks: IndexMap<String, Vec<String>> =
+            parse_sections(input.split("\n\n\n").next().unwrap()).unwrap();
+        assert_eq!(decks["Player 2"], ["5", "x"]);
+        let e = parse_sections::<IndexMap<String, String>>(input).unwrap_err();
+        assert_eq!((e.line, e.expected.as_str()), (10, "section header"));
+
+        type Rules = Rows<Vec<[i32; 2]>>;
+        let (Rows(rules), Rows(updates)): (Rules, Rows<Vec<Vec<i32>>>) =
+            parse_sections("1|2\n3|4\n\n1,2,3\n4,5").unwrap();
+        assert_eq!(rules, [[1, 2], [3, 4]]);
+        assert_eq!(updates, [vec![1, 2, 3], vec![4, 5]]);
+        let e = parse_sections::<Vec<Rows<Vec<[i32; 2]>>>>("1|2\n\n3|4\n5")
+            .unwrap_err();
+        assert_eq!(e.line, 4);
+    }
+
+    #[test]
+    fn program() {
+        use serde::Deserialize;
+
+        use crate::Operand::{self, *};
+
+        #[derive(Copy, Clone, Eq, PartialEq, Debug, Deserialize, Serialize)]
+        #[serde(rename_all = "lowercase")]
+        enum Op {
+            Cpy(Operand, char),
+            Jnz(Operand, Operand),
+            Inc(char),
+        }
+
+        let input = "cpy 41 a\ninc a\n\njnz a -2\n";
+        let prog: Vec<Op> = parse_program(input).unwrap();
+        assert_eq!(
+            prog,
+            [
+                Op::Cpy(Int(41), 'a'),
+                Op::Inc('a'),
+                Op::Jnz(Reg('a'), Int(-2))
+            ]
+        );
+        assert_eq!(format_program(&prog), "cpy 41 a\ninc a\njnz a -2\n");
+        assert_eq!(parse_program::<Op>(&format_program(&prog)), Ok(prog));
+
+        let e = parse_program::<Op>("inc a\ncpy 1 a\n  jnz x1 2").unwrap_err();
+        assert_eq!((e.line, e.col, e.text.as_str()), (3, 7, "x1"));
+        let e = parse_program::<Op>("inc a\ndec a").unwrap_err();
+        assert_eq!((e.line, e.col, e.text.as_str()), (2, 1, "dec"));
+        let e = parse_program::<Op>("cpy 1").unwrap_err();
+        assert_eq!((e.line, e.col, e.text.as_str()), (1, 1, "cpy 1"));
+    }
+
+    #[test]
+    fn derive() {
+        let policy = Policy {
+            lo: 1,
+            hi: 3,
+            c: 'a',
+            pw: "abcde".into(),
+        };
+        assert_eq!("1-3 a: abcde".parse(), Ok(policy));
+        let e = "1-3 ab: abcde".parse::<Policy>().unwrap_err();
+        assert_eq!(e.expected, r"match for /^(\d+)-(\d+) (\w): (\w+)$/");
+
+        assert_eq!(
+            "gem".parse(),
+            Ok(Item {
+                count: None,
+                name: "gem".into(),
+                policy: None
+            })
+        );
+        let item: Item = "gem x12 (2-9 c: ccc)".parse().unwrap();
+        assert_eq!(item.count, Some(12));
+        assert_eq!(item.policy.unwrap().hi, 9);
+        let e = "gem x12 (2-9 c)".parse::<Item>().unwrap_err();
+        assert_eq!((e.col, e.text.as_str()), (10, "2-9 c"));
+
+        assert_eq!("noop".parse(), Ok(Cmd::Noop));
+        assert_eq!("addx -5".parse(), Ok(Cmd::Add(-5)));
+        assert_eq!("move 3 to x".parse(), Ok(Cmd::Move { to: 'x', n: 3 }));
+        let e = "move 300 to x".parse::<Cmd>().unwrap_err();
+        assert_eq!((e.col, e.expected.as_str()), (6, "u8"));
+        assert!("jump".parse::<Cmd>().is_err());
+
+        // Derived types work with any regex through `RegexParseable`.
+        let parse = re_parser::<Policy>(r"^(\d+),(\d+),(.),(.+)$");
+        assert_eq!(parse("2,4,z,zz").unwrap().pw, "zz");
+        let parse = re_parser::<Cmd>(r"^\d+: (.+)$");
+        assert_eq!(parse("1: addx 3"), Ok(Cmd::Add(3)));
+        let e = parse("1: move 300 to x").unwrap_err();
+        assert_eq!((e.col, e.text.as_str()), (9, "300"));
+    }
+}
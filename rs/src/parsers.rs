@@ -0,0 +1,237 @@
+//! Ready-made nom parsers for recurring puzzle input syntax.
+//!
+//! Text parsers work on `&str` with nom's default error type, run them with
+//! [`parse_all`] to get a [`ParseError`] with the line and column of the
+//! failure.
+
+use std::{ops::RangeInclusive, str::FromStr};
+
+use nom::{
+    bits::complete::{tag as bit_tag, take as take_bits},
+    branch::alt,
+    bytes::complete::{tag, take_while1},
+    character::complete::{char, digit1, line_ending, multispace0, space0},
+    combinator::{all_consuming, map, map_res, opt, recognize},
+    error::{Error, ErrorKind},
+    multi::{many_till, separated_list0, separated_list1},
+    sequence::{delimited, pair, preceded, separated_pair, terminated},
+    Finish, IResult, Parser,
+};
+
+use crate::parse::ParseError;
+
+/// Run a parser that must consume the whole input, save for trailing
+/// whitespace.
+pub fn parse_all<'a, O>(
+    input: &'a str,
+    parser: impl Parser<&'a str, O, Error<&'a str>>,
+) -> Result<O, ParseError> {
+    match all_consuming(terminated(parser, multispace0))(input).finish() {
+        Ok((_, ret)) => Ok(ret),
+        Err(e) => {
+            let offset = input.len() - e.input.len();
+            let found = e.input.lines().next().unwrap_or("");
+            let expected = match e.code {
+                ErrorKind::Eof => "end of input".to_owned(),
+                ErrorKind::Digit => "digits".to_owned(),
+                code => code.description().to_lowercase(),
+            };
+            Err(ParseError::new(found, expected).at_offset(input, offset))
+        }
+    }
+}
+
+/// Parser that applies `parser` on every line.
+pub fn lines<'a, O>(
+    parser: impl Parser<&'a str, O, Error<&'a str>>,
+) -> impl FnMut(&'a str) -> IResult<&'a str, Vec<O>> {
+    separated_list0(line_ending, parser)
+}
+
+/// Integer with an optional minus sign.
+pub fn int<T: FromStr>(input: &str) -> IResult<&str, T> {
+    map_res(recognize(pair(opt(char('-')), digit1)), str::parse)(input)
+}
+
+/// Name made of letters, digits and underscores.
+pub fn ident(input: &str) -> IResult<&str, &str> {
+    take_while1(|c: char| c.is_alphanumeric() || c == '_')(input)
+}
+
+/// Comma separated list, optionally with spaces after the commas.
+pub fn comma_list<'a, O>(
+    elt: impl Parser<&'a str, O, Error<&'a str>>,
+) -> impl FnMut(&'a str) -> IResult<&'a str, Vec<O>> {
+    separated_list1(pair(char(','), space0), elt)
+}
+
+/// `key: value` pair.
+pub fn key_value<'a, V>(
+    value: impl Parser<&'a str, V, Error<&'a str>>,
+) -> impl FnMut(&'a str) -> IResult<&'a str, (&'a str, V)> {
+    separated_pair(ident, pair(char(':'), space0), value)
+}
+
+/// Comma separated `key: value` pairs, like `cats: 7, trees: 3`.
+pub fn key_values<'a, V>(
+    value: impl Parser<&'a str, V, Error<&'a str>>,
+) -> impl FnMut(&'a str) -> IResult<&'a str, Vec<(&'a str, V)>> {
+    comma_list(key_value(value))
+}
+
+/// Adjacency line like `a -> b, c, d`.
+pub fn adjacency<'a, N>(
+    node: impl Parser<&'a str, N, Error<&'a str>> + Clone,
+) -> impl FnMut(&'a str) -> IResult<&'a str, (N, Vec<N>)> {
+    separated_pair(
+        node.clone(),
+        delimited(space0, tag("->"), space0),
+        comma_list(node),
+    )
+}
+
+/// Inclusive range like `-3..7`.
+pub fn range<T: FromStr>(input: &str) -> IResult<&str, RangeInclusive<T>> {
+    map(separated_pair(int, tag(".."), int), |(a, b)| a..=b)(input)
+}
+
+/// Comma separated `name=value` assignments, like `x=1, y=-2` or
+/// `x=1..3,y=2..4`.
+pub fn assignments<'a, V>(
+    value: impl Parser<&'a str, V, Error<&'a str>>,
+) -> impl FnMut(&'a str) -> IResult<&'a str, Vec<(&'a str, V)>> {
+    comma_list(separated_pair(ident, char('='), value))
+}
+
+/// Fixed number of `name=value` assignments as an array of the values.
+pub fn coords<'a, T, const N: usize>(
+    value: impl Parser<&'a str, T, Error<&'a str>>,
+) -> impl FnMut(&'a str) -> IResult<&'a str, [T; N]> {
+    map_res(assignments(value), |elts| {
+        let values: Vec<T> = elts.into_iter().map(|(_, v)| v).collect();
+        values.try_into().map_err(|_| ErrorKind::Count)
+    })
+}
+
+/// Bracketed nested lists like `[1,[2,3],[]]`.
+///
+/// Atoms are parsed with `atom` and wrapped with `leaf`, lists are built
+/// with `list`.
+pub fn nested<'a, T, N>(
+    atom: fn(&'a str) -> IResult<&'a str, T>,
+    leaf: fn(T) -> N,
+    list: fn(Vec<N>) -> N,
+) -> impl Fn(&'a str) -> IResult<&'a str, N> {
+    move |input| {
+        alt((
+            map(
+                delimited(
+                    char('['),
+                    separated_list0(
+                        pair(char(','), space0),
+                        nested(atom, leaf, list),
+                    ),
+                    char(']'),
+                ),
+                list,
+            ),
+            map(atom, leaf),
+        ))(input)
+    }
+}
+
+/// Input for bit-level parsers, a byte slice and a bit offset into its
+/// first byte.
+pub type Bits<'a> = (&'a [u8], usize);
+
+/// Number of bits left in the input.
+pub fn bits_left((bytes, offset): Bits) -> usize {
+    bytes.len() * 8 - offset
+}
+
+/// Unsigned field of `n` bits, most significant bit first.
+pub fn field<'a>(n: usize) -> impl FnMut(Bits<'a>) -> IResult<Bits<'a>, u64> {
+    take_bits(n)
+}
+
+/// Field that must have the given value.
+pub fn marker<'a>(
+    value: u64,
+    n: usize,
+) -> impl FnMut(Bits<'a>) -> IResult<Bits<'a>, u64> {
+    bit_tag(value, n)
+}
+
+/// Number stored in chunks of `n` bits, each prefixed with a 1 bit except
+/// the last one which is prefixed with a 0 bit.
+pub fn chunked<'a>(n: usize) -> impl FnMut(Bits<'a>) -> IResult<Bits<'a>, u64> {
+    map(
+        many_till(
+            preceded(marker(1, 1), field(n)),
+            preceded(marker(0, 1), field(n)),
+        ),
+        move |(init, last)| {
+            init.iter()
+                .chain(std::iter::once(&last))
+                .fold(0, |a, &x| (a << n) | x)
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use nom::sequence::tuple;
+
+    use super::*;
+
+    #[test]
+    fn text() {
+        assert_eq!(parse_all("-12", int::<i32>), Ok(-12));
+        assert_eq!(
+            parse_all("cats: 7, trees: 3\n", key_values(int::<u32>)),
+            Ok(vec![("cats", 7), ("trees", 3)])
+        );
+        assert_eq!(
+            parse_all("a -> b, c\nb -> a", lines(adjacency(ident))),
+            Ok(vec![("a", vec!["b", "c"]), ("b", vec!["a"])])
+        );
+        assert_eq!(
+            parse_all("x=1..3,y=-2..4", coords::<_, 2>(range::<i32>)),
+            Ok([1..=3, -2..=4])
+        );
+        assert_eq!(parse_all("x=1, y=2", coords::<i64, 2>(int)), Ok([1, 2]));
+        assert!(parse_all("x=1, y=2", coords::<i64, 3>(int)).is_err());
+
+        #[derive(PartialEq, Debug)]
+        enum Item {
+            Atom(u32),
+            List(Vec<Item>),
+        }
+        use Item::*;
+        assert_eq!(
+            parse_all("[1,[2, []]]", nested(int, Atom, List)),
+            Ok(List(vec![Atom(1), List(vec![Atom(2), List(vec![])])]))
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let e =
+            parse_all("a -> b\nc => d", lines(adjacency(ident))).unwrap_err();
+        assert_eq!((e.line, e.col, e.text.as_str()), (2, 1, "c => d"));
+        let e = parse_all("1\n2\n3x", lines(int::<i32>)).unwrap_err();
+        assert_eq!((e.line, e.col), (3, 2));
+        assert_eq!(e.expected, "end of input");
+    }
+
+    #[test]
+    fn bits() {
+        // Literal packet from 2021 day 16, version 6 and value 2021.
+        let data = [0xD2, 0xFE, 0x28];
+        let (rest, (version, kind, value)) =
+            tuple((field(3), field(3), chunked(4)))((&data[..], 0)).unwrap();
+        assert_eq!((version, kind, value), (6, 4, 2021));
+        assert_eq!(bits_left(rest), 3);
+        assert!(marker(5, 3)((&data[..], 0)).is_err());
+    }
+}
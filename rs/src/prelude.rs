@@ -4,10 +4,8 @@ use std::{
     convert::TryInto,
     fmt::{Debug, Write},
     hash::{Hash, Hasher},
-    io::{BufRead, stdin},
     ops::{Add, Sub},
     rc::Rc,
-    sync::LazyLock,
 };
 
 use derive_more::Deref;
@@ -16,6 +14,12 @@ use nalgebra::{DMatrix, DVector};
 use num_traits::{One, Zero};
 use regex::Regex;
 
+use crate::{
+    lattice::Shell,
+    parse::{capture, captures},
+    scan::Scanner,
+};
+
 pub use glam::{
     i64vec2, i64vec3, ivec2, ivec3, I64Vec2, I64Vec3, IVec2, IVec3,
     Vec3Swizzles,
@@ -30,8 +34,21 @@ pub use std::str::FromStr;
 pub use rustc_hash::{FxHashMap as HashMap, FxHashSet as HashSet};
 
 pub use crate::axis_box::{area, volume, Cube, Rect};
+pub use crate::box_set::BoxSet;
+pub use crate::l1_ball::{line_cover, L1Ball};
+pub use crate::lattice::Metric;
 pub use crate::md5::md5sum;
+pub use crate::nested::Nested;
+pub use crate::numbers::Numbers;
+pub use crate::numeral::{MixedRadix, Numeral};
 pub use crate::ocr::{ocr, points, PointCloud};
+pub use crate::parse::{
+    format_program, parse_program, parse_sections, split_sections, FromSection,
+    FromSections, ParseError, Rows, Section,
+};
+pub use crate::rotation::Rotation3;
+pub use crate::spatial_index::SpatialIndex;
+pub use aoc_derive::RegexParse;
 
 pub const RIGHT: usize = 0;
 pub const DOWN: usize = 1;
@@ -143,33 +160,86 @@ where
     stdin_string().parse().unwrap()
 }
 
+/// Split stdin into blank line separated sections and parse them.
+///
+/// ```no_run
+/// # use aoc::prelude::*;
+/// let (rules, updates): (Vec<String>, Rows<Vec<Vec<i32>>>) =
+///     sections().unwrap();
+/// ```
+pub fn sections<T: FromSections>() -> Result<T, ParseError> {
+    parse_sections(&stdin_string())
+}
+
+/// Parse an instruction list from stdin with `parse_program`.
+///
+/// Panics with the line and token if an instruction fails to parse.
+pub fn stdin_program<T: serde::de::DeserializeOwned>() -> Vec<T> {
+    parse_program(&stdin_string()).unwrap_or_else(|e| panic!("{e}"))
+}
+
+/// Read a parameter set by the example runner, or use the default for real
+/// input.
+///
+/// Example files can start with `@name=value` header lines to set puzzle
+/// parameters that differ between the example and the real input, like grid
+/// sizes. The runner passes them in the `AOC_PARAMS` environment variable.
+///
+/// Panics if the value fails to parse.
+pub fn param<T: FromStr>(name: &str, default: T) -> T {
+    let params = std::env::var("AOC_PARAMS").unwrap_or_default();
+    lookup_param(&params, name)
+        .map(|a| a.unwrap_or_else(|e| panic!("{e}")))
+        .unwrap_or(default)
+}
+
+fn lookup_param<T: FromStr>(
+    params: &str,
+    name: &str,
+) -> Option<Result<T, ParseError>> {
+    params.split_whitespace().find_map(|p| {
+        let (key, value) = p.split_once('=')?;
+        (key == name).then(|| {
+            value.parse().map_err(|_| {
+                let ty = std::any::type_name::<T>();
+                ParseError::new(value, format!("{ty} for parameter {name}"))
+            })
+        })
+    })
+}
+
+/// Lines of stdin without the line endings.
 pub fn stdin_lines() -> impl Iterator<Item = String> + 'static {
-    std::iter::from_fn(|| stdin().lock().lines().next().map(|a| a.unwrap()))
+    let mut scanner = Scanner::stdin();
+    std::iter::from_fn(move || scanner.line().map(String::from))
 }
 
+/// Chars of stdin, including line endings.
 pub fn stdin_chars() -> impl Iterator<Item = char> + 'static {
-    let s = stdin_string();
-    let mut p = 0;
-    std::iter::from_fn(move || match &s[p..].chars().next() {
-        Some(c) => {
-            p += c.len_utf8();
-            Some(*c)
-        }
-        None => None,
-    })
+    let mut scanner = Scanner::stdin();
+    std::iter::from_fn(move || scanner.char())
 }
 
+/// Parse stdin lines into values.
+///
+/// Panics with the line number if a line fails to parse.
 pub fn stdin_lines_as<T>() -> impl Iterator<Item = T> + 'static
 where
-    T: FromStr + Debug,
-    <T as FromStr>::Err: Debug,
+    T: FromStr,
 {
-    std::iter::from_fn(|| {
-        stdin()
-            .lock()
-            .lines()
-            .next()
-            .map(|a| a.unwrap().parse().unwrap())
+    try_stdin_lines_as().map(|a| a.unwrap_or_else(|e| panic!("{e}")))
+}
+
+/// Parse stdin lines into values, yielding an error for each line that
+/// fails to parse.
+pub fn try_stdin_lines_as<T>(
+) -> impl Iterator<Item = Result<T, ParseError>> + 'static
+where
+    T: FromStr,
+{
+    stdin_lines().enumerate().map(|(i, line)| {
+        line.parse()
+            .map_err(|_| ParseError::of_type::<T>(line).offset_lines(i))
     })
 }
 
@@ -198,52 +268,66 @@ pub fn stdin_grid_iter(
     })
 }
 
-static SIGNED_INTEGER: LazyLock<Regex> =
-    LazyLock::new(|| Regex::new(r"(-?\d+)(.|$)").unwrap());
-
 /// Extract numbers from a string.
+///
+/// Panics if a number doesn't fit in the type.
 pub fn numbers<T: FromStr>(line: impl AsRef<str>) -> Vec<T> {
-    // The regex captures one character after the number as well so we can
-    // parse "1-2" as [1, 2] instead of [1, -2]. This means we need to use
-    // captures_iter for the input.
-    SIGNED_INTEGER
-        .captures_iter(line.as_ref())
-        .map(|caps| {
-            caps.get(1)
-                .unwrap()
-                .as_str()
-                .parse()
-                .unwrap_or_else(|_| panic!("Type didn't parse from integer"))
-        })
-        .collect()
+    try_numbers(line).unwrap_or_else(|e| panic!("{e}"))
+}
+
+/// Extract numbers from a string, fail if a number doesn't fit in the type.
+pub fn try_numbers<T: FromStr>(
+    line: impl AsRef<str>,
+) -> Result<Vec<T>, ParseError> {
+    let mut scanner = Scanner::new(line.as_ref().as_bytes());
+    std::iter::from_fn(|| scanner.try_int()).collect()
 }
 
 /// Extract a fixed amount of numbers from a string into an array.
+///
+/// Panics if the string has a different amount of numbers.
 pub fn fixed_numbers<T, const N: usize>(line: impl AsRef<str>) -> [T; N]
 where
     T: Copy + FromStr,
 {
-    let elts: Vec<T> =
-        SIGNED_INTEGER
-            .captures_iter(line.as_ref())
-            .map(|caps| {
-                caps.get(1).unwrap().as_str().parse().unwrap_or_else(|_| {
-                    panic!("Type didn't parse from integer")
-                })
-            })
-            .collect();
+    try_fixed_numbers(line).unwrap_or_else(|e| panic!("{e}"))
+}
 
-    elts.as_slice().try_into().unwrap()
+/// Extract a fixed amount of numbers from a string into an array, fail if
+/// the string has a different amount of numbers.
+pub fn try_fixed_numbers<T, const N: usize>(
+    line: impl AsRef<str>,
+) -> Result<[T; N], ParseError>
+where
+    T: Copy + FromStr,
+{
+    let line = line.as_ref();
+    let elts: Vec<T> = try_numbers(line)?;
+    let n = elts.len();
+    elts.try_into().map_err(|_| {
+        ParseError::new(line, format!("{N} numbers, got {n}")).at(1, 1)
+    })
 }
 
+/// Collect an iterator into an array.
+///
+/// Panics if the iterator has the wrong number of items.
 pub fn to_array<T, const N: usize>(
     input: impl IntoIterator<Item = T>,
 ) -> [T; N] {
-    input
-        .into_iter()
-        .collect::<Vec<T>>()
-        .try_into()
-        .unwrap_or_else(|_| panic!("fail"))
+    try_to_array(input).unwrap_or_else(|e| panic!("{e}"))
+}
+
+/// Collect an iterator into an array, fail if the iterator has the wrong
+/// number of items.
+pub fn try_to_array<T, const N: usize>(
+    input: impl IntoIterator<Item = T>,
+) -> Result<[T; N], ParseError> {
+    let elts = input.into_iter().collect::<Vec<T>>();
+    let n = elts.len();
+    elts.try_into().map_err(|_| {
+        ParseError::new(format!("{n} items"), format!("{N} items"))
+    })
 }
 
 pub fn to_ivec2s(
@@ -275,46 +359,59 @@ pub fn suffixes(s: &str) -> impl Iterator<Item = &str> {
 }
 
 pub trait Row: Sized {
-    fn parse(s: impl AsRef<str>) -> Self;
+    fn try_parse(s: impl AsRef<str>) -> Result<Self, ParseError>;
+
+    fn parse(s: impl AsRef<str>) -> Self {
+        Self::try_parse(s).unwrap_or_else(|e| panic!("{e}"))
+    }
 }
 
 impl<T: FromStr> Row for Vec<T> {
-    fn parse(s: impl AsRef<str>) -> Self {
-        numbers(s)
+    fn try_parse(s: impl AsRef<str>) -> Result<Self, ParseError> {
+        try_numbers(s)
     }
 }
 
 impl<T: Copy + FromStr, const N: usize> Row for [T; N] {
-    fn parse(s: impl AsRef<str>) -> Self {
-        fixed_numbers(s)
+    fn try_parse(s: impl AsRef<str>) -> Result<Self, ParseError> {
+        try_fixed_numbers(s)
     }
 }
 
 pub trait Matrix: Sized {
-    fn parse(s: impl AsRef<str>) -> Self;
+    fn try_parse(s: impl AsRef<str>) -> Result<Self, ParseError>;
+
+    fn parse(s: impl AsRef<str>) -> Self {
+        Self::try_parse(s).unwrap_or_else(|e| panic!("{e}"))
+    }
+}
+
+/// Parse the rows of a trimmed multi-line string, keeping line numbers
+/// relative to the untrimmed string.
+fn parse_rows<T: Row>(s: &str) -> Result<Vec<T>, ParseError> {
+    let skipped = s[..s.len() - s.trim_start().len()].matches('\n').count();
+    s.trim()
+        .split('\n')
+        .enumerate()
+        .map(|(i, line)| {
+            T::try_parse(line).map_err(|e| e.offset_lines(skipped + i))
+        })
+        .collect()
 }
 
 impl<T: Row> Matrix for Vec<T> {
-    fn parse(s: impl AsRef<str>) -> Self {
-        s.as_ref()
-            .trim()
-            .split('\n')
-            .map(|line| Row::parse(line))
-            .collect()
+    fn try_parse(s: impl AsRef<str>) -> Result<Self, ParseError> {
+        parse_rows(s.as_ref())
     }
 }
 
-impl<T: Row + Clone, const N: usize> Matrix for [T; N] {
-    fn parse(s: impl AsRef<str>) -> Self {
-        let mut ret: [T; N] = unsafe { std::mem::zeroed() };
-        let elts: Vec<T> = s
-            .as_ref()
-            .trim()
-            .split('\n')
-            .map(|line| Row::parse(line))
-            .collect();
-        ret.clone_from_slice(elts.as_slice());
-        ret
+impl<T: Row, const N: usize> Matrix for [T; N] {
+    fn try_parse(s: impl AsRef<str>) -> Result<Self, ParseError> {
+        let rows = parse_rows(s.as_ref())?;
+        let n = rows.len();
+        try_to_array(rows).map_err(|_| {
+            ParseError::new(format!("{n} rows"), format!("{N} rows"))
+        })
     }
 }
 
@@ -713,13 +810,13 @@ macro_rules! tuple_parseable {
         where
             $($t: std::str::FromStr),+
         {
-            type Error = ();
+            type Error = ParseError;
 
             fn parse(re: &Regex, input: &str) -> Result<Self, Self::Error> {
-                let caps = re.captures(input).ok_or(())?;
+                let caps = captures(re, input)?;
 
                 Ok((
-                    $(caps.get($n).ok_or(())?.as_str().parse().map_err(|_| ())?,)+
+                    $(capture::<$t>(re, &caps, None, $n, input)?,)+
                 ))
             }
         }
@@ -732,11 +829,11 @@ macro_rules! tuple_parseable {
 macro_rules! primitive_parseable {
     ($($t:ty),+) => {
         $(impl RegexParseable for $t {
-            type Error = ();
+            type Error = ParseError;
 
             fn parse(re: &Regex, input: &str) -> Result<Self, Self::Error> {
-                let caps = re.captures(input).ok_or(())?;
-                caps.get(1).ok_or(())?.as_str().parse().map_err(|_| ())
+                let caps = captures(re, input)?;
+                capture(re, &caps, None, 1, input)
             }
         })+
     }
@@ -786,15 +883,26 @@ pub fn re_parser<T: RegexParseable>(
 }
 
 /// Parse all stdin lines using the parsing regex.
+///
+/// Panics with the line number if a line fails to parse.
 pub fn parsed_stdin_lines<T>(re: &str) -> impl Iterator<Item = T>
 where
-    T: RegexParseable + 'static,
+    T: RegexParseable<Error = ParseError> + 'static,
+{
+    try_parsed_stdin_lines(re).map(|a| a.unwrap_or_else(|e| panic!("{e}")))
+}
+
+/// Parse all stdin lines using the parsing regex, yielding an error for
+/// each line that fails to parse.
+pub fn try_parsed_stdin_lines<T>(
+    re: &str,
+) -> impl Iterator<Item = Result<T, ParseError>>
+where
+    T: RegexParseable<Error = ParseError> + 'static,
 {
     let parser = re_parser(re);
-    stdin_lines().map(move |line| {
-        parser(&line)
-            .map_err(|_| ())
-            .expect("Failed to parse input line")
+    stdin_lines().enumerate().map(move |(i, line)| {
+        parser(&line).map_err(|e: ParseError| e.offset_lines(i))
     })
 }
 
@@ -1069,76 +1177,35 @@ impl Interner {
 }
 
 /// Iterate through points in 3D space out of origin.
-#[derive(Copy, Clone, Default, Debug)]
-pub struct SpacePoints(I64Vec3);
+///
+/// Points come in order of increasing taxicab distance, one
+/// [`Metric::shell`] at a time.
+#[derive(Clone, Debug)]
+pub struct SpacePoints {
+    r: i64,
+    shell: Shell<3>,
+}
+
+impl Default for SpacePoints {
+    fn default() -> Self {
+        SpacePoints {
+            r: 0,
+            shell: Metric::Taxicab.shell([0, 0, 0], 0),
+        }
+    }
+}
 
 impl Iterator for SpacePoints {
     type Item = I64Vec3;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let ret = self.0;
-
-        let (x, y, z) = (self.0.x, self.0.y, self.0.z);
-
-        // Cycle through sign permutations.
-        if z < 0 {
-            *self = SpacePoints(i64vec3(x, y, -z));
-            return Some(ret);
-        } else if y < 0 {
-            *self = SpacePoints(i64vec3(x, -y, -z));
-            return Some(ret);
-        } else if x < 0 {
-            *self = SpacePoints(i64vec3(-x, -y, -z));
-            return Some(ret);
-        }
-
-        debug_assert!(x >= 0 && y >= 0 && z >= 0);
-
-        // Further mutations set all values to negative so sign permutations
-        // will be hit next.
-        let set = |a: &mut Self, x: i64, y: i64, z: i64| {
-            debug_assert!(x >= 0 && y >= 0 && z >= 0);
-            *a = SpacePoints(i64vec3(-x, -y, -z))
-        };
-
-        // Generate next element.
-        if x >= y && y >= z {
-            // At the end of permutating the values. Go back to ascending
-            // order.
-            let (x, y, z) = (z, y, x);
-
-            if y > x + 1 {
-                // Can pull values from y to x while keeping order.
-                set(self, x + 1, y - 1, z);
-            } else if 3 * (z - 1) >= x + y + z {
-                // Bring z down, fill y and spill over to x if needed.
-                let n = x + y + z;
-                let z2 = z - 1;
-                let y2 = z2.min(n - z2);
-                let x2 = n - z2 - y2;
-                set(self, x2, y2, z2);
-            } else {
-                // Can't shuffle further, increment the whole.
-                set(self, 0, 0, x + y + z + 1);
-            }
-        } else {
-            let min = x.min(y).min(z);
-
-            // Permute by walking smallest right.
-            // 123 213 231 132 312 321 (end)
-            // 122 212 221 (end)
-            if x == min && y != min {
-                set(self, y, x, z)
-            } else if y == min && z != min {
-                set(self, x, z, y)
-            } else if z == min && x != min {
-                set(self, z, y, x)
-            } else {
-                unreachable!()
+        loop {
+            if let Some(p) = self.shell.next() {
+                return Some(p.into());
             }
+            self.r += 1;
+            self.shell = Metric::Taxicab.shell([0, 0, 0], self.r);
         }
-
-        Some(ret)
     }
 }
 
@@ -1184,6 +1251,48 @@ mod test {
         assert_eq!(a, s);
     }
 
+    #[test]
+    fn parse_errors() {
+        let e = try_numbers::<u8>("x=12, y=300").unwrap_err();
+        assert_eq!(e, ParseError::new("300", "u8").at(1, 9));
+
+        let e = try_fixed_numbers::<i32, 3>("1 2").unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "line 1, column 1: expected 3 numbers, got 2, found \"1 2\""
+        );
+
+        assert_eq!(try_to_array::<_, 2>([1, 2]), Ok([1, 2]));
+        assert!(try_to_array::<_, 2>([1, 2, 3]).is_err());
+
+        let e =
+            <Vec<Vec<u8>> as Matrix>::try_parse("\n1 2\n3 -4\n").unwrap_err();
+        assert_eq!((e.line, e.col, e.text.as_str()), (3, 3, "-4"));
+        let e = <[[u8; 2]; 3] as Matrix>::try_parse("1 2\n3 4").unwrap_err();
+        assert_eq!(e.expected, "3 rows");
+
+        let parser = re_parser::<(String, u32)>(r"^(\w+) = (.+)$");
+        assert_eq!(parser("ab = 12"), Ok(("ab".into(), 12)));
+        let e = parser("ab = 1x").unwrap_err();
+        assert_eq!((e.line, e.col, e.text.as_str()), (1, 6, "1x"));
+        assert_eq!(e.expected, "u32");
+        assert_eq!(
+            parser("ab 12").unwrap_err().expected,
+            r"match for /^(\w+) = (.+)$/"
+        );
+    }
+
+    #[test]
+    fn params() {
+        let params = "size=7\nbytes=12 part=2";
+        assert_eq!(lookup_param::<i32>(params, "size"), Some(Ok(7)));
+        assert_eq!(lookup_param::<u8>(params, "part"), Some(Ok(2)));
+        assert_eq!(lookup_param::<i32>(params, "row"), None);
+        assert_eq!(lookup_param::<i32>("", "size"), None);
+        let e = lookup_param::<u8>("size=-1", "size").unwrap();
+        assert_eq!(e.unwrap_err().expected, "u8 for parameter size");
+    }
+
     #[test]
     fn permutations() {
         let mut perm: Vec<u32> = (0..10).collect();
@@ -0,0 +1,368 @@
+//! Networks of nodes that pass messages to each other.
+
+use std::{collections::VecDeque, hash::Hash};
+
+use crate::prelude::*;
+
+/// How a node reacts to incoming messages.
+pub trait Behavior<S> {
+    /// Called once for every connection into the node when it's made.
+    fn connect(&mut self, _from: usize) {}
+
+    /// Handle a message from node `from`, return the message to send to all
+    /// outputs if any.
+    fn receive(&mut self, from: usize, signal: S) -> Option<S>;
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum Pulse {
+    Low,
+    High,
+}
+
+use Pulse::*;
+
+/// Standard pulse modules.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub enum Module {
+    /// Send every pulse on as is.
+    Broadcast,
+    /// Ignore high pulses, toggle on low pulses and send high when turned on
+    /// and low when turned off.
+    FlipFlop(bool),
+    /// Remember the last pulse from each input, send low if they're all high
+    /// and high otherwise.
+    Conjunction(Vec<(usize, Pulse)>),
+}
+
+impl Behavior<Pulse> for Module {
+    fn connect(&mut self, from: usize) {
+        if let Module::Conjunction(mem) = self {
+            mem.push((from, Low));
+        }
+    }
+
+    fn receive(&mut self, from: usize, signal: Pulse) -> Option<Pulse> {
+        match self {
+            Module::Broadcast => Some(signal),
+            Module::FlipFlop(_) if signal == High => None,
+            Module::FlipFlop(on) => {
+                *on = !*on;
+                Some(if *on { High } else { Low })
+            }
+            Module::Conjunction(mem) => {
+                for (i, s) in mem.iter_mut() {
+                    if *i == from {
+                        *s = signal;
+                    }
+                }
+                if mem.iter().all(|(_, s)| *s == High) {
+                    Some(Low)
+                } else {
+                    Some(High)
+                }
+            }
+        }
+    }
+}
+
+/// Discrete event simulator for a network of named nodes.
+///
+/// The simulation runs in rounds that start with one node sending a message.
+/// Messages are delivered in the order they were sent and the round ends when
+/// there are no messages left.
+#[derive(Clone, Debug)]
+pub struct Simulator<B, S> {
+    nodes: IndexMap<String, B>,
+    inputs: Vec<Vec<usize>>,
+    outputs: Vec<Vec<usize>>,
+    /// Messages in transit as (from, to, signal).
+    queue: VecDeque<(usize, usize, S)>,
+    round: u64,
+    /// Number of messages each node has sent, one for every receiver.
+    sent: HashMap<(usize, S), u64>,
+    /// First and second round each node has sent a signal in.
+    first: HashMap<(usize, S), u64>,
+    second: HashMap<(usize, S), u64>,
+}
+
+impl<B, S> Default for Simulator<B, S> {
+    fn default() -> Self {
+        Simulator {
+            nodes: Default::default(),
+            inputs: Default::default(),
+            outputs: Default::default(),
+            queue: Default::default(),
+            round: 0,
+            sent: Default::default(),
+            first: Default::default(),
+            second: Default::default(),
+        }
+    }
+}
+
+impl<B: Behavior<S>, S: Copy + Eq + Hash> Simulator<B, S> {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Add a node or replace the behavior of an existing one. Returns the
+    /// index of the node.
+    pub fn add(&mut self, name: &str, node: B) -> usize {
+        let (i, _) = self.nodes.insert_full(name.to_owned(), node);
+        if i == self.inputs.len() {
+            self.inputs.push(Vec::new());
+            self.outputs.push(Vec::new());
+        }
+        i
+    }
+
+    /// Index of a named node.
+    pub fn index(&self, name: &str) -> Option<usize> {
+        self.nodes.get_index_of(name)
+    }
+
+    pub fn name(&self, node: usize) -> &str {
+        self.nodes.get_index(node).unwrap().0
+    }
+
+    pub fn node(&self, node: usize) -> &B {
+        &self.nodes[node]
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Connect the output of a node into another node.
+    pub fn connect(&mut self, from: usize, to: usize) {
+        self.outputs[from].push(to);
+        self.inputs[to].push(from);
+        self.nodes[to].connect(from);
+    }
+
+    pub fn inputs(&self, node: usize) -> &[usize] {
+        &self.inputs[node]
+    }
+
+    pub fn outputs(&self, node: usize) -> &[usize] {
+        &self.outputs[node]
+    }
+
+    /// Number of rounds run so far.
+    pub fn round(&self) -> u64 {
+        self.round
+    }
+
+    /// Run a round that starts with `node` sending `signal` to its outputs.
+    pub fn send(&mut self, node: usize, signal: S) {
+        self.round += 1;
+        self.emit(node, signal);
+        while let Some((from, to, signal)) = self.queue.pop_front() {
+            if let Some(out) = self.nodes[to].receive(from, signal) {
+                self.emit(to, out);
+            }
+        }
+    }
+
+    fn emit(&mut self, node: usize, signal: S) {
+        for &to in &self.outputs[node] {
+            self.queue.push_back((node, to, signal));
+        }
+        *self.sent.entry((node, signal)).or_default() +=
+            self.outputs[node].len() as u64;
+        let first = *self.first.entry((node, signal)).or_insert(self.round);
+        if first != self.round {
+            self.second.entry((node, signal)).or_insert(self.round);
+        }
+    }
+
+    /// Number of messages with the signal a node has sent, counting one for
+    /// every receiver.
+    pub fn sent(&self, node: usize, signal: S) -> u64 {
+        self.sent.get(&(node, signal)).copied().unwrap_or(0)
+    }
+
+    /// Number of messages with the signal sent by all nodes.
+    pub fn total(&self, signal: S) -> u64 {
+        (0..self.len()).map(|i| self.sent(i, signal)).sum()
+    }
+
+    /// First round in which a node sent the signal, even if it has no
+    /// outputs.
+    pub fn first_sent(&self, node: usize, signal: S) -> Option<u64> {
+        self.first.get(&(node, signal)).copied()
+    }
+
+    /// Cycle lengths of nodes that send the signal periodically.
+    ///
+    /// Runs rounds started by `start` until every node has sent the signal
+    /// in two different rounds. A node's cycle length is the round it first
+    /// sent the signal in, and the second round must be twice that, counting
+    /// rounds from the start of the simulation. Returns `None` if a node
+    /// isn't periodic or `max_rounds` rounds pass first.
+    ///
+    /// A node that needs all of the periodic nodes to send the signal in the
+    /// same round will react after the least common multiple of the cycle
+    /// lengths.
+    pub fn periods(
+        &mut self,
+        nodes: &[usize],
+        signal: S,
+        start: (usize, S),
+        max_rounds: u64,
+    ) -> Option<Vec<u64>> {
+        let end = self.round + max_rounds;
+        loop {
+            let rounds: Vec<_> = nodes
+                .iter()
+                .map(|&n| {
+                    let first = self.first_sent(n, signal)?;
+                    let second = self.second.get(&(n, signal))?;
+                    Some((first, *second))
+                })
+                .collect();
+
+            if rounds.iter().all(|r| r.is_some()) {
+                return rounds
+                    .into_iter()
+                    .map(|r| {
+                        let (first, second) = r.unwrap();
+                        (second == 2 * first).then_some(first)
+                    })
+                    .collect();
+            }
+            if self.round == end {
+                return None;
+            }
+            self.send(start.0, start.1);
+        }
+    }
+}
+
+impl Simulator<Module, Pulse> {
+    /// Inputs of the conjunction that decides when `target` gets a pulse.
+    ///
+    /// Walks back from `target` through nodes with a single input until
+    /// reaching a conjunction that joins several inputs. Returns `None` if
+    /// the path branches or loops before that.
+    pub fn feeders(&self, target: usize) -> Option<&[usize]> {
+        let mut node = target;
+        for _ in 0..self.len() {
+            match (self.node(node), self.inputs(node)) {
+                (Module::Conjunction(_), inputs) if inputs.len() > 1 => {
+                    return Some(inputs)
+                }
+                (_, &[input]) => node = input,
+                _ => return None,
+            }
+        }
+        None
+    }
+}
+
+impl FromStr for Simulator<Module, Pulse> {
+    type Err = &'static str;
+
+    /// Parse module lines like `%a -> b, c` where `%` marks a flip-flop, `&`
+    /// a conjunction and no prefix a broadcaster. Nodes that are only named
+    /// as outputs become broadcasters with no outputs.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut ret = Simulator::new();
+        let mut edges = Vec::new();
+        for line in s.lines().filter(|l| !l.trim().is_empty()) {
+            let (module, outputs) =
+                line.split_once(" -> ").ok_or("missing arrow")?;
+            let (name, node) = if let Some(name) = module.strip_prefix('%') {
+                (name, Module::FlipFlop(false))
+            } else if let Some(name) = module.strip_prefix('&') {
+                (name, Module::Conjunction(Vec::new()))
+            } else {
+                (module, Module::Broadcast)
+            };
+            ret.add(name, node);
+            for o in outputs.split(", ") {
+                edges.push((name, o));
+            }
+        }
+
+        for (a, b) in edges {
+            let i = ret.index(a).unwrap();
+            let j = match ret.index(b) {
+                Some(j) => j,
+                None => ret.add(b, Module::Broadcast),
+            };
+            ret.connect(i, j);
+        }
+        Ok(ret)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn push_button(sim: &mut Simulator<Module, Pulse>) -> usize {
+        let button = sim.add("button", Module::Broadcast);
+        let broadcaster = sim.index("broadcaster").unwrap();
+        sim.connect(button, broadcaster);
+        button
+    }
+
+    #[test]
+    fn pulses() {
+        for (input, low, high) in [
+            ("broadcaster -> a, b, c\n%a -> b\n%b -> c\n%c -> inv\n&inv -> a", 8000, 4000),
+            ("broadcaster -> a\n%a -> inv, con\n&inv -> b\n%b -> con\n&con -> output", 4250, 2750),
+        ] {
+            let mut sim: Simulator<Module, Pulse> = input.parse().unwrap();
+            let button = push_button(&mut sim);
+            for _ in 0..1000 {
+                sim.send(button, Low);
+            }
+            assert_eq!(sim.total(Low), low);
+            assert_eq!(sim.total(High), high);
+        }
+    }
+
+    #[test]
+    fn periods() {
+        // Two counters that reset at 5 and 3 and are joined into `out`.
+        let input = "\
+broadcaster -> a0, b0
+%a0 -> a1, ca
+%a1 -> a2
+%a2 -> ca
+&ca -> a1, a0, ia
+&ia -> hub
+%b0 -> b1, cb
+%b1 -> cb
+&cb -> b0, ib
+&ib -> hub
+&hub -> out";
+        let mut sim: Simulator<Module, Pulse> = input.parse().unwrap();
+        let button = push_button(&mut sim);
+        let out = sim.index("out").unwrap();
+        let feeders = sim.feeders(out).unwrap().to_vec();
+        let names: Vec<_> = feeders.iter().map(|&n| sim.name(n)).collect();
+        assert_eq!(names, ["ia", "ib"]);
+        assert_eq!(sim.feeders(sim.index("a2").unwrap()), None);
+        assert_eq!(sim.clone().periods(&feeders, High, (button, Low), 9), None);
+        assert_eq!(
+            sim.clone().periods(&feeders, High, (button, Low), 10),
+            Some(vec![5, 3])
+        );
+
+        while sim.first_sent(out, Low).is_none() {
+            sim.send(button, Low);
+        }
+        assert_eq!(sim.round(), 15);
+        // Flip-flop that is on in odd rounds.
+        let a0 = sim.index("a0").unwrap();
+        assert_eq!(sim.periods(&[a0], High, (button, Low), 10), None);
+    }
+}
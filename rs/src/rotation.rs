@@ -0,0 +1,259 @@
+use std::{
+    hash::Hash,
+    ops::{Mul, Neg, Sub},
+};
+
+use crate::prelude::HashMap;
+
+/// One of the 24 rotations that map an axis-aligned cube onto itself.
+///
+/// Stored as a signed permutation of axes, component `i` of the rotated
+/// vector is `signs[i] * v[axes[i]]`.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct Rotation3 {
+    axes: [usize; 3],
+    signs: [i8; 3],
+}
+
+impl Default for Rotation3 {
+    fn default() -> Self {
+        Rotation3::IDENTITY
+    }
+}
+
+impl Rotation3 {
+    pub const IDENTITY: Rotation3 = Rotation3 {
+        axes: [0, 1, 2],
+        signs: [1, 1, 1],
+    };
+
+    /// All the proper rotations of a cube, identity first.
+    pub fn all() -> [Rotation3; 24] {
+        const PERMUTATIONS: [([usize; 3], i8); 6] = [
+            ([0, 1, 2], 1),
+            ([0, 2, 1], -1),
+            ([1, 0, 2], -1),
+            ([1, 2, 0], 1),
+            ([2, 0, 1], 1),
+            ([2, 1, 0], -1),
+        ];
+
+        let mut ret = [Rotation3::IDENTITY; 24];
+        let mut i = 0;
+        for (axes, parity) in PERMUTATIONS {
+            for bits in 0..8 {
+                let signs: [i8; 3] =
+                    std::array::from_fn(|j| 1 - 2 * ((bits >> j) & 1));
+                // Reflections have determinant -1, skip them.
+                if parity * signs.iter().product::<i8>() == 1 {
+                    ret[i] = Rotation3 { axes, signs };
+                    i += 1;
+                }
+            }
+        }
+        ret
+    }
+
+    pub fn inverse(&self) -> Self {
+        let mut ret = *self;
+        for i in 0..3 {
+            ret.axes[self.axes[i]] = i;
+            ret.signs[self.axes[i]] = self.signs[i];
+        }
+        ret
+    }
+
+    /// Rotate a vector.
+    pub fn apply<T, V>(&self, v: V) -> V
+    where
+        T: Copy + Neg<Output = T>,
+        V: Into<[T; 3]> + From<[T; 3]>,
+    {
+        let v = v.into();
+        V::from(std::array::from_fn(|i| {
+            let x = v[self.axes[i]];
+            if self.signs[i] < 0 {
+                -x
+            } else {
+                x
+            }
+        }))
+    }
+
+    /// Integer rotation matrix, `m[row][col]`.
+    pub fn matrix(&self) -> [[i32; 3]; 3] {
+        let mut ret = [[0; 3]; 3];
+        for i in 0..3 {
+            ret[i][self.axes[i]] = self.signs[i] as i32;
+        }
+        ret
+    }
+}
+
+/// Composition, `(a * b).apply(v) == a.apply(b.apply(v))`.
+impl Mul for Rotation3 {
+    type Output = Rotation3;
+
+    fn mul(self, rhs: Rotation3) -> Rotation3 {
+        Rotation3 {
+            axes: self.axes.map(|a| rhs.axes[a]),
+            signs: std::array::from_fn(|i| {
+                self.signs[i] * rhs.signs[self.axes[i]]
+            }),
+        }
+    }
+}
+
+/// Find the rotation that puts a point cloud in a canonical orientation.
+///
+/// Point clouds that are rotated and translated copies of each other will
+/// have the same canonical form. The canonical form is the lexically
+/// smallest sorted list of rotated points moved so that the smallest point
+/// is at origin.
+pub fn canonical_orientation<T, V>(points: &[V]) -> (Rotation3, Vec<[T; 3]>)
+where
+    T: Copy + Ord + Neg<Output = T> + Sub<Output = T>,
+    V: Copy + Into<[T; 3]> + From<[T; 3]>,
+{
+    Rotation3::all()
+        .into_iter()
+        .map(|rot| {
+            let mut ps: Vec<[T; 3]> =
+                points.iter().map(|&p| rot.apply(p).into()).collect();
+            ps.sort();
+            if let Some(&min) = ps.first() {
+                for p in ps.iter_mut() {
+                    *p = std::array::from_fn(|i| p[i] - min[i]);
+                }
+            }
+            (rot, ps)
+        })
+        .min_by(|(_, a), (_, b)| a.cmp(b))
+        .unwrap()
+}
+
+/// Find a rotation and translation that moves at least `k` points of `scan`
+/// onto points in `reference`.
+///
+/// Returns `(rot, offset)` so that `rot.apply(p) + offset` maps points of
+/// `scan` into the `reference` frame.
+pub fn register<T, V>(
+    reference: &[V],
+    scan: &[V],
+    k: usize,
+) -> Option<(Rotation3, [T; 3])>
+where
+    T: Copy + Eq + Hash + Neg<Output = T> + Sub<Output = T>,
+    V: Copy + Into<[T; 3]> + From<[T; 3]>,
+{
+    for rot in Rotation3::all() {
+        let rotated: Vec<[T; 3]> =
+            scan.iter().map(|&p| rot.apply(p).into()).collect();
+
+        // Every pairing of points votes for the offset that would make them
+        // coincide.
+        let mut votes: HashMap<[T; 3], usize> = HashMap::default();
+        for &a in reference {
+            let a = a.into();
+            for b in &rotated {
+                let offset = std::array::from_fn(|i| a[i] - b[i]);
+                let n = votes.entry(offset).or_default();
+                *n += 1;
+                if *n >= k {
+                    return Some((rot, offset));
+                }
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+
+    #[test]
+    fn group() {
+        let all = Rotation3::all();
+        assert_eq!(all.iter().collect::<HashSet<_>>().len(), 24);
+        assert_eq!(all[0], Rotation3::IDENTITY);
+
+        for a in all {
+            assert!(all.contains(&a.inverse()));
+            assert_eq!(a * a.inverse(), Rotation3::IDENTITY);
+            assert_eq!(a.inverse() * a, Rotation3::IDENTITY);
+
+            for b in all {
+                assert!(all.contains(&(a * b)));
+                let v = ivec3(1, 2, 3);
+                assert_eq!((a * b).apply(v), a.apply(b.apply(v)));
+            }
+
+            // Rotations preserve the cross product, reflections would flip
+            // it.
+            let (x, y) = (ivec3(1, 0, 0), ivec3(0, 1, 0));
+            assert_eq!(a.apply(x).cross(a.apply(y)), a.apply(x.cross(y)));
+        }
+    }
+
+    #[test]
+    fn orbits() {
+        let uniqs = |v: [i64; 3]| {
+            Rotation3::all()
+                .iter()
+                .map(|r| r.apply(v))
+                .collect::<HashSet<_>>()
+                .len()
+        };
+
+        assert_eq!(uniqs([0, 0, 0]), 1);
+
+        assert_eq!(uniqs([1, 0, 0]), 6);
+        assert_eq!(uniqs([0, 1, 0]), 6);
+        assert_eq!(uniqs([0, 0, 1]), 6);
+
+        assert_eq!(uniqs([1, 2, 3]), 24);
+    }
+
+    #[test]
+    fn matrix() {
+        for rot in Rotation3::all() {
+            let m = rot.matrix();
+            let v = [4, -5, 6];
+            let mv: [i32; 3] =
+                std::array::from_fn(|i| (0..3).map(|j| m[i][j] * v[j]).sum());
+            assert_eq!(rot.apply(v), mv);
+        }
+    }
+
+    #[test]
+    fn registration() {
+        let cloud = [
+            i64vec3(1, 2, 3),
+            i64vec3(-4, 0, 2),
+            i64vec3(7, 7, -1),
+            i64vec3(0, -3, 5),
+            i64vec3(2, 9, 0),
+        ];
+        let rot = Rotation3::all()[17];
+        let offset = i64vec3(10, -20, 30);
+        // Scan sees the cloud from a rotated and moved frame, plus some
+        // points the reference doesn't have.
+        let mut scan: Vec<I64Vec3> = cloud
+            .iter()
+            .map(|&p| rot.inverse().apply(p - offset))
+            .collect();
+        scan.push(i64vec3(100, 100, 100));
+        scan.push(i64vec3(-50, 30, 20));
+
+        let (r, t) = register(&cloud, &scan, 5).unwrap();
+        assert_eq!(r, rot);
+        assert_eq!(I64Vec3::from(t), offset);
+        assert!(register(&cloud, &scan, 6).is_none());
+
+        let (_, a) = canonical_orientation(&cloud);
+        let (_, b) = canonical_orientation(&scan[..5]);
+        assert_eq!(a, b);
+    }
+}
@@ -0,0 +1,396 @@
+//! Streaming tokenizer for reading input without allocating per item.
+
+use std::{
+    io::{self, BufRead, Read},
+    str::FromStr,
+};
+
+use crate::{
+    axis_box::{area, Rect},
+    parse::ParseError,
+};
+
+/// Byte oriented scanner over a buffered reader.
+///
+/// Tokens are slices into the reader's buffer when they fit in it and get
+/// copied into a reused scratch buffer when they cross a refill, so scanning
+/// doesn't allocate per token. The bytes of a returned token are consumed
+/// from the reader on the next call or when the scanner is dropped, so other
+/// readers of the same stream see a consistent position after that.
+///
+/// I/O errors and invalid UTF-8 in text tokens cause panics.
+pub struct Scanner<R: BufRead> {
+    reader: R,
+    /// Bytes of the last token that are still in the reader's buffer.
+    pending: usize,
+    scratch: Vec<u8>,
+    pos: Pos,
+    /// The last token was an integer, the byte after it is a separator.
+    after_int: bool,
+}
+
+/// Line and column in chars of the next byte, starting from 1.
+#[derive(Copy, Clone)]
+struct Pos {
+    line: usize,
+    col: usize,
+}
+
+impl Pos {
+    fn track(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            if b == b'\n' {
+                self.line += 1;
+                self.col = 1;
+            } else if b & 0xC0 != 0x80 {
+                // Count the first byte of each UTF-8 sequence.
+                self.col += 1;
+            }
+        }
+    }
+}
+
+/// Stdin reader that only takes the stdin lock while refilling.
+///
+/// Refills read one line at a time, so whatever follows the current line
+/// stays in the shared stdin buffer for other readers.
+#[derive(Default)]
+pub struct Stdin {
+    buf: Vec<u8>,
+    pos: usize,
+}
+
+impl Read for Stdin {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        let buf = self.fill_buf()?;
+        let n = buf.len().min(out.len());
+        out[..n].copy_from_slice(&buf[..n]);
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl BufRead for Stdin {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.pos == self.buf.len() {
+            self.buf.clear();
+            self.pos = 0;
+            io::stdin().lock().read_until(b'\n', &mut self.buf)?;
+        }
+        Ok(&self.buf[self.pos..])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos = (self.pos + amt).min(self.buf.len());
+    }
+}
+
+impl Scanner<Stdin> {
+    /// Scanner over stdin that doesn't hold the stdin lock between calls.
+    pub fn stdin() -> Self {
+        Scanner::new(Stdin::default())
+    }
+}
+
+impl<R: BufRead> Scanner<R> {
+    pub fn new(reader: R) -> Self {
+        Scanner {
+            reader,
+            pending: 0,
+            scratch: Vec::new(),
+            pos: Pos { line: 1, col: 1 },
+            after_int: false,
+        }
+    }
+
+    /// Line and column of the next byte, starting from 1.
+    pub fn position(&self) -> (usize, usize) {
+        (self.pos.line, self.pos.col)
+    }
+
+    fn settle(&mut self) {
+        self.after_int = false;
+        if self.pending > 0 {
+            self.reader.consume(self.pending);
+            self.pending = 0;
+        }
+    }
+
+    fn fill(&mut self) -> &[u8] {
+        self.reader.fill_buf().expect("read error")
+    }
+
+    /// Skip bytes that match the predicate, return the last skipped byte.
+    fn skip_while(&mut self, pred: impl Fn(u8) -> bool) -> Option<u8> {
+        let mut last = None;
+        loop {
+            let buf = self.reader.fill_buf().expect("read error");
+            let n = buf.iter().position(|&b| !pred(b)).unwrap_or(buf.len());
+            if n == 0 {
+                return last;
+            }
+            last = Some(buf[n - 1]);
+            self.pos.track(&buf[..n]);
+            let done = n < buf.len();
+            self.reader.consume(n);
+            if done {
+                return last;
+            }
+        }
+    }
+
+    /// Take bytes that match the predicate after `prefix`.
+    fn take_while(
+        &mut self,
+        prefix: &[u8],
+        pred: impl Fn(u8) -> bool,
+    ) -> &[u8] {
+        self.scratch.clear();
+        self.scratch.extend_from_slice(prefix);
+        loop {
+            let buf = self.reader.fill_buf().expect("read error");
+            let n = buf.iter().position(|&b| !pred(b)).unwrap_or(buf.len());
+            self.pos.track(&buf[..n]);
+            if n == buf.len() && n > 0 {
+                // Token continues past the buffer.
+                self.scratch.extend_from_slice(buf);
+                self.reader.consume(n);
+            } else if self.scratch.is_empty() {
+                self.pending = n;
+                return &self.fill()[..n];
+            } else {
+                self.scratch.extend_from_slice(&buf[..n]);
+                self.reader.consume(n);
+                return &self.scratch;
+            }
+        }
+    }
+
+    /// Next byte without consuming it.
+    pub fn peek(&mut self) -> Option<u8> {
+        self.settle();
+        self.fill().first().copied()
+    }
+
+    pub fn byte(&mut self) -> Option<u8> {
+        let b = self.peek()?;
+        self.pos.track(&[b]);
+        self.reader.consume(1);
+        Some(b)
+    }
+
+    pub fn char(&mut self) -> Option<char> {
+        let len = match self.peek()? {
+            0..=0x7F => 1,
+            0xC0..=0xDF => 2,
+            0xE0..=0xEF => 3,
+            _ => 4,
+        };
+        let mut bytes = [0; 4];
+        for b in bytes.iter_mut().take(len) {
+            *b = self.byte().expect("invalid UTF-8");
+        }
+        let s = std::str::from_utf8(&bytes[..len]).expect("invalid UTF-8");
+        s.chars().next()
+    }
+
+    /// Parse the next integer, skipping anything that isn't a digit.
+    ///
+    /// A minus sign right before the digits makes the number negative,
+    /// except when it directly follows the previous number, so `1-2` reads
+    /// as 1 and 2. This is the same rule as
+    /// [`numbers`](crate::prelude::numbers).
+    pub fn try_int<T: FromStr>(&mut self) -> Option<Result<T, ParseError>> {
+        let after_int = self.after_int;
+        self.settle();
+        if after_int && self.fill().first().is_some_and(|b| *b != b'\n') {
+            self.byte();
+        }
+        let neg = self.skip_while(|b| !b.is_ascii_digit()) == Some(b'-');
+        self.fill().first()?;
+
+        let (line, mut col) = self.position();
+        if neg {
+            col -= 1;
+        }
+        let prefix: &[u8] = if neg { b"-" } else { b"" };
+        let digits = self.take_while(prefix, |b| b.is_ascii_digit());
+        // Only ASCII digits and sign.
+        let text = std::str::from_utf8(digits).unwrap();
+        let ret = text
+            .parse()
+            .map_err(|_| ParseError::of_type::<T>(text).at(line, col));
+        self.after_int = true;
+        Some(ret)
+    }
+
+    /// Parse the next integer, panic if it doesn't fit in the type.
+    pub fn int<T: FromStr>(&mut self) -> Option<T> {
+        self.try_int()
+            .map(|a| a.unwrap_or_else(|e: ParseError| panic!("{e}")))
+    }
+
+    /// Iterate the remaining integers.
+    pub fn ints<T: FromStr>(&mut self) -> impl Iterator<Item = T> + '_ {
+        std::iter::from_fn(|| self.int())
+    }
+
+    /// Next run of non-whitespace bytes.
+    pub fn word_bytes(&mut self) -> Option<&[u8]> {
+        self.settle();
+        self.skip_while(|b| b.is_ascii_whitespace());
+        self.fill().first()?;
+        Some(self.take_while(b"", |b| !b.is_ascii_whitespace()))
+    }
+
+    /// Next run of non-whitespace characters.
+    pub fn word(&mut self) -> Option<&str> {
+        self.word_bytes()
+            .map(|a| std::str::from_utf8(a).expect("invalid UTF-8"))
+    }
+
+    /// Next line without the line ending.
+    pub fn line_bytes(&mut self) -> Option<&[u8]> {
+        self.settle();
+        self.fill().first()?;
+        self.take_while(b"", |b| b != b'\n');
+        let n = self.scratch.len().max(self.pending);
+        // Consume the newline along with the line.
+        if self.scratch.is_empty() {
+            if self.fill().get(n) == Some(&b'\n') {
+                self.pos.track(b"\n");
+                self.pending += 1;
+            }
+        } else if self.fill().first() == Some(&b'\n') {
+            self.pos.track(b"\n");
+            self.reader.consume(1);
+        }
+
+        let line = if self.scratch.is_empty() {
+            &self.reader.fill_buf().expect("read error")[..n]
+        } else {
+            &self.scratch[..]
+        };
+        Some(line.strip_suffix(b"\r").unwrap_or(line))
+    }
+
+    /// Next line without the line ending.
+    pub fn line(&mut self) -> Option<&str> {
+        self.line_bytes()
+            .map(|a| std::str::from_utf8(a).expect("invalid UTF-8"))
+    }
+
+    /// Next non-whitespace byte and its position as zero-based column and
+    /// line.
+    pub fn cell(&mut self) -> Option<([i32; 2], u8)> {
+        self.settle();
+        self.skip_while(|b| b.is_ascii_whitespace());
+        let (line, col) = self.position();
+        let b = self.byte()?;
+        Some(([col as i32 - 1, line as i32 - 1], b))
+    }
+
+    /// Read the rest of the input as a grid of bytes.
+    ///
+    /// Works like [`grid`](crate::prelude::grid), leading blank lines are
+    /// skipped and short lines are padded with spaces.
+    pub fn grid(&mut self) -> (Rect<i32>, Vec<u8>) {
+        // Concatenated lines and their lengths.
+        let mut bytes = Vec::new();
+        let mut lens = Vec::new();
+        while let Some(line) = self.line_bytes() {
+            let line = line.trim_ascii_end();
+            if lens.is_empty() && line.is_empty() {
+                continue;
+            }
+            bytes.extend_from_slice(line);
+            lens.push(line.len());
+        }
+        while lens.last() == Some(&0) {
+            lens.pop();
+        }
+
+        let w = lens.iter().copied().max().unwrap_or(0);
+        let mut ret = Vec::with_capacity(w * lens.len());
+        let mut p = 0;
+        for &n in &lens {
+            ret.extend_from_slice(&bytes[p..p + n]);
+            ret.resize(ret.len() + w - n, b' ');
+            p += n;
+        }
+        (area(w as i32, lens.len() as i32), ret)
+    }
+}
+
+impl<R: BufRead> Drop for Scanner<R> {
+    fn drop(&mut self) {
+        self.settle();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Scanner with a buffer of the given size, small sizes make tokens
+    /// cross refills.
+    fn scanner(s: &str, cap: usize) -> Scanner<io::BufReader<&[u8]>> {
+        Scanner::new(io::BufReader::with_capacity(cap, s.as_bytes()))
+    }
+
+    #[test]
+    fn ints() {
+        let input = "x=-12, y=345..-6\n1-2 99999999999 -7";
+        for cap in [3, 64] {
+            let mut sc = scanner(input, cap);
+            let got: Vec<i64> = sc.ints().collect();
+            assert_eq!(got, [-12, 345, -6, 1, 2, 99999999999, -7]);
+        }
+        for cap in [3, 64] {
+            let mut sc = scanner(input, cap);
+            let got: Vec<_> = std::iter::from_fn(|| sc.try_int::<i32>())
+                .map(|a| a.map_err(|e| (e.line, e.col)))
+                .collect();
+            assert_eq!(got[..5], [Ok(-12), Ok(345), Ok(-6), Ok(1), Ok(2)]);
+            assert_eq!(got[5], Err((2, 5)));
+            assert_eq!(sc.int::<i32>(), None);
+        }
+        let mut sc = scanner("-5 6", 3);
+        assert!(sc.try_int::<u32>().unwrap().is_err());
+        assert_eq!(sc.int::<u32>(), Some(6));
+    }
+
+    #[test]
+    fn text() {
+        let input = "abc defgh\r\n\nij  k\n";
+        for cap in [3, 64] {
+            let mut sc = scanner(input, cap);
+            assert_eq!(sc.word(), Some("abc"));
+            assert_eq!(sc.line(), Some(" defgh"));
+            assert_eq!(sc.line(), Some(""));
+            assert_eq!(sc.position(), (3, 1));
+            assert_eq!(sc.word(), Some("ij"));
+            assert_eq!(sc.word(), Some("k"));
+            assert_eq!(sc.word(), None);
+            assert_eq!(sc.line(), None);
+        }
+
+        let mut sc = scanner("å\nb", 3);
+        assert_eq!(sc.char(), Some('å'));
+        assert_eq!(sc.position(), (1, 2));
+        assert_eq!(sc.line(), Some(""));
+        assert_eq!(sc.line(), Some("b"));
+    }
+
+    #[test]
+    fn grid() {
+        let input = "\n\n #\n..#.\n\n";
+        let mut sc = scanner(input, 3);
+        assert_eq!(sc.cell(), Some(([1, 2], b'#')));
+        assert_eq!(sc.cell(), Some(([0, 3], b'.')));
+
+        let (bounds, cells) = scanner(input, 3).grid();
+        assert_eq!(bounds, area(4, 2));
+        assert_eq!(cells, b" #  ..#.");
+    }
+}
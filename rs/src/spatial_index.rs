@@ -0,0 +1,360 @@
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::BinaryHeap,
+    ops::Range,
+};
+
+use crate::{
+    axis_box::{pmax, pmin, AxisBox, Element},
+    lattice::Metric,
+};
+
+struct Node<T, const N: usize> {
+    /// Bounds of everything in the node, treated as a closed box.
+    bounds: AxisBox<T, N>,
+    /// Range of the node's items in the items array.
+    items: Range<usize>,
+    children: Option<[usize; 2]>,
+}
+
+const LEAF_SIZE: usize = 8;
+
+/// Bounding volume hierarchy over items that are either points or
+/// axis-aligned boxes.
+///
+/// Points are stored as zero-volume boxes and are considered to overlap a
+/// region if the region contains them. Boxes overlap a region if they
+/// intersect it.
+pub struct SpatialIndex<T, V, const N: usize> {
+    items: Vec<(AxisBox<T, N>, V)>,
+    nodes: Vec<Node<T, N>>,
+}
+
+impl<T: Element, V, const N: usize> SpatialIndex<T, V, N> {
+    pub fn from_boxes(
+        items: impl IntoIterator<Item = (AxisBox<T, N>, V)>,
+    ) -> Self {
+        let mut ret = SpatialIndex {
+            items: items.into_iter().collect(),
+            nodes: Vec::new(),
+        };
+        if !ret.items.is_empty() {
+            ret.build(0..ret.items.len());
+        }
+        ret
+    }
+
+    pub fn from_points(
+        items: impl IntoIterator<Item = (impl Into<[T; N]>, V)>,
+    ) -> Self {
+        Self::from_boxes(items.into_iter().map(|(p, v)| {
+            let p = p.into();
+            (AxisBox::new(p, p), v)
+        }))
+    }
+
+    /// Build a subtree for the items in range, return its node index.
+    fn build(&mut self, range: Range<usize>) -> usize {
+        let items = &mut self.items[range.clone()];
+        let bounds = items
+            .iter()
+            .map(|(b, _)| *b)
+            .reduce(|a, b| hull(&a, &b))
+            .unwrap();
+
+        let idx = self.nodes.len();
+        self.nodes.push(Node {
+            bounds,
+            items: range.clone(),
+            children: None,
+        });
+
+        if items.len() <= LEAF_SIZE {
+            return idx;
+        }
+
+        // Split at the median item center along the longest axis.
+        let axis = bounds.longest_axis();
+        let mid = items.len() / 2;
+        items.select_nth_unstable_by(mid, |(a, _), (b, _)| {
+            (a.p0[axis] + a.p1[axis])
+                .partial_cmp(&(b.p0[axis] + b.p1[axis]))
+                .unwrap_or(Ordering::Equal)
+        });
+
+        let mid = range.start + mid;
+        let left = self.build(range.start..mid);
+        let right = self.build(mid..range.end);
+        self.nodes[idx].children = Some([left, right]);
+        idx
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&AxisBox<T, N>, &V)> {
+        self.items.iter().map(|(b, v)| (b, v))
+    }
+
+    /// Return all items that overlap `region`.
+    pub fn range(&self, region: &AxisBox<T, N>) -> Vec<(&AxisBox<T, N>, &V)> {
+        let mut ret = Vec::new();
+        if self.is_empty() {
+            return ret;
+        }
+
+        let mut stack = vec![0];
+        while let Some(i) = stack.pop() {
+            let node = &self.nodes[i];
+            if !hull_overlaps(&node.bounds, region) {
+                continue;
+            }
+            if let Some(children) = node.children {
+                stack.extend(children);
+            } else {
+                ret.extend(
+                    self.items[node.items.clone()]
+                        .iter()
+                        .filter(|(b, _)| overlaps(b, region))
+                        .map(|(b, v)| (b, v)),
+                );
+            }
+        }
+        ret
+    }
+
+    /// Return up to `k` items closest to `p`, closest first.
+    ///
+    /// Box items are measured from their nearest point, treating them as
+    /// closed boxes.
+    pub fn nearest(
+        &self,
+        p: impl Into<[T; N]>,
+        k: usize,
+        metric: Metric,
+    ) -> Vec<(T, &AxisBox<T, N>, &V)> {
+        let p = p.into();
+        // Sorted list of (distance, item index), at most k long.
+        let mut best: Vec<(T, usize)> = Vec::new();
+        if k > 0 && !self.is_empty() {
+            self.nearest_search(0, p, k, metric, &mut best);
+        }
+        best.into_iter()
+            .map(|(d, i)| (d, &self.items[i].0, &self.items[i].1))
+            .collect()
+    }
+
+    fn nearest_search(
+        &self,
+        node: usize,
+        p: [T; N],
+        k: usize,
+        metric: Metric,
+        best: &mut Vec<(T, usize)>,
+    ) {
+        let node = &self.nodes[node];
+        let Some(children) = node.children else {
+            for i in node.items.clone() {
+                let d = metric.box_dist(p, &self.items[i].0);
+                if best.len() == k && d >= best[k - 1].0 {
+                    continue;
+                }
+                let pos = best.partition_point(|(e, _)| *e <= d);
+                best.insert(pos, (d, i));
+                best.truncate(k);
+            }
+            return;
+        };
+
+        // Visit the closer child first so the second one is more likely to
+        // get pruned.
+        let mut children =
+            children.map(|c| (metric.box_dist(p, &self.nodes[c].bounds), c));
+        if children[1].0 < children[0].0 {
+            children.swap(0, 1);
+        }
+        for (d, c) in children {
+            if best.len() == k && d >= best[k - 1].0 {
+                continue;
+            }
+            self.nearest_search(c, p, k, metric, best);
+        }
+    }
+}
+
+impl<T: Element + Ord, V, const N: usize> SpatialIndex<T, V, N> {
+    /// Branch-and-bound search for the integer point within `bounds` that
+    /// overlaps the largest number of items. Ties are broken by taxicab
+    /// distance to origin.
+    ///
+    /// `overlaps(region, item)` must return true if the item covers any
+    /// point in region, the search only calls it for items whose box
+    /// intersects the region. It must not give false negatives for large
+    /// regions and must be exact for unit cubes.
+    ///
+    /// Returns the point and the number of items that overlap it.
+    pub fn max_overlap(
+        &self,
+        bounds: &AxisBox<T, N>,
+        overlaps: impl Fn(&AxisBox<T, N>, &V) -> bool,
+    ) -> Option<([T; N], usize)> {
+        let count = |region: &AxisBox<T, N>| {
+            self.range(region)
+                .into_iter()
+                .filter(|(_, v)| overlaps(region, v))
+                .count()
+        };
+        let origin_dist = |region: &AxisBox<T, N>| {
+            Metric::Taxicab.dist(region.clamp([T::zero(); N]), [T::zero(); N])
+        };
+        // Use edge length instead of volume to prefer smaller regions, volume
+        // overflows easily.
+        let size = |region: &AxisBox<T, N>| region.dim()[region.longest_axis()];
+
+        let mut regions = vec![*bounds];
+        let mut heap = BinaryHeap::from([(
+            count(bounds),
+            Reverse(origin_dist(bounds)),
+            Reverse(size(bounds)),
+            0,
+        )]);
+
+        while let Some((n, _, Reverse(edge), i)) = heap.pop() {
+            let region = regions[i];
+            if edge <= T::one() {
+                return Some((region.p0, n));
+            }
+
+            for r in region.split_along(region.longest_axis()) {
+                if r.is_empty() {
+                    continue;
+                }
+                heap.push((
+                    count(&r),
+                    Reverse(origin_dist(&r)),
+                    Reverse(size(&r)),
+                    regions.len(),
+                ));
+                regions.push(r);
+            }
+        }
+
+        None
+    }
+}
+
+/// Smallest closed box containing both closed boxes.
+fn hull<T: Element, const N: usize>(
+    a: &AxisBox<T, N>,
+    b: &AxisBox<T, N>,
+) -> AxisBox<T, N> {
+    AxisBox {
+        p0: std::array::from_fn(|i| pmin(a.p0[i], b.p0[i])),
+        p1: std::array::from_fn(|i| pmax(a.p1[i], b.p1[i])),
+    }
+}
+
+/// Conservative overlap test for node bounds.
+fn hull_overlaps<T: Element, const N: usize>(
+    hull: &AxisBox<T, N>,
+    region: &AxisBox<T, N>,
+) -> bool {
+    (0..N).all(|i| hull.p0[i] < region.p1[i] && region.p0[i] <= hull.p1[i])
+}
+
+/// Item overlap test where zero-width axes are treated as points.
+fn overlaps<T: Element, const N: usize>(
+    item: &AxisBox<T, N>,
+    region: &AxisBox<T, N>,
+) -> bool {
+    (0..N).all(|i| {
+        if item.p0[i] == item.p1[i] {
+            region.p0[i] <= item.p0[i] && item.p0[i] < region.p1[i]
+        } else {
+            item.p0[i] < region.p1[i] && region.p0[i] < item.p1[i]
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+    use super::*;
+
+    fn random_points(n: usize) -> Vec<[i32; 3]> {
+        let mut rng = SmallRng::seed_from_u64(123);
+        (0..n)
+            .map(|_| std::array::from_fn(|_| rng.gen_range(-100..100)))
+            .collect()
+    }
+
+    #[test]
+    fn range_query() {
+        let points = random_points(1000);
+        let index = SpatialIndex::from_points(
+            points.iter().enumerate().map(|(i, &p)| (p, i)),
+        );
+        assert_eq!(index.len(), points.len());
+
+        let region = AxisBox::new([-20, -50, 0], [30, 10, 100]);
+        let mut found: Vec<usize> =
+            index.range(&region).into_iter().map(|(_, &i)| i).collect();
+        found.sort();
+        let expected: Vec<usize> = (0..points.len())
+            .filter(|&i| region.contains(points[i]))
+            .collect();
+        assert_eq!(found, expected);
+    }
+
+    #[test]
+    fn nearest_neighbors() {
+        let points = random_points(1000);
+        let index = SpatialIndex::from_points(
+            points.iter().enumerate().map(|(i, &p)| (p, i)),
+        );
+
+        for metric in [Metric::Taxicab, Metric::Euclidean, Metric::Chessboard] {
+            for q in [[0, 0, 0], [100, 100, 100], [-37, 12, 80]] {
+                let found: Vec<i32> = index
+                    .nearest(q, 10, metric)
+                    .into_iter()
+                    .map(|(d, _, _)| d)
+                    .collect();
+                let mut expected: Vec<i32> =
+                    points.iter().map(|&p| metric.dist(p, q)).collect();
+                expected.sort();
+                expected.truncate(10);
+                assert_eq!(found, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn box_overlap() {
+        let index = SpatialIndex::from_boxes([
+            (AxisBox::new([0, 0], [10, 10]), 'a'),
+            (AxisBox::new([5, 5], [15, 15]), 'b'),
+            (AxisBox::new([8, -5], [20, 9]), 'c'),
+            (AxisBox::new([-10, -10], [-5, -5]), 'd'),
+        ]);
+
+        let mut hits: Vec<char> = index
+            .range(&AxisBox::new([9, 8], [10, 9]))
+            .into_iter()
+            .map(|(_, &c)| c)
+            .collect();
+        hits.sort();
+        assert_eq!(hits, vec!['a', 'b', 'c']);
+
+        let bounds = AxisBox::new([-20, -20], [20, 20]);
+        let (p, n) = index.max_overlap(&bounds, |r, _| !r.is_empty()).unwrap();
+        assert_eq!(n, 3);
+        assert_eq!(p, [8, 5]);
+    }
+}
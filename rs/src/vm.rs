@@ -0,0 +1,456 @@
+//! Generic register machine for the assembly language puzzles.
+
+use std::{
+    collections::VecDeque,
+    fmt::Debug,
+    ops::{Index, IndexMut},
+};
+
+use derive_more::{Deref, DerefMut};
+
+use crate::{prelude::reg, Operand};
+
+mod decompile;
+mod network;
+mod optimize;
+mod search;
+
+pub use decompile::{Block, Cfg, Decompiler, Expr, Loop, Symbolic, Target};
+pub use network::{Channel, IdleHook, Link, Network, Outcome, Policy};
+pub use search::{bit_search, BitSearch};
+
+/// Instruction set for a register machine.
+pub trait InstructionSet: Sized + Clone {
+    /// Value type for registers and I/O.
+    type Word: Copy + Default + Debug;
+
+    /// Size of the register file.
+    const REGISTERS: usize;
+
+    /// Execute the instruction on the CPU.
+    ///
+    /// The instruction is a copy of `cpu.prog[cpu.pc]`, so it's safe to
+    /// modify the program during execution.
+    fn exec(&self, cpu: &mut Cpu<Self>) -> Flow;
+
+    /// Describe the instruction for the loop optimizer.
+    fn micro(&self) -> Micro {
+        Micro::Opaque
+    }
+}
+
+/// Description of an instruction for program analysis.
+///
+/// Registers are named with letters, `'a'` is register 0. Memory operands
+/// read memory that only opaque instructions can write.
+#[derive(Copy, Clone, Debug)]
+pub enum Micro {
+    /// `r = a`
+    Set(char, Operand),
+    /// `r = a op b`
+    Bin(char, BinOp, Operand, Operand),
+    /// Read the next input value into `r`.
+    Input(char),
+    /// Write `a` to output.
+    Output(Operand),
+    /// Relative jump by `b` if `a` is nonzero.
+    Jnz(Operand, Operand),
+    /// Jump to instruction `b` if `a` is nonzero.
+    Goto(Operand, usize),
+    /// Instruction with no effect.
+    Nop,
+    /// Anything else, assumed to have unknown effects on registers and the
+    /// program. Opaque instructions must not jump.
+    Opaque,
+}
+
+impl Micro {
+    /// `r = r op a`
+    pub fn apply(r: char, op: BinOp, a: Operand) -> Micro {
+        Micro::Bin(r, op, Operand::Reg(r), a)
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    /// Division rounding towards zero.
+    Div,
+    /// Remainder with the sign of the dividend.
+    Mod,
+    /// 1 if equal, 0 otherwise.
+    Eql,
+    /// 1 if not equal, 0 otherwise.
+    Neq,
+    And,
+    Xor,
+    /// Right shift.
+    Shr,
+}
+
+impl BinOp {
+    /// Evaluate the operation, `None` if it's undefined for the arguments.
+    pub fn eval(self, a: i64, b: i64) -> Option<i64> {
+        match self {
+            BinOp::Add => a.checked_add(b),
+            BinOp::Sub => a.checked_sub(b),
+            BinOp::Mul => a.checked_mul(b),
+            BinOp::Div => a.checked_div(b),
+            BinOp::Mod => a.checked_rem(b),
+            BinOp::Eql => Some((a == b) as i64),
+            BinOp::Neq => Some((a != b) as i64),
+            BinOp::And => Some(a & b),
+            BinOp::Xor => Some(a ^ b),
+            BinOp::Shr => u32::try_from(b)
+                .ok()
+                .map(|b| a.checked_shr(b).unwrap_or(a >> 63)),
+        }
+    }
+}
+
+/// Control flow effect of an instruction.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Flow {
+    /// Proceed to next instruction.
+    Next,
+    /// Relative jump.
+    Jump(i64),
+    /// Absolute jump.
+    Goto(i64),
+    /// Instruction can't execute yet, usually because it's waiting for
+    /// input. PC stays put and the instruction will be retried.
+    Block,
+    /// Stop the machine.
+    Halt,
+}
+
+/// State of the CPU after a step.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Status {
+    Running,
+    /// Waiting on something, typically input.
+    Blocked,
+    /// Program counter has left the program or a halt instruction was run.
+    Halted,
+    /// Hook stopped execution before the current instruction.
+    Break,
+}
+
+/// Register file that can be indexed with numbers or register names.
+///
+/// Register names are letters starting from 'a', see `reg`.
+#[derive(Clone, Default, Eq, PartialEq, Hash, Debug, Deref, DerefMut)]
+pub struct Registers<W>(pub Vec<W>);
+
+impl<W> Index<usize> for Registers<W> {
+    type Output = W;
+
+    fn index(&self, i: usize) -> &W {
+        &self.0[i]
+    }
+}
+
+impl<W> IndexMut<usize> for Registers<W> {
+    fn index_mut(&mut self, i: usize) -> &mut W {
+        &mut self.0[i]
+    }
+}
+
+impl<W> Index<char> for Registers<W> {
+    type Output = W;
+
+    fn index(&self, c: char) -> &W {
+        &self.0[reg(c)]
+    }
+}
+
+impl<W> IndexMut<char> for Registers<W> {
+    fn index_mut(&mut self, c: char) -> &mut W {
+        &mut self.0[reg(c)]
+    }
+}
+
+/// Hook called before each instruction. Returning false stops execution
+/// before the instruction is run.
+pub type Hook<I> = Box<dyn FnMut(&mut Cpu<I>, &I) -> bool>;
+
+pub struct Cpu<I: InstructionSet> {
+    pub pc: i64,
+    pub regs: Registers<I::Word>,
+    pub prog: Vec<I>,
+    pub input: VecDeque<I::Word>,
+    pub output: VecDeque<I::Word>,
+    /// Number of instructions executed.
+    pub steps: u64,
+    halted: bool,
+    hook: Option<Hook<I>>,
+    optimizer: Option<fn(&mut Cpu<I>) -> bool>,
+    cache: optimize::Cache,
+}
+
+impl<I: InstructionSet> Cpu<I> {
+    pub fn new(prog: impl Into<Vec<I>>) -> Self {
+        Cpu {
+            pc: 0,
+            regs: Registers(vec![Default::default(); I::REGISTERS]),
+            prog: prog.into(),
+            input: Default::default(),
+            output: Default::default(),
+            steps: 0,
+            halted: false,
+            hook: None,
+            optimizer: None,
+            cache: Default::default(),
+        }
+    }
+
+    /// Set initial register values, starting from the first register.
+    pub fn with_regs(
+        mut self,
+        regs: impl IntoIterator<Item = I::Word>,
+    ) -> Self {
+        for (r, x) in self.regs.iter_mut().zip(regs) {
+            *r = x;
+        }
+        self
+    }
+
+    pub fn with_input(
+        mut self,
+        input: impl IntoIterator<Item = I::Word>,
+    ) -> Self {
+        self.input.extend(input);
+        self
+    }
+
+    pub fn set_hook(
+        &mut self,
+        hook: impl FnMut(&mut Cpu<I>, &I) -> bool + 'static,
+    ) {
+        self.hook = Some(Box::new(hook));
+    }
+
+    pub fn clear_hook(&mut self) {
+        self.hook = None;
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.halted || self.pc < 0 || self.pc >= self.prog.len() as i64
+    }
+
+    /// Current instruction, if the machine is not halted.
+    pub fn current(&self) -> Option<&I> {
+        if self.is_halted() {
+            None
+        } else {
+            self.prog.get(self.pc as usize)
+        }
+    }
+
+    /// Execute a single instruction.
+    pub fn step(&mut self) -> Status {
+        let Some(op) = self.current().cloned() else {
+            return Status::Halted;
+        };
+
+        if let Some(mut hook) = self.hook.take() {
+            let proceed = hook(self, &op);
+            self.hook = Some(hook);
+            if !proceed {
+                return Status::Break;
+            }
+        }
+
+        if let Some(skip_loop) = self.optimizer {
+            if skip_loop(self) {
+                self.steps += 1;
+                return if self.is_halted() {
+                    Status::Halted
+                } else {
+                    Status::Running
+                };
+            }
+        }
+
+        let flow = op.exec(self);
+        if self.optimizer.is_some() && matches!(op.micro(), Micro::Opaque) {
+            // The instruction may have modified the program.
+            self.cache.clear();
+        }
+        match flow {
+            Flow::Next => self.pc += 1,
+            Flow::Jump(n) => self.pc += n,
+            Flow::Goto(n) => self.pc = n,
+            Flow::Block => return Status::Blocked,
+            Flow::Halt => {
+                self.steps += 1;
+                self.halted = true;
+                return Status::Halted;
+            }
+        }
+        self.steps += 1;
+
+        if self.is_halted() {
+            Status::Halted
+        } else {
+            Status::Running
+        }
+    }
+
+    /// Run until the machine halts, blocks or hits a break.
+    pub fn run_until_blocked(&mut self) -> Status {
+        loop {
+            match self.step() {
+                Status::Running => {}
+                s => return s,
+            }
+        }
+    }
+
+    /// Run until the machine halts.
+    ///
+    /// Panics if the machine blocks or hits a break before halting.
+    pub fn run(&mut self) {
+        let status = self.run_until_blocked();
+        assert_eq!(status, Status::Halted, "Machine stopped before halting");
+    }
+
+    /// Run until the machine produces output and return the oldest output
+    /// value.
+    ///
+    /// Returns `None` if the machine stops before producing output.
+    pub fn next_output(&mut self) -> Option<I::Word> {
+        while self.output.is_empty() {
+            if self.step() != Status::Running {
+                break;
+            }
+        }
+        self.output.pop_front()
+    }
+}
+
+impl<I: InstructionSet<Word = i64>> Cpu<I> {
+    /// Skip over loops with a closed form solution instead of executing
+    /// them.
+    ///
+    /// Skipped loops count as one step and don't trigger hooks. Loop
+    /// analyses are cached until an opaque instruction runs, so changes to
+    /// `prog` from outside the machine aren't seen once it has started.
+    pub fn optimize(mut self) -> Self {
+        self.optimizer = Some(optimize::skip_loop);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, rc::Rc};
+
+    use super::*;
+
+    /// Toy instruction set for a two-register machine.
+    #[derive(Copy, Clone, Debug)]
+    enum Op {
+        /// Read input into register.
+        In(usize),
+        /// Write register to output.
+        Out(usize),
+        /// Add register to register.
+        Add(usize, usize),
+        /// Decrement register.
+        Dec(usize),
+        /// Jump relative if register is nonzero.
+        Jnz(usize, i64),
+    }
+
+    impl InstructionSet for Op {
+        type Word = i64;
+
+        const REGISTERS: usize = 2;
+
+        fn exec(&self, cpu: &mut Cpu<Self>) -> Flow {
+            match *self {
+                Op::In(r) => match cpu.input.pop_front() {
+                    Some(x) => cpu.regs[r] = x,
+                    None => return Flow::Block,
+                },
+                Op::Out(r) => cpu.output.push_back(cpu.regs[r]),
+                Op::Add(a, b) => cpu.regs[a] += cpu.regs[b],
+                Op::Dec(r) => cpu.regs[r] -= 1,
+                Op::Jnz(r, n) => {
+                    if cpu.regs[r] != 0 {
+                        return Flow::Jump(n);
+                    }
+                }
+            }
+            Flow::Next
+        }
+    }
+
+    use Op::*;
+
+    // Read n, output n, n-1, ..., 1.
+    const COUNTDOWN: [Op; 5] = [In(0), Out(0), Dec(0), Jnz(0, -2), Jnz(1, -4)];
+
+    #[test]
+    fn io() {
+        let mut cpu = Cpu::new(COUNTDOWN).with_regs([0, 1]);
+        assert_eq!(cpu.run_until_blocked(), Status::Blocked);
+        assert_eq!(cpu.steps, 0);
+
+        cpu.input.push_back(3);
+        assert_eq!(cpu.next_output(), Some(3));
+        assert_eq!(cpu.next_output(), Some(2));
+        assert_eq!(cpu.next_output(), Some(1));
+        assert_eq!(cpu.next_output(), None);
+        assert_eq!(cpu.run_until_blocked(), Status::Blocked);
+
+        let mut cpu = Cpu::new(&COUNTDOWN[..4]).with_input([2]);
+        cpu.run();
+        assert!(cpu.is_halted());
+        assert_eq!(cpu.output, [2, 1]);
+        assert_eq!(cpu.steps, 7);
+        assert_eq!(cpu.regs, Registers(vec![0, 0]));
+
+        let mut cpu = Cpu::new([In(0), In(1), Add(0, 1), Out(0)]);
+        cpu.input.extend([2, 3]);
+        assert_eq!(cpu.next_output(), Some(5));
+        assert_eq!(cpu.run_until_blocked(), Status::Halted);
+    }
+
+    #[test]
+    fn hook() {
+        // Count executed jumps.
+        let jumps = Rc::new(Cell::new(0));
+        let mut cpu = Cpu::new(&COUNTDOWN[..4]).with_input([5]);
+        cpu.set_hook({
+            let jumps = jumps.clone();
+            move |_, op| {
+                if matches!(op, Jnz(..)) {
+                    jumps.set(jumps.get() + 1);
+                }
+                true
+            }
+        });
+        cpu.run();
+        assert_eq!(jumps.get(), 5);
+
+        // Break on decrementing to zero.
+        let mut cpu = Cpu::new(&COUNTDOWN[..4]).with_input([5]);
+        cpu.set_hook(|cpu, op| !(matches!(op, Dec(_)) && cpu.regs[0] == 1));
+        assert_eq!(cpu.run_until_blocked(), Status::Break);
+        assert_eq!(cpu.output, [5, 4, 3, 2, 1]);
+        assert_eq!(cpu.regs[0], 1);
+    }
+
+    #[test]
+    fn named_registers() {
+        let mut regs = Registers(vec![0; 4]);
+        regs['c'] = 3;
+        assert_eq!(regs[2], 3);
+        regs[3] += 1;
+        assert_eq!(regs['d'], 1);
+    }
+}
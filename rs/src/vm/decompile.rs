@@ -0,0 +1,1150 @@
+//! Symbolic execution and decompilation of register machine programs.
+
+use std::{
+    fmt::{self, Debug, Write},
+    ops::{Range, RangeInclusive},
+    rc::Rc,
+};
+
+use super::{BinOp, InstructionSet, Micro};
+use crate::{prelude::*, Operand};
+
+/// Symbolic value.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum Expr {
+    Const(i64),
+    /// Value of a register at the start of the analyzed code.
+    Reg(char),
+    /// Input value, numbered by the input instruction that read it.
+    Input(usize),
+    Bin(BinOp, Rc<Expr>, Rc<Expr>),
+    /// Memory at an address. Only opaque instructions can change memory.
+    Load(Rc<Expr>),
+}
+
+use Expr::*;
+
+impl Expr {
+    pub fn as_const(&self) -> Option<i64> {
+        match self {
+            Const(c) => Some(*c),
+            _ => None,
+        }
+    }
+
+    /// Direct subexpressions.
+    fn args(&self) -> Vec<&Rc<Expr>> {
+        match self {
+            Bin(_, a, b) => vec![a, b],
+            Load(a) => vec![a],
+            _ => Vec::new(),
+        }
+    }
+
+    /// Write expression, using names for named subexpressions.
+    fn write(
+        self: &Rc<Self>,
+        f: &mut impl Write,
+        names: &HashMap<*const Expr, String>,
+        parent: u8,
+    ) -> fmt::Result {
+        if let Some(name) = names.get(&Rc::as_ptr(self)) {
+            return write!(f, "{name}");
+        }
+        match &**self {
+            Const(c) => write!(f, "{c}"),
+            Reg(r) => write!(f, "{r}"),
+            Input(i) => write!(f, "in{i}"),
+            Bin(op, a, b) => {
+                // Show adding a negative constant as subtraction.
+                let (op, b) = match (op, &**b) {
+                    (BinOp::Add, &Const(c)) if c < 0 && c != i64::MIN => {
+                        (BinOp::Sub, Rc::new(Const(-c)))
+                    }
+                    _ => (*op, b.clone()),
+                };
+                let (sym, prec) = op_syntax(op);
+                if prec < parent {
+                    write!(f, "(")?;
+                }
+                a.write(f, names, prec)?;
+                write!(f, " {sym} ")?;
+                // Operators are left-associative.
+                b.write(f, names, prec + 1)?;
+                if prec < parent {
+                    write!(f, ")")?;
+                }
+                Ok(())
+            }
+            Load(a) => {
+                write!(f, "[")?;
+                a.write(f, names, 0)?;
+                write!(f, "]")
+            }
+        }
+    }
+}
+
+fn op_syntax(op: BinOp) -> (&'static str, u8) {
+    match op {
+        BinOp::Mul => ("*", 6),
+        BinOp::Div => ("/", 6),
+        BinOp::Mod => ("%", 6),
+        BinOp::Add => ("+", 5),
+        BinOp::Sub => ("-", 5),
+        BinOp::Shr => (">>", 4),
+        BinOp::Eql => ("==", 3),
+        BinOp::Neq => ("!=", 3),
+        BinOp::And => ("&", 2),
+        BinOp::Xor => ("^", 1),
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Rc::new(self.clone()).write(f, &Default::default(), 0)
+    }
+}
+
+type Interval = (i64, i64);
+
+const FULL: Interval = (i64::MIN, i64::MAX);
+
+/// Interpreter that runs instructions over symbolic values.
+///
+/// Expressions are simplified as they are built, using value range analysis
+/// to drop comparisons and modulo operations that can't affect the result.
+pub struct Symbolic {
+    /// Register values, indexed with `reg`.
+    pub regs: Vec<Rc<Expr>>,
+    /// Values written to output.
+    pub outputs: Vec<Rc<Expr>>,
+    /// Number of input values read.
+    pub inputs: usize,
+    input_range: Interval,
+    /// Value ranges of expressions created by the interpreter. The
+    /// expressions are kept alive in the arena so the addresses stay valid.
+    ranges: HashMap<*const Expr, Interval>,
+    arena: Vec<Rc<Expr>>,
+}
+
+impl Default for Symbolic {
+    fn default() -> Self {
+        let mut ret = Symbolic {
+            regs: Vec::new(),
+            outputs: Vec::new(),
+            inputs: 0,
+            input_range: FULL,
+            ranges: Default::default(),
+            arena: Vec::new(),
+        };
+        ret.regs = ('a'..='z').map(|c| ret.node(Reg(c))).collect();
+        ret
+    }
+}
+
+impl Symbolic {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Set the range of values inputs can have.
+    pub fn with_input_range(mut self, range: RangeInclusive<i64>) -> Self {
+        self.input_range = (*range.start(), *range.end());
+        self
+    }
+
+    /// Set a register to a known value.
+    pub fn set(&mut self, r: char, x: i64) {
+        self.regs[reg(r)] = self.node(Const(x));
+    }
+
+    pub fn get(&self, r: char) -> &Rc<Expr> {
+        &self.regs[reg(r)]
+    }
+
+    pub fn constant(&mut self, x: i64) -> Rc<Expr> {
+        self.node(Const(x))
+    }
+
+    /// Expression for the next input value.
+    pub fn input(&mut self) -> Rc<Expr> {
+        self.inputs += 1;
+        self.node(Input(self.inputs - 1))
+    }
+
+    pub fn arg(&mut self, a: Operand) -> Rc<Expr> {
+        match a {
+            Operand::Int(x) => self.node(Const(x)),
+            Operand::Reg(r) => self.get(r).clone(),
+            Operand::Mem(r) => {
+                let addr = self.get(r).clone();
+                self.node(Load(addr))
+            }
+        }
+    }
+
+    /// Execute an instruction. Returns false without doing anything if the
+    /// instruction is a jump or opaque.
+    pub fn exec(&mut self, op: &Micro) -> bool {
+        match *op {
+            Micro::Set(r, a) => self.regs[reg(r)] = self.arg(a),
+            Micro::Bin(r, op, a, b) => {
+                let (a, b) = (self.arg(a), self.arg(b));
+                self.regs[reg(r)] = self.bin(op, a, b);
+            }
+            Micro::Input(r) => self.regs[reg(r)] = self.input(),
+            Micro::Output(a) => {
+                let a = self.arg(a);
+                self.outputs.push(a);
+            }
+            Micro::Nop => {}
+            Micro::Jnz(..) | Micro::Goto(..) | Micro::Opaque => return false,
+        }
+        true
+    }
+
+    /// Range of possible values of an expression.
+    pub fn range(&self, e: &Rc<Expr>) -> RangeInclusive<i64> {
+        let (lo, hi) = self.interval(e);
+        lo..=hi
+    }
+
+    fn interval(&self, e: &Rc<Expr>) -> Interval {
+        if let Some(&r) = self.ranges.get(&Rc::as_ptr(e)) {
+            return r;
+        }
+        match **e {
+            Const(c) => (c, c),
+            Input(_) => self.input_range,
+            _ => FULL,
+        }
+    }
+
+    fn node(&mut self, e: Expr) -> Rc<Expr> {
+        let range = match &e {
+            Const(c) => (*c, *c),
+            Reg(_) | Load(_) => FULL,
+            Input(_) => self.input_range,
+            Bin(op, a, b) => {
+                bin_interval(*op, self.interval(a), self.interval(b))
+            }
+        };
+        let e = Rc::new(e);
+        self.ranges.insert(Rc::as_ptr(&e), range);
+        self.arena.push(e.clone());
+        e
+    }
+
+    /// Build a simplified binary operation.
+    pub fn bin(&mut self, op: BinOp, a: Rc<Expr>, b: Rc<Expr>) -> Rc<Expr> {
+        use BinOp::*;
+
+        if let (Some(x), Some(y)) = (a.as_const(), b.as_const()) {
+            if let Some(z) = op.eval(x, y) {
+                return self.node(Const(z));
+            }
+        }
+
+        // Put constants on the right side of commutative operations.
+        let (a, b) = match op {
+            Add | Mul | Eql | Neq | And | Xor if a.as_const().is_some() => {
+                (b, a)
+            }
+            _ => (a, b),
+        };
+        let (ra, rb) = (self.interval(&a), self.interval(&b));
+        let k = b.as_const();
+        let disjoint = ra.1 < rb.0 || rb.1 < ra.0;
+        let boolean = ra.0 >= 0 && ra.1 <= 1;
+
+        match (op, k) {
+            (Add | Sub | Xor | Shr, Some(0)) | (Mul | Div, Some(1)) => {
+                return a
+            }
+            (Mul | And, Some(0)) => return b,
+            (Add, Some(k)) => {
+                // Merge constant additions.
+                if let Bin(Add, x, y) = &*a {
+                    if let Some(k) = y.as_const().and_then(|y| y.checked_add(k))
+                    {
+                        let k = self.node(Const(k));
+                        return self.bin(Add, x.clone(), k);
+                    }
+                }
+            }
+            // Move constants out to the right: x + (y + k) = (x + y) + k.
+            (Add, None) => {
+                if let Bin(Add, y, k) = &*b {
+                    if k.as_const().is_some() {
+                        let (y, k) = (y.clone(), k.clone());
+                        let a = self.bin(Add, a, y);
+                        return self.bin(Add, a, k);
+                    }
+                }
+            }
+            (Sub, Some(k)) if k != i64::MIN => {
+                let k = self.node(Const(-k));
+                return self.bin(Add, a, k);
+            }
+            (Mul, Some(k)) => {
+                if let Bin(Mul, x, y) = &*a {
+                    if let Some(k) = y.as_const().and_then(|y| y.checked_mul(k))
+                    {
+                        let k = self.node(Const(k));
+                        return self.bin(Mul, x.clone(), k);
+                    }
+                }
+            }
+            // Small values divide or shift to zero.
+            (Div, Some(k)) if k > 0 && ra.0 >= 0 && ra.1 < k => {
+                return self.node(Const(0))
+            }
+            (Shr, Some(k))
+                if (0..63).contains(&k) && ra.0 >= 0 && ra.1 >> k == 0 =>
+            {
+                return self.node(Const(0))
+            }
+            // Masking or taking modulo of a small value does nothing.
+            (Mod, Some(k)) if k > 0 && ra.0 >= 0 && ra.1 < k => return a,
+            (And, Some(k))
+                if k >= 0
+                    && (k as u64 + 1).is_power_of_two()
+                    && ra.0 >= 0
+                    && ra.1 <= k =>
+            {
+                return a
+            }
+            // Comparisons of booleans with constants.
+            (Eql, Some(1)) | (Neq, Some(0)) if boolean => return a,
+            (Eql, Some(0)) => match &*a {
+                Bin(Eql, x, y) => return self.bin(Neq, x.clone(), y.clone()),
+                Bin(Neq, x, y) => return self.bin(Eql, x.clone(), y.clone()),
+                _ => {}
+            },
+            _ => {}
+        }
+
+        match op {
+            Sub if a == b => return self.node(Const(0)),
+            Eql if a == b => return self.node(Const(1)),
+            Neq if a == b => return self.node(Const(0)),
+            Eql if disjoint => return self.node(Const(0)),
+            Neq if disjoint => return self.node(Const(1)),
+            _ => {}
+        }
+
+        self.node(Bin(op, a, b))
+    }
+}
+
+/// Range of a binary operation's result.
+fn bin_interval(op: BinOp, (a0, a1): Interval, (b0, b1): Interval) -> Interval {
+    let k = (b0 == b1).then_some(b0);
+    match op {
+        BinOp::Add => (a0.saturating_add(b0), a1.saturating_add(b1)),
+        BinOp::Sub => (a0.saturating_sub(b1), a1.saturating_sub(b0)),
+        BinOp::Mul => {
+            let ps = [
+                a0.saturating_mul(b0),
+                a0.saturating_mul(b1),
+                a1.saturating_mul(b0),
+                a1.saturating_mul(b1),
+            ];
+            (*ps.iter().min().unwrap(), *ps.iter().max().unwrap())
+        }
+        BinOp::Div => match k {
+            Some(k) if k > 0 => (a0 / k, a1 / k),
+            Some(k) if k < -1 => (a1 / k, a0 / k),
+            _ => FULL,
+        },
+        BinOp::Mod => match k.and_then(|k| k.checked_abs()) {
+            Some(m) if m > 0 => {
+                if a0 >= 0 {
+                    (0, a1.min(m - 1))
+                } else if a1 <= 0 {
+                    (a0.max(1 - m), 0)
+                } else {
+                    (1 - m, m - 1)
+                }
+            }
+            _ => FULL,
+        },
+        BinOp::Eql | BinOp::Neq => (0, 1),
+        BinOp::And if a0 >= 0 || b0 >= 0 => {
+            let hi = match (a0 >= 0, b0 >= 0) {
+                (true, true) => a1.min(b1),
+                (true, false) => a1,
+                _ => b1,
+            };
+            (0, hi)
+        }
+        BinOp::Xor if a0 >= 0 && b0 >= 0 => {
+            let bits = 64 - a1.max(b1).leading_zeros();
+            (0, ((1u64 << bits) - 1) as i64)
+        }
+        BinOp::Shr => match k {
+            Some(k) if (0..64).contains(&k) => (a0 >> k, a1 >> k),
+            _ if a0 >= 0 => (0, a1),
+            _ => FULL,
+        },
+        _ => FULL,
+    }
+}
+
+/// Where a jump goes.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Target {
+    Pc(usize),
+    /// Outside the program, the machine halts.
+    Exit,
+    /// Jump offset is computed at runtime.
+    Unknown,
+}
+
+/// Condition and target of a jump instruction.
+fn jump(prog: &[Micro], pc: usize) -> Option<(Operand, Target)> {
+    let target = |t: i64| {
+        if (0..prog.len() as i64).contains(&t) {
+            Target::Pc(t as usize)
+        } else {
+            Target::Exit
+        }
+    };
+    match prog[pc] {
+        Micro::Jnz(a, Operand::Int(b)) => Some((a, target(pc as i64 + b))),
+        Micro::Jnz(a, Operand::Reg(_) | Operand::Mem(_)) => {
+            Some((a, Target::Unknown))
+        }
+        Micro::Goto(a, b) => Some((a, target(b as i64))),
+        _ => None,
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Block {
+    /// Instructions of the block.
+    pub range: Range<usize>,
+    /// Indices of the blocks control can pass to after this one.
+    pub succs: Vec<usize>,
+}
+
+/// Natural loop formed by jumps back to a header block.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Loop {
+    pub header: usize,
+    /// Blocks in the loop including the header, sorted.
+    pub blocks: Vec<usize>,
+}
+
+/// Control flow graph made of basic blocks.
+#[derive(Clone, Debug)]
+pub struct Cfg {
+    pub blocks: Vec<Block>,
+}
+
+impl Cfg {
+    pub fn new(prog: &[Micro]) -> Self {
+        // Find the instructions that start blocks.
+        let mut leaders = vec![false; prog.len() + 1];
+        leaders[0] = true;
+        for pc in 0..prog.len() {
+            if let Some((_, target)) = jump(prog, pc) {
+                leaders[pc + 1] = true;
+                if let Target::Pc(t) = target {
+                    leaders[t] = true;
+                }
+            }
+        }
+        let starts: Vec<usize> =
+            (0..prog.len()).filter(|&i| leaders[i]).collect();
+
+        let block_of = |pc: usize| starts.partition_point(|&s| s <= pc) - 1;
+        let blocks = starts
+            .iter()
+            .enumerate()
+            .map(|(i, &start)| {
+                let end = starts.get(i + 1).copied().unwrap_or(prog.len());
+                let mut succs = Vec::new();
+                let falls_through = match jump(prog, end - 1) {
+                    Some((cond, target)) => {
+                        if let Target::Pc(t) = target {
+                            if !matches!(cond, Operand::Int(0)) {
+                                succs.push(block_of(t));
+                            }
+                        }
+                        !matches!(cond, Operand::Int(c) if c != 0)
+                    }
+                    None => true,
+                };
+                if falls_through
+                    && end < prog.len()
+                    && !succs.contains(&(i + 1))
+                {
+                    succs.push(i + 1);
+                }
+                Block {
+                    range: start..end,
+                    succs,
+                }
+            })
+            .collect();
+
+        Cfg { blocks }
+    }
+
+    /// Index of the block containing an instruction.
+    pub fn block_of(&self, pc: usize) -> Option<usize> {
+        let i = self.blocks.partition_point(|b| b.range.start <= pc);
+        (i > 0 && self.blocks[i - 1].range.contains(&pc)).then(|| i - 1)
+    }
+
+    pub fn preds(&self, block: usize) -> Vec<usize> {
+        (0..self.blocks.len())
+            .filter(|&i| self.blocks[i].succs.contains(&block))
+            .collect()
+    }
+
+    /// Loops reachable from the start of the program, ordered by header.
+    pub fn loops(&self) -> Vec<Loop> {
+        if self.blocks.is_empty() {
+            return Vec::new();
+        }
+
+        // Depth-first search for back edges.
+        let mut back_edges = Vec::new();
+        let mut seen = vec![false; self.blocks.len()];
+        let mut on_stack = vec![false; self.blocks.len()];
+        let mut stack = vec![(0, 0)];
+        seen[0] = true;
+        on_stack[0] = true;
+        while let Some((b, i)) = stack.pop() {
+            let Some(&s) = self.blocks[b].succs.get(i) else {
+                on_stack[b] = false;
+                continue;
+            };
+            stack.push((b, i + 1));
+            if on_stack[s] {
+                back_edges.push((b, s));
+            } else if !seen[s] {
+                seen[s] = true;
+                on_stack[s] = true;
+                stack.push((s, 0));
+            }
+        }
+
+        let mut ret: Vec<Loop> = Vec::new();
+        for (tail, header) in back_edges {
+            // Everything that reaches the tail without passing the header.
+            let mut blocks = HashSet::from_iter([header]);
+            let mut edge = vec![tail];
+            while let Some(b) = edge.pop() {
+                if blocks.insert(b) {
+                    edge.extend(self.preds(b));
+                }
+            }
+
+            match ret.iter_mut().find(|l| l.header == header) {
+                Some(l) => l.blocks.extend(blocks),
+                None => ret.push(Loop {
+                    header,
+                    blocks: blocks.into_iter().collect(),
+                }),
+            }
+        }
+        for l in ret.iter_mut() {
+            l.blocks.sort();
+            l.blocks.dedup();
+        }
+        ret.sort_by_key(|l| l.header);
+        ret
+    }
+}
+
+/// Pseudocode printer for register machine programs.
+///
+/// Each basic block is symbolically executed and printed as simplified
+/// assignments in terms of register values at the start of the block.
+/// Subexpressions used more than once get temporary names.
+pub struct Decompiler {
+    prog: Vec<Micro>,
+    /// Text of opaque instructions.
+    text: Vec<String>,
+    cfg: Cfg,
+    initial: Vec<(char, i64)>,
+    input_range: RangeInclusive<i64>,
+}
+
+impl Decompiler {
+    pub fn new<I: InstructionSet + Debug>(prog: &[I]) -> Self {
+        let micros: Vec<Micro> = prog.iter().map(|op| op.micro()).collect();
+        Decompiler {
+            cfg: Cfg::new(&micros),
+            prog: micros,
+            text: prog.iter().map(|op| format!("{op:?}")).collect(),
+            initial: Vec::new(),
+            input_range: i64::MIN..=i64::MAX,
+        }
+    }
+
+    /// Set known register values at program start.
+    pub fn with_regs(
+        mut self,
+        regs: impl IntoIterator<Item = (char, i64)>,
+    ) -> Self {
+        self.initial.extend(regs);
+        self
+    }
+
+    /// Set the range of values inputs can have.
+    pub fn with_input_range(mut self, range: RangeInclusive<i64>) -> Self {
+        self.input_range = range;
+        self
+    }
+
+    pub fn cfg(&self) -> &Cfg {
+        &self.cfg
+    }
+
+    fn write_block(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        block: usize,
+        temps: &mut usize,
+    ) -> fmt::Result {
+        let range = self.cfg.blocks[block].range.clone();
+        let mut sym = self.symbolic();
+        if block == 0 && self.cfg.preds(0).is_empty() {
+            for &(r, x) in &self.initial {
+                sym.set(r, x);
+            }
+        }
+        let mut seg = Segment::new(&sym);
+
+        for pc in range.clone() {
+            match self.prog[pc] {
+                Micro::Input(r) => {
+                    // Number inputs by instruction so loops read the same
+                    // name.
+                    let n = self.prog[..pc]
+                        .iter()
+                        .filter(|op| matches!(op, Micro::Input(_)))
+                        .count();
+                    let e = sym.node(Input(n));
+                    sym.regs[reg(r)] = e.clone();
+                    seg.stmts.push(Stmt::Read(e));
+                }
+                Micro::Output(a) => {
+                    let e = sym.arg(a);
+                    seg.stmts.push(Stmt::Output(e));
+                }
+                Micro::Opaque => {
+                    seg.write(f, &sym, None, temps)?;
+                    writeln!(f, "    {}", self.text[pc])?;
+                    // Registers can be anything now, start over.
+                    sym = self.symbolic();
+                    seg = Segment::new(&sym);
+                }
+                ref op => {
+                    if let Some((cond, target)) = jump(&self.prog, pc) {
+                        let cond = sym.arg(cond);
+                        let target = match target {
+                            Target::Pc(t) => {
+                                format!("L{}", self.cfg.block_of(t).unwrap())
+                            }
+                            Target::Exit => "exit".into(),
+                            Target::Unknown => {
+                                let Micro::Jnz(_, b) = *op else {
+                                    unreachable!()
+                                };
+                                let b = sym.arg(b);
+                                format!("{pc} + {b}")
+                            }
+                        };
+                        seg.write(f, &sym, Some((cond, target)), temps)?;
+                        return Ok(());
+                    }
+                    sym.exec(op);
+                }
+            }
+        }
+        seg.write(f, &sym, None, temps)
+    }
+
+    fn symbolic(&self) -> Symbolic {
+        Symbolic::new().with_input_range(self.input_range.clone())
+    }
+}
+
+impl fmt::Display for Decompiler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let loops = self.cfg.loops();
+        let mut temps = 0;
+        for i in 0..self.cfg.blocks.len() {
+            write!(f, "L{i}:")?;
+            if let Some(l) = loops.iter().find(|l| l.header == i) {
+                write!(f, " // loop")?;
+                for b in &l.blocks {
+                    write!(f, " L{b}")?;
+                }
+            }
+            writeln!(f)?;
+            self.write_block(f, i, &mut temps)?;
+        }
+        Ok(())
+    }
+}
+
+enum Stmt {
+    Read(Rc<Expr>),
+    Output(Rc<Expr>),
+}
+
+/// Straight-line code between opaque instructions.
+struct Segment {
+    /// Register values at the start of the segment.
+    entry: Vec<Rc<Expr>>,
+    stmts: Vec<Stmt>,
+}
+
+impl Segment {
+    fn new(sym: &Symbolic) -> Self {
+        Segment {
+            entry: sym.regs.clone(),
+            stmts: Vec::new(),
+        }
+    }
+
+    /// Print the segment's statements, register assignments and the jump at
+    /// the end.
+    fn write(
+        &self,
+        f: &mut impl Write,
+        sym: &Symbolic,
+        jump: Option<(Rc<Expr>, String)>,
+        temps: &mut usize,
+    ) -> fmt::Result {
+        let changed: Vec<usize> = (0..sym.regs.len())
+            .filter(|&i| !Rc::ptr_eq(&sym.regs[i], &self.entry[i]))
+            .collect();
+
+        // Everything that gets printed, in order.
+        let mut roots: Vec<Rc<Expr>> = self
+            .stmts
+            .iter()
+            .map(|s| match s {
+                Stmt::Read(e) | Stmt::Output(e) => e.clone(),
+            })
+            .collect();
+        // Values that end up in several places are printed once and then
+        // referred to through the register.
+        for e in changed
+            .iter()
+            .map(|&i| &sym.regs[i])
+            .chain(jump.iter().map(|(e, _)| e))
+        {
+            if !roots[self.stmts.len()..].iter().any(|r| Rc::ptr_eq(r, e)) {
+                roots.push(e.clone());
+            }
+        }
+
+        // Name compound expressions that are used more than once.
+        let mut uses: HashMap<*const Expr, usize> = HashMap::default();
+        let mut stack = roots.clone();
+        while let Some(e) = stack.pop() {
+            let n = uses.entry(Rc::as_ptr(&e)).or_default();
+            *n += 1;
+            if *n == 1 {
+                stack.extend(e.args().into_iter().cloned());
+            }
+        }
+        let mut named: HashSet<*const Expr> = HashSet::default();
+        let mut stack = roots.clone();
+        let mut seen = HashSet::default();
+        while let Some(e) = stack.pop() {
+            if !seen.insert(Rc::as_ptr(&e)) {
+                continue;
+            }
+            if matches!(*e, Bin(..)) && uses[&Rc::as_ptr(&e)] > 1 {
+                named.insert(Rc::as_ptr(&e));
+            }
+            stack.extend(e.args().into_iter().cloned());
+        }
+
+        // Assign registers after the other assignments have read their start
+        // values when possible.
+        let mut order = Vec::new();
+        let mut left = changed;
+        while !left.is_empty() {
+            let k = left
+                .iter()
+                .position(|&i| {
+                    !left.iter().any(|&j| {
+                        j != i && reaches(&sym.regs[j], &self.entry[i], &named)
+                    })
+                })
+                .unwrap_or(0);
+            order.push(left.remove(k));
+        }
+
+        // Start values of registers that are still read after the register
+        // has been assigned need to be saved in a temporary.
+        for (j, &i) in order.iter().enumerate() {
+            let entry = &self.entry[i];
+            let mut later = order[j + 1..]
+                .iter()
+                .map(|&k| &sym.regs[k])
+                .chain(jump.iter().map(|(e, _)| e));
+            if matches!(**entry, Reg(_))
+                && later.any(|e| reaches(e, entry, &named))
+            {
+                named.insert(Rc::as_ptr(entry));
+            }
+        }
+
+        let mut names: HashMap<*const Expr, String> = HashMap::default();
+        let mut line = String::new();
+
+        // Print the named subexpressions of an expression that haven't been
+        // printed yet.
+        let mut define = |e: &Rc<Expr>,
+                          names: &mut HashMap<*const Expr, String>,
+                          f: &mut dyn Write|
+         -> fmt::Result {
+            let mut order = Vec::new();
+            postorder(e, &named, names, &mut HashSet::default(), &mut order);
+            for e in order {
+                line.clear();
+                e.write(&mut line, names, 0)?;
+                let name = format!("t{}", *temps);
+                *temps += 1;
+                writeln!(f, "    {name} = {line}")?;
+                names.insert(Rc::as_ptr(&e), name);
+            }
+            Ok(())
+        };
+
+        let mut out = String::new();
+        for s in &self.stmts {
+            match s {
+                Stmt::Read(e) => {
+                    e.write(&mut out, &names, 0)?;
+                    writeln!(f, "    {out} = input()")?;
+                }
+                Stmt::Output(e) => {
+                    define(e, &mut names, &mut out)?;
+                    write!(f, "{out}")?;
+                    out.clear();
+                    e.write(&mut out, &names, 0)?;
+                    writeln!(f, "    output({out})")?;
+                }
+            }
+            out.clear();
+        }
+
+        // Define all temporaries before registers start changing.
+        for e in &roots[self.stmts.len()..] {
+            define(e, &mut names, &mut out)?;
+        }
+        write!(f, "{out}")?;
+
+        for &i in &order {
+            let e = &sym.regs[i];
+            let r = (b'a' + i as u8) as char;
+            out.clear();
+            e.write(&mut out, &names, 0)?;
+            writeln!(f, "    {r} = {out}")?;
+            // Later code can refer to the value through the register.
+            names.insert(Rc::as_ptr(e), r.to_string());
+        }
+
+        if let Some((cond, target)) = jump {
+            out.clear();
+            cond.write(&mut out, &names, 0)?;
+            match cond.as_const() {
+                Some(0) => {}
+                Some(_) => writeln!(f, "    goto {target}")?,
+                None if sym.range(&cond) == (0..=1) => {
+                    writeln!(f, "    if {out} goto {target}")?
+                }
+                None => writeln!(f, "    if {out} != 0 goto {target}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Whether `needle` is a subexpression of `e` without going through named
+/// expressions.
+fn reaches(
+    e: &Rc<Expr>,
+    needle: &Rc<Expr>,
+    named: &HashSet<*const Expr>,
+) -> bool {
+    if Rc::ptr_eq(e, needle) {
+        return true;
+    }
+    if named.contains(&Rc::as_ptr(e)) {
+        return false;
+    }
+    e.args().into_iter().any(|a| reaches(a, needle, named))
+}
+
+/// Collect named subexpressions that don't have names yet in dependency
+/// order.
+fn postorder(
+    e: &Rc<Expr>,
+    named: &HashSet<*const Expr>,
+    names: &HashMap<*const Expr, String>,
+    seen: &mut HashSet<*const Expr>,
+    order: &mut Vec<Rc<Expr>>,
+) {
+    let p = Rc::as_ptr(e);
+    if names.contains_key(&p) || !seen.insert(p) {
+        return;
+    }
+    for a in e.args() {
+        postorder(a, named, names, seen, order);
+    }
+    if named.contains(&p) {
+        order.push(e.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vm::{Cpu, Flow};
+    use Operand::{Int, Reg as R};
+
+    /// Instruction set made of micro-operations.
+    #[derive(Copy, Clone, Debug)]
+    struct Op(Micro);
+
+    impl InstructionSet for Op {
+        type Word = i64;
+
+        const REGISTERS: usize = 26;
+
+        fn exec(&self, cpu: &mut Cpu<Self>) -> Flow {
+            match self.0 {
+                Micro::Set(r, a) => cpu.regs[r] = a.val(&cpu.regs),
+                Micro::Bin(r, op, a, b) => {
+                    let (a, b) = (a.val(&cpu.regs), b.val(&cpu.regs));
+                    cpu.regs[r] = op.eval(a, b).expect("undefined operation");
+                }
+                Micro::Input(r) => match cpu.input.pop_front() {
+                    Some(x) => cpu.regs[r] = x,
+                    None => return Flow::Block,
+                },
+                Micro::Output(a) => cpu.output.push_back(a.val(&cpu.regs)),
+                Micro::Jnz(a, b) => {
+                    if a.val(&cpu.regs) != 0 {
+                        return Flow::Jump(b.val(&cpu.regs));
+                    }
+                }
+                Micro::Goto(a, n) => {
+                    if a.val(&cpu.regs) != 0 {
+                        return Flow::Goto(n as i64);
+                    }
+                }
+                Micro::Nop => {}
+                Micro::Opaque => unreachable!("opaque instruction"),
+            }
+            Flow::Next
+        }
+
+        fn micro(&self) -> Micro {
+            self.0
+        }
+    }
+
+    fn prog(ops: impl IntoIterator<Item = Micro>) -> Vec<Op> {
+        ops.into_iter().map(Op).collect()
+    }
+
+    #[test]
+    fn simplify() {
+        use BinOp::*;
+
+        let mut sym = Symbolic::new().with_input_range(1..=9);
+        let x = sym.input();
+        let three = sym.constant(3);
+        let e = sym.bin(Add, x.clone(), three.clone());
+        let e = sym.bin(Sub, e, three.clone());
+        assert_eq!(e, x);
+
+        let e = sym.bin(Add, three.clone(), x.clone());
+        assert_eq!(e.to_string(), "in0 + 3");
+        let m = sym.constant(-5);
+        let e = sym.bin(Add, e, m);
+        assert_eq!(e.to_string(), "in0 - 2");
+        assert_eq!(sym.range(&e), -1..=7);
+
+        let big = sym.constant(26);
+        let e = sym.bin(Mod, x.clone(), big.clone());
+        assert_eq!(e, x);
+        let e = sym.bin(Div, x.clone(), big.clone());
+        assert_eq!(e.as_const(), Some(0));
+        let e = sym.bin(Eql, x.clone(), big);
+        assert_eq!(e.as_const(), Some(0));
+
+        let y = sym.input();
+        let e = sym.bin(Eql, x.clone(), y.clone());
+        let zero = sym.constant(0);
+        let e = sym.bin(Eql, e, zero);
+        assert_eq!(e.to_string(), "in0 != in1");
+
+        let e = sym.bin(Add, x.clone(), y);
+        let e = sym.bin(Mul, e, three);
+        assert_eq!(e.to_string(), "(in0 + in1) * 3");
+    }
+
+    #[test]
+    fn straight_line() {
+        use BinOp::*;
+
+        // Three digits of a MONAD-style checker.
+        let mut ops = Vec::new();
+        for (div, check, offset) in [(1, 12, 4), (1, 11, 2), (26, -3, 7)] {
+            ops.extend([
+                Micro::Input('w'),
+                Micro::Bin('x', Mul, R('x'), Int(0)),
+                Micro::Bin('x', Add, R('x'), R('z')),
+                Micro::Bin('x', Mod, R('x'), Int(26)),
+                Micro::Bin('z', Div, R('z'), Int(div)),
+                Micro::Bin('x', Add, R('x'), Int(check)),
+                Micro::Bin('x', Eql, R('x'), R('w')),
+                Micro::Bin('x', Eql, R('x'), Int(0)),
+                Micro::Bin('y', Mul, R('y'), Int(0)),
+                Micro::Bin('y', Add, R('y'), Int(25)),
+                Micro::Bin('y', Mul, R('y'), R('x')),
+                Micro::Bin('y', Add, R('y'), Int(1)),
+                Micro::Bin('z', Mul, R('z'), R('y')),
+                Micro::Bin('y', Mul, R('y'), Int(0)),
+                Micro::Bin('y', Add, R('y'), R('w')),
+                Micro::Bin('y', Add, R('y'), Int(offset)),
+                Micro::Bin('y', Mul, R('y'), R('x')),
+                Micro::Bin('z', Add, R('z'), R('y')),
+            ]);
+        }
+        let prog = prog(ops);
+        for (input, z) in [([5, 3, 8], 249), ([5, 3, 2], 9)] {
+            let mut cpu = Cpu::new(prog.clone()).with_input(input);
+            cpu.run();
+            assert_eq!(cpu.regs['z'], z);
+        }
+
+        let code = Decompiler::new(&prog)
+            .with_regs("wxyz".chars().map(|c| (c, 0)))
+            .with_input_range(1..=9)
+            .to_string();
+        assert_eq!(
+            code,
+            "\
+L0:
+    in0 = input()
+    in1 = input()
+    in2 = input()
+    t0 = (in0 + 4) * 26 + in1 + 2
+    t1 = t0 % 26 - 3 != in2
+    t2 = (in2 + 7) * t1
+    w = in2
+    x = t1
+    y = t2
+    z = t0 / 26 * (x * 25 + 1) + y
+"
+        );
+    }
+
+    #[test]
+    fn loops() {
+        use BinOp::*;
+
+        // Count divisor pairs of b with nested loops.
+        let ops = [
+            Micro::Set('d', Int(2)),
+            Micro::Set('e', Int(2)),
+            Micro::Bin('g', Mul, R('d'), R('e')),
+            Micro::Bin('g', Eql, R('g'), R('b')),
+            Micro::Bin('h', Add, R('h'), R('g')),
+            Micro::Bin('e', Add, R('e'), Int(1)),
+            Micro::Bin('g', Sub, R('e'), R('b')),
+            Micro::Jnz(R('g'), Int(-5)),
+            Micro::Bin('d', Add, R('d'), Int(1)),
+            Micro::Bin('g', Sub, R('d'), R('b')),
+            Micro::Jnz(R('g'), Int(-9)),
+            Micro::Output(R('h')),
+        ];
+        let prog = prog(ops);
+        let mut cpu = Cpu::new(prog.clone()).with_regs([0, 6]);
+        cpu.run();
+        assert_eq!(cpu.output, [2]);
+
+        let dec = Decompiler::new(&prog);
+        let cfg = dec.cfg();
+        let ranges: Vec<_> =
+            cfg.blocks.iter().map(|b| b.range.clone()).collect();
+        assert_eq!(ranges, [0..1, 1..2, 2..8, 8..11, 11..12]);
+        assert_eq!(cfg.blocks[2].succs, [2, 3]);
+        assert_eq!(cfg.preds(2), [1, 2]);
+        assert_eq!(
+            cfg.loops(),
+            [
+                Loop {
+                    header: 1,
+                    blocks: vec![1, 2, 3]
+                },
+                Loop {
+                    header: 2,
+                    blocks: vec![2]
+                },
+            ]
+        );
+
+        assert_eq!(
+            dec.to_string(),
+            "\
+L0:
+    d = 2
+L1: // loop L1 L2 L3
+    e = 2
+L2: // loop L2
+    t0 = e + 1
+    g = t0 - b
+    h = h + (d * e == b)
+    e = t0
+    if g != 0 goto L2
+L3:
+    t1 = d + 1
+    d = t1
+    g = d - b
+    if g != 0 goto L1
+L4:
+    output(h)
+"
+        );
+    }
+
+    #[test]
+    fn memory() {
+        use Operand::Mem;
+
+        let ops = [
+            Micro::Bin('a', BinOp::Add, Mem('b'), Int(1)),
+            Micro::Output(Mem('a')),
+            Micro::Jnz(R('a'), Mem('c')),
+        ];
+        let dec = Decompiler::new(&prog(ops));
+        assert_eq!(
+            dec.to_string(),
+            "\
+L0:
+    t0 = [b] + 1
+    output([t0])
+    a = t0
+    if a != 0 goto 2 + [c]
+"
+        );
+    }
+}
@@ -0,0 +1,364 @@
+//! Multiple machines that talk to each other through channels.
+
+use std::collections::VecDeque;
+
+use super::{Cpu, InstructionSet, Status};
+
+/// Where a channel delivers the values written into it.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum Link {
+    /// Every value goes to one machine.
+    Direct(usize),
+    /// Every value goes to all the listed machines.
+    Broadcast(Vec<usize>),
+    /// Values form packets of the given length. The first value of a packet
+    /// is the index of the machine that gets the rest of the packet.
+    Addressed(usize),
+}
+
+#[derive(Clone, Debug)]
+pub struct Channel<W> {
+    pub name: String,
+    pub link: Link,
+    /// Machines whose output goes into the channel.
+    pub sources: Vec<usize>,
+    /// Number of values written into the channel.
+    pub sent: usize,
+    /// Number of values delivered to machine inputs, counted once for each
+    /// receiver.
+    pub received: usize,
+    /// Addressed packets with a destination outside the network, destination
+    /// included.
+    pub stray: VecDeque<Vec<W>>,
+    /// Incomplete addressed packets for each source.
+    partial: Vec<Vec<W>>,
+}
+
+/// How machines take turns.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Policy {
+    /// Run each machine until it blocks or halts.
+    UntilBlocked,
+    /// Run each machine for at most the given number of steps.
+    RoundRobin(u64),
+}
+
+/// Why a network stopped running.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Outcome {
+    /// Every machine has halted.
+    Halted,
+    /// No machine can proceed and the idle hook didn't help.
+    Deadlock,
+    /// The idle hook or a machine's hook asked to stop.
+    Stopped,
+}
+
+/// Hook called when every machine is waiting for input and nothing is in
+/// transit. It can feed new input to machines. Returning false stops the
+/// network.
+pub type IdleHook<I> = Box<dyn FnMut(&mut Network<I>) -> bool>;
+
+/// Scheduler for machines connected by channels.
+///
+/// Each machine's output goes to at most one channel. Output is moved into
+/// channels and delivered after every turn a machine gets.
+pub struct Network<I: InstructionSet> {
+    pub cpus: Vec<Cpu<I>>,
+    pub channels: Vec<Channel<I::Word>>,
+    policy: Policy,
+    idle: Option<IdleHook<I>>,
+}
+
+impl<I: InstructionSet> Network<I>
+where
+    I::Word: TryInto<usize>,
+{
+    pub fn new(cpus: impl IntoIterator<Item = Cpu<I>>) -> Self {
+        Network {
+            cpus: cpus.into_iter().collect(),
+            channels: Vec::new(),
+            policy: Policy::UntilBlocked,
+            idle: None,
+        }
+    }
+
+    pub fn with_policy(mut self, policy: Policy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Add a channel that carries the output of the source machines.
+    ///
+    /// Panics if a source already outputs to another channel.
+    pub fn with_channel(
+        mut self,
+        name: &str,
+        sources: impl IntoIterator<Item = usize>,
+        link: Link,
+    ) -> Self {
+        let sources: Vec<usize> = sources.into_iter().collect();
+        for &s in &sources {
+            assert!(
+                self.route(s).is_none(),
+                "Network: machine {s} already has an output channel"
+            );
+        }
+        self.channels.push(Channel {
+            name: name.to_owned(),
+            link,
+            partial: vec![Vec::new(); sources.len()],
+            sources,
+            sent: 0,
+            received: 0,
+            stray: Default::default(),
+        });
+        self
+    }
+
+    pub fn set_idle_hook(
+        &mut self,
+        hook: impl FnMut(&mut Network<I>) -> bool + 'static,
+    ) {
+        self.idle = Some(Box::new(hook));
+    }
+
+    /// Look up a channel by name.
+    ///
+    /// Panics if there is no such channel.
+    pub fn channel(&self, name: &str) -> &Channel<I::Word> {
+        self.channels
+            .iter()
+            .find(|c| c.name == name)
+            .unwrap_or_else(|| panic!("Network: no channel {name:?}"))
+    }
+
+    /// Channel and source slot for a machine's output.
+    fn route(&self, cpu: usize) -> Option<(usize, usize)> {
+        self.channels.iter().enumerate().find_map(|(i, c)| {
+            c.sources.iter().position(|&s| s == cpu).map(|j| (i, j))
+        })
+    }
+
+    /// Move a machine's output into its channel and on to the receivers.
+    fn deliver(&mut self, cpu: usize) {
+        let Some((i, slot)) = self.route(cpu) else {
+            return;
+        };
+        let chan = &mut self.channels[i];
+        let cpus = &mut self.cpus;
+        for x in cpus[cpu].output.drain(..).collect::<Vec<_>>() {
+            chan.sent += 1;
+            match &chan.link {
+                Link::Direct(dest) => {
+                    cpus[*dest].input.push_back(x);
+                    chan.received += 1;
+                }
+                Link::Broadcast(dests) => {
+                    for &dest in dests {
+                        cpus[dest].input.push_back(x);
+                        chan.received += 1;
+                    }
+                }
+                &Link::Addressed(len) => {
+                    let packet = &mut chan.partial[slot];
+                    packet.push(x);
+                    if packet.len() < len {
+                        continue;
+                    }
+                    let packet = std::mem::take(packet);
+                    match packet[0].try_into() {
+                        Ok(dest) if dest < cpus.len() => {
+                            cpus[dest]
+                                .input
+                                .extend(packet[1..].iter().copied());
+                            chan.received += len - 1;
+                        }
+                        _ => chan.stray.push_back(packet),
+                    }
+                }
+            }
+        }
+    }
+
+    /// Give one machine a turn. Returns whether it executed anything.
+    fn turn(&mut self, i: usize) -> Result<bool, Outcome> {
+        let cpu = &mut self.cpus[i];
+        let steps = cpu.steps;
+        let status = match self.policy {
+            Policy::UntilBlocked => cpu.run_until_blocked(),
+            Policy::RoundRobin(n) => {
+                let mut status = Status::Running;
+                for _ in 0..n {
+                    status = cpu.step();
+                    if status != Status::Running {
+                        break;
+                    }
+                }
+                status
+            }
+        };
+        let progress = cpu.steps != steps;
+        self.deliver(i);
+        if status == Status::Break {
+            return Err(Outcome::Stopped);
+        }
+        Ok(progress)
+    }
+
+    /// Run until all machines halt, the network deadlocks or a hook stops
+    /// it.
+    pub fn run(&mut self) -> Outcome {
+        loop {
+            let mut progress = false;
+            for i in 0..self.cpus.len() {
+                match self.turn(i) {
+                    Ok(p) => progress |= p,
+                    Err(outcome) => return outcome,
+                }
+            }
+            if progress {
+                continue;
+            }
+
+            if self.cpus.iter().all(|c| c.is_halted()) {
+                return Outcome::Halted;
+            }
+            if let Some(mut hook) = self.idle.take() {
+                let proceed = hook(self);
+                self.idle = Some(hook);
+                if !proceed {
+                    return Outcome::Stopped;
+                }
+            }
+            // Blocked machines only block on empty input.
+            if !self
+                .cpus
+                .iter()
+                .any(|c| !c.is_halted() && !c.input.is_empty())
+            {
+                return Outcome::Deadlock;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, rc::Rc};
+
+    use super::*;
+    use crate::vm::Flow;
+
+    /// Single register machine with input, output and countdown.
+    #[derive(Copy, Clone, Debug)]
+    enum Op {
+        In,
+        Out,
+        /// Output a constant.
+        Put(i64),
+        Dec,
+        /// Jump relative if register is nonzero.
+        Jnz(i64),
+    }
+
+    impl InstructionSet for Op {
+        type Word = i64;
+
+        const REGISTERS: usize = 1;
+
+        fn exec(&self, cpu: &mut Cpu<Self>) -> Flow {
+            match *self {
+                Op::In => match cpu.input.pop_front() {
+                    Some(x) => cpu.regs[0] = x,
+                    None => return Flow::Block,
+                },
+                Op::Out => cpu.output.push_back(cpu.regs[0]),
+                Op::Put(x) => cpu.output.push_back(x),
+                Op::Dec => cpu.regs[0] -= 1,
+                Op::Jnz(n) => {
+                    if cpu.regs[0] != 0 {
+                        return Flow::Jump(n);
+                    }
+                }
+            }
+            Flow::Next
+        }
+    }
+
+    use Op::*;
+
+    // Pass a decreasing value back and forth until it hits zero.
+    const PING: [Op; 5] = [In, Dec, Out, Jnz(-3), Put(0)];
+
+    #[test]
+    fn ping_pong() {
+        for policy in [Policy::UntilBlocked, Policy::RoundRobin(1)] {
+            let mut net =
+                Network::new([Cpu::new(PING).with_input([10]), Cpu::new(PING)])
+                    .with_policy(policy)
+                    .with_channel("a", [0], Link::Direct(1))
+                    .with_channel("b", [1], Link::Direct(0));
+            // Second machine halts after sending 0, first one waits for more.
+            assert_eq!(net.run(), Outcome::Deadlock);
+            assert!(net.cpus[1].is_halted());
+            assert_eq!(net.channel("a").sent, 7);
+            assert_eq!(net.channel("b").sent, 6);
+            assert_eq!(net.channel("b").received, 6);
+            assert_eq!(net.cpus[1].input, [-1, -1]);
+        }
+    }
+
+    #[test]
+    fn broadcast() {
+        let mut net = Network::new([
+            Cpu::new([Put(1), Put(2)]),
+            Cpu::new([In, In, Out, Put(7)]),
+            Cpu::new([In, In, In, Out, Put(9)]),
+        ])
+        .with_channel("all", [0], Link::Broadcast(vec![1, 2]))
+        .with_channel("out", [1, 2], Link::Addressed(2));
+        assert_eq!(net.run(), Outcome::Halted);
+        assert_eq!(net.channel("all").received, 4);
+        // Second machine sends 7 to the third, which sends 9 to nowhere.
+        let out = net.channel("out");
+        assert_eq!(out.sent, 4);
+        assert_eq!(out.received, 1);
+        assert_eq!(out.stray, [vec![7, 9]]);
+    }
+
+    #[test]
+    fn idle_hook() {
+        // Read a value and send it outside the network until it's zero.
+        let prog = [In, Put(5), Out, Jnz(-3)];
+        let net = || {
+            Network::new([Cpu::new(prog).with_input([3]), Cpu::new(prog)])
+                .with_channel("net", [0, 1], Link::Addressed(2))
+        };
+
+        // Count down the value bouncing it between machines.
+        let mut bounce = net();
+        let wakeups = Rc::new(Cell::new(0));
+        bounce.set_idle_hook({
+            let wakeups = wakeups.clone();
+            move |net| {
+                wakeups.set(wakeups.get() + 1);
+                let packet = net.channels[0].stray.pop_front().unwrap();
+                if packet[1] == 0 {
+                    return false;
+                }
+                net.cpus[wakeups.get() % 2].input.push_back(packet[1] - 1);
+                true
+            }
+        });
+        assert_eq!(bounce.run(), Outcome::Stopped);
+        assert_eq!(wakeups.get(), 4);
+        assert!(bounce.cpus[1].is_halted());
+
+        // Hook that doesn't do anything.
+        let mut stuck = net();
+        stuck.set_idle_hook(|_| true);
+        assert_eq!(stuck.run(), Outcome::Deadlock);
+        assert_eq!(stuck.channels[0].stray, [vec![5, 3]]);
+    }
+}
@@ -0,0 +1,872 @@
+//! Loop acceleration for register machines.
+//!
+//! When a backward jump is about to be taken, the loop body is executed
+//! symbolically. If every register ends up changing by a constant amount per
+//! iteration or being overwritten with a value that does, the number of
+//! remaining iterations can be solved from the loop condition and the loop
+//! can be skipped in one go. This covers add loops, nested multiply loops
+//! and search loops that set a flag when an expression hits zero, including
+//! searches over nested loops.
+//!
+//! Analyses don't depend on register values, so they are cached per loop.
+//! The cache is cleared whenever an opaque instruction runs, since those may
+//! modify the program.
+
+use std::{collections::BTreeMap, ops::Range};
+
+use super::{BinOp, Cpu, InstructionSet, Micro};
+use crate::{prelude::HashMap, Operand};
+
+/// Polynomial over register values, maps the sorted register indices of
+/// each monomial to its coefficient.
+#[derive(Clone, Default, Eq, PartialEq, Debug)]
+struct Poly(BTreeMap<Vec<usize>, i64>);
+
+impl Poly {
+    fn constant(k: i64) -> Self {
+        let mut ret = Poly::default();
+        ret.push(Vec::new(), k);
+        ret
+    }
+
+    fn var(i: usize) -> Self {
+        Poly(BTreeMap::from([(vec![i], 1)]))
+    }
+
+    /// Add the term `c * mono`.
+    fn push(&mut self, mono: Vec<usize>, c: i64) {
+        let c = self.0.get(&mono).copied().unwrap_or(0) + c;
+        if c == 0 {
+            self.0.remove(&mono);
+        } else {
+            self.0.insert(mono, c);
+        }
+    }
+
+    fn as_const(&self) -> Option<i64> {
+        match self.0.first_key_value() {
+            None => Some(0),
+            Some((m, &k)) if m.is_empty() && self.0.len() == 1 => Some(k),
+            _ => None,
+        }
+    }
+
+    fn is_zero(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    fn depends_on(&self, i: usize) -> bool {
+        self.0.keys().any(|m| m.contains(&i))
+    }
+
+    /// `self + s * rhs`
+    fn add(&self, rhs: &Poly, s: i64) -> Poly {
+        let mut ret = self.clone();
+        for (m, c) in &rhs.0 {
+            ret.push(m.clone(), s * c);
+        }
+        ret
+    }
+
+    fn scale(&self, s: i64) -> Poly {
+        Poly::default().add(self, s)
+    }
+
+    fn mul(&self, rhs: &Poly) -> Poly {
+        let mut ret = Poly::default();
+        for (a, x) in &self.0 {
+            for (b, y) in &rhs.0 {
+                let mut m = [&a[..], b].concat();
+                m.sort_unstable();
+                ret.push(m, x * y);
+            }
+        }
+        ret
+    }
+
+    /// Substitute register values with expressions.
+    fn subst(&self, vals: &[Poly]) -> Poly {
+        let mut ret = Poly::default();
+        for (m, &c) in &self.0 {
+            let term = m
+                .iter()
+                .fold(Poly::constant(c), |acc, &i| acc.mul(&vals[i]));
+            ret = ret.add(&term, 1);
+        }
+        ret
+    }
+
+    fn eval(&self, regs: &[i64]) -> i64 {
+        self.0
+            .iter()
+            .map(|(m, c)| c * m.iter().map(|&i| regs[i]).product::<i64>())
+            .sum()
+    }
+}
+
+/// Constant assignment `reg = val` that happens on iterations where
+/// `cond + j * step` is zero for some `j` in `0..count`.
+///
+/// Conditional assignments in the loop body have a single `j`, ones in inner
+/// loops sweep over the iterations of the inner loop.
+struct Guard {
+    cond: Poly,
+    step: Poly,
+    count: Poly,
+    reg: usize,
+    val: i64,
+}
+
+/// Symbolic effect of one pass through a loop body, in terms of register
+/// values at the start of the pass.
+struct Pass {
+    state: Vec<Poly>,
+    guards: Vec<Guard>,
+    /// Expressions that must not be negative for the pass to be valid.
+    nonneg: Vec<Poly>,
+}
+
+impl Pass {
+    /// Registers that are set by guards. Fails unless they are set to a
+    /// single value and nothing else in the pass or `cond` touches them.
+    fn guarded(&self, cond: &Poly) -> Option<Vec<bool>> {
+        let n = self.state.len();
+        let mut vals = vec![None; n];
+        for g in &self.guards {
+            if vals[g.reg].is_some_and(|v| v != g.val) {
+                return None;
+            }
+            vals[g.reg] = Some(g.val);
+        }
+
+        let mut exprs: Vec<&Poly> = self.state.iter().collect();
+        for g in &self.guards {
+            exprs.extend([&g.cond, &g.step, &g.count]);
+        }
+        exprs.extend(&self.nonneg);
+        exprs.push(cond);
+        for i in (0..n).filter(|&i| vals[i].is_some()) {
+            if self.state[i] != Poly::var(i)
+                || exprs
+                    .iter()
+                    .enumerate()
+                    .any(|(j, e)| j != i && e.depends_on(i))
+            {
+                return None;
+            }
+        }
+        Some(vals.iter().map(Option::is_some).collect())
+    }
+}
+
+/// How registers change over iterations of a loop.
+struct Rates {
+    /// Registers that are increased by the same amount every iteration.
+    acc: Vec<bool>,
+    /// Per-iteration change of registers, in terms of registers the loop
+    /// doesn't change. Registers overwritten with an expression of
+    /// accumulators also change at a constant rate.
+    delta: Vec<Poly>,
+}
+
+impl Rates {
+    fn new(pass: &Pass) -> Option<Rates> {
+        let n = pass.state.len();
+        let changed: Vec<bool> =
+            (0..n).map(|i| pass.state[i] != Poly::var(i)).collect();
+
+        let delta: Vec<Poly> = (0..n)
+            .map(|i| pass.state[i].add(&Poly::var(i), -1))
+            .collect();
+        let acc: Vec<bool> = (0..n)
+            .map(|i| (0..n).all(|j| !changed[j] || !delta[i].depends_on(j)))
+            .collect();
+        let mut ret = Rates { acc, delta };
+
+        for i in 0..n {
+            if !ret.acc[i] {
+                ret.delta[i] = ret.rate(&pass.state[i])?;
+            }
+        }
+        Some(ret)
+    }
+
+    fn changes(&self, i: usize) -> bool {
+        !self.acc[i] || !self.delta[i].is_zero()
+    }
+
+    /// Per-iteration change of an expression over values at the start of an
+    /// iteration. Fails unless every term of the expression is an
+    /// accumulator times registers the loop doesn't change.
+    fn rate(&self, e: &Poly) -> Option<Poly> {
+        let mut ret = Poly::default();
+        for (m, &c) in &e.0 {
+            let mut changing = (0..m.len()).filter(|&p| self.changes(m[p]));
+            let Some(p) = changing.next() else {
+                continue;
+            };
+            if changing.next().is_some() || !self.acc[m[p]] {
+                return None;
+            }
+            let mut rest = m.clone();
+            rest.remove(p);
+            let term = Poly(BTreeMap::from([(rest, c)]));
+            ret = ret.add(&term.mul(&self.delta[m[p]]), 1);
+        }
+        Some(ret)
+    }
+}
+
+/// Index of a named register in a file of `n` registers.
+fn reg_index(r: char, n: usize) -> Option<usize> {
+    let i = (r as usize).checked_sub('a' as usize)?;
+    (i < n).then_some(i)
+}
+
+struct Walker<'a> {
+    prog: &'a [Micro],
+    /// Size of the register file.
+    n: usize,
+}
+
+impl Walker<'_> {
+    fn reg(&self, r: char) -> Option<usize> {
+        reg_index(r, self.n)
+    }
+
+    fn arg(&self, a: Operand, state: &[Poly]) -> Option<Poly> {
+        match a {
+            Operand::Int(k) => Some(Poly::constant(k)),
+            Operand::Reg(r) => Some(state[self.reg(r)?].clone()),
+            Operand::Mem(_) => None,
+        }
+    }
+
+    /// Symbolically execute one pass through `range`.
+    fn walk(&self, range: Range<usize>) -> Option<Pass> {
+        let mut pass = Pass {
+            state: (0..self.n).map(Poly::var).collect(),
+            guards: Vec::new(),
+            nonneg: Vec::new(),
+        };
+        let mut visited = vec![false; range.len()];
+
+        let mut pc = range.start;
+        while pc < range.end {
+            visited[pc - range.start] = true;
+            let state = &mut pass.state;
+            match self.prog[pc] {
+                Micro::Set(r, a) => {
+                    let r = self.reg(r)?;
+                    state[r] = self.arg(a, state)?;
+                }
+                Micro::Bin(r, op, a, b) => {
+                    let r = self.reg(r)?;
+                    let (a, b) = (self.arg(a, state)?, self.arg(b, state)?);
+                    state[r] = match (op, a.as_const(), b.as_const()) {
+                        (BinOp::Add, ..) => a.add(&b, 1),
+                        (BinOp::Sub, ..) => a.add(&b, -1),
+                        (BinOp::Mul, ..) => a.mul(&b),
+                        (_, Some(a), Some(b)) => Poly::constant(op.eval(a, b)?),
+                        _ => return None,
+                    };
+                }
+                Micro::Nop => {}
+                Micro::Input(_)
+                | Micro::Output(_)
+                | Micro::Goto(..)
+                | Micro::Opaque => return None,
+                Micro::Jnz(a, b) => {
+                    let cond = self.arg(a, state)?;
+                    let offset = self.arg(b, state)?.as_const()?;
+                    let target = pc as i64 + offset;
+                    if cond.is_zero() {
+                        // Never jumps.
+                    } else if offset > 0 {
+                        let target = target as usize;
+                        if target > range.end {
+                            return None;
+                        }
+                        if cond.as_const().is_none() {
+                            // Conditional skip over constant assignments.
+                            for i in pc + 1..target {
+                                let Micro::Set(r, Operand::Int(val)) =
+                                    self.prog[i]
+                                else {
+                                    return None;
+                                };
+                                pass.guards.push(Guard {
+                                    cond: cond.clone(),
+                                    step: Poly::default(),
+                                    count: Poly::constant(1),
+                                    reg: self.reg(r)?,
+                                    val,
+                                });
+                            }
+                        }
+                        pc = target;
+                        continue;
+                    } else {
+                        // Inner loop, must have been entered from the top.
+                        if target < range.start as i64
+                            || !visited[target as usize - range.start]
+                        {
+                            return None;
+                        }
+                        self.inner_loop(target as usize..pc, a, &mut pass)?;
+                    }
+                }
+            }
+            pc += 1;
+        }
+
+        Some(pass)
+    }
+
+    /// Apply the rest of an inner loop that has just run its first pass.
+    fn inner_loop(
+        &self,
+        body: Range<usize>,
+        cond: Operand,
+        outer: &mut Pass,
+    ) -> Option<()> {
+        let inner = self.walk(body)?;
+        let rates = Rates::new(&inner)?;
+        let inner_cond = self.arg(cond, &inner.state)?;
+        inner.guarded(&inner_cond)?;
+
+        // Iterations left, the condition must count down to zero one step at
+        // a time.
+        let step = rates.rate(&inner_cond)?.as_const()?;
+        if step.abs() != 1 {
+            return None;
+        }
+        let left = self.arg(cond, &outer.state)?.scale(-step);
+
+        // Constraints of nested loops must not depend on this loop.
+        for e in &inner.nonneg {
+            if !rates.rate(e)?.is_zero() {
+                return None;
+            }
+            outer.nonneg.push(e.subst(&outer.state));
+        }
+        outer.nonneg.push(left.clone());
+
+        // Guards sweep over the remaining iterations. Only guards directly
+        // in the inner loop can be swept.
+        for g in &inner.guards {
+            if !g.step.is_zero() {
+                return None;
+            }
+            outer.guards.push(Guard {
+                cond: g.cond.subst(&outer.state),
+                step: rates.rate(&g.cond)?.subst(&outer.state),
+                count: left.clone(),
+                reg: g.reg,
+                val: g.val,
+            });
+        }
+
+        let state = outer.state.clone();
+        for (i, d) in rates.delta.iter().enumerate() {
+            let d = d.subst(&state);
+            outer.state[i] = state[i].add(&left.mul(&d), 1);
+        }
+        Some(())
+    }
+}
+
+/// Expression whose value changes by a constant amount every iteration.
+struct Line {
+    /// Value on the first iteration.
+    start: Poly,
+    /// Change per iteration.
+    rate: Poly,
+}
+
+impl Line {
+    fn new(e: &Poly, rates: &Rates) -> Option<Line> {
+        Some(Line {
+            start: e.clone(),
+            rate: rates.rate(e)?,
+        })
+    }
+
+    fn eval(&self, regs: &[i64]) -> (i64, i64) {
+        (self.start.eval(regs), self.rate.eval(regs))
+    }
+}
+
+/// Guard with lines for its expressions over the iterations of a loop.
+struct GuardLines {
+    cond: Line,
+    step: Line,
+    count: Line,
+    reg: usize,
+    val: i64,
+}
+
+impl GuardLines {
+    /// Whether the guard fires during `iterations` iterations.
+    fn fires(&self, regs: &[i64], iterations: i64) -> bool {
+        let (c0, dc) = self.cond.eval(regs);
+        let (s0, ds) = self.step.eval(regs);
+        let (n0, dn) = self.count.eval(regs);
+        if s0 == 0 && ds == 0 && dn == 0 {
+            n0 > 0 && hits(c0, dc, iterations)
+        } else {
+            // No closed form for nested searches, scan the outer iterations
+            // until one hits.
+            (0..iterations).any(|k| hits(c0 + k * dc, s0 + k * ds, n0 + k * dn))
+        }
+    }
+}
+
+/// Whether `x + j * dx` is zero for some `j` in `0..n`.
+fn hits(x: i64, dx: i64, n: i64) -> bool {
+    if dx == 0 {
+        x == 0 && n > 0
+    } else {
+        x % dx == 0 && (0..n).contains(&(-x / dx))
+    }
+}
+
+/// Solved loop in terms of register values at the start of its next
+/// iteration.
+struct Summary {
+    /// Register values after the iterations.
+    regs: Vec<Line>,
+    /// Loop condition at the end of the iterations.
+    cond: Line,
+    guards: Vec<GuardLines>,
+    nonneg: Vec<Line>,
+}
+
+impl Summary {
+    fn new(
+        prog: &[Micro],
+        n: usize,
+        body: Range<usize>,
+        cond: Operand,
+    ) -> Option<Summary> {
+        let walker = Walker { prog, n };
+        let pass = walker.walk(body)?;
+        let rates = Rates::new(&pass)?;
+        let cond = walker.arg(cond, &pass.state)?;
+        let guarded = pass.guarded(&cond)?;
+
+        let regs = (0..n)
+            .map(|i| {
+                if guarded[i] {
+                    Line {
+                        start: Poly::var(i),
+                        rate: Poly::default(),
+                    }
+                } else {
+                    // Accumulators add their delta on each iteration,
+                    // overwritten registers get the value from the last one.
+                    let d = &rates.delta[i];
+                    Line {
+                        start: pass.state[i].add(d, -1),
+                        rate: d.clone(),
+                    }
+                }
+            })
+            .collect();
+        let guards = pass
+            .guards
+            .iter()
+            .map(|g| {
+                Some(GuardLines {
+                    cond: Line::new(&g.cond, &rates)?,
+                    step: Line::new(&g.step, &rates)?,
+                    count: Line::new(&g.count, &rates)?,
+                    reg: g.reg,
+                    val: g.val,
+                })
+            })
+            .collect::<Option<_>>()?;
+        let nonneg = pass
+            .nonneg
+            .iter()
+            .map(|e| Line::new(e, &rates))
+            .collect::<Option<_>>()?;
+
+        Some(Summary {
+            regs,
+            cond: Line::new(&cond, &rates)?,
+            guards,
+            nonneg,
+        })
+    }
+
+    /// Register values after the loop exits.
+    fn solve(&self, regs: &[i64]) -> Option<Vec<i64>> {
+        // The loop stops after the first iteration that ends with a zero
+        // condition.
+        let (c0, dc) = self.cond.eval(regs);
+        if dc == 0 || c0 % dc != 0 || -c0 / dc < 0 {
+            return None;
+        }
+        let iterations = -c0 / dc + 1;
+
+        for e in &self.nonneg {
+            let (x0, dx) = e.eval(regs);
+            if x0 < 0 || x0 + (iterations - 1) * dx < 0 {
+                return None;
+            }
+        }
+
+        let mut ret: Vec<i64> = self
+            .regs
+            .iter()
+            .map(|e| {
+                let (x, d) = e.eval(regs);
+                x + iterations * d
+            })
+            .collect();
+        for g in &self.guards {
+            if g.fires(regs, iterations) {
+                ret[g.reg] = g.val;
+            }
+        }
+        Some(ret)
+    }
+}
+
+/// Decoded program and loop analyses.
+#[derive(Default)]
+pub(super) struct Cache {
+    prog: Vec<Micro>,
+    /// Loops by the position of their backward jump, `None` if the loop
+    /// can't be solved.
+    loops: HashMap<usize, Option<Summary>>,
+}
+
+impl Cache {
+    /// Forget everything after the program has changed.
+    pub fn clear(&mut self) {
+        self.prog.clear();
+        self.loops.clear();
+    }
+}
+
+/// If the CPU is at a taken backward jump of a loop that can be solved,
+/// run the rest of the loop and move past the jump.
+pub(super) fn skip_loop<I: InstructionSet<Word = i64>>(
+    cpu: &mut Cpu<I>,
+) -> bool {
+    let n = cpu.regs.len();
+    let pc = cpu.pc as usize;
+    let Cache { prog, loops } = &mut cpu.cache;
+    if prog.is_empty() {
+        *prog = cpu.prog.iter().map(|op| op.micro()).collect();
+    }
+    let Micro::Jnz(cond, Operand::Int(offset)) = prog[pc] else {
+        return false;
+    };
+    if offset >= 0 || offset < -(pc as i64) {
+        return false;
+    }
+    // Only look at loops that are about to repeat.
+    match cond {
+        Operand::Int(0) => return false,
+        Operand::Reg(r) if reg_index(r, n).is_none_or(|i| cpu.regs[i] == 0) => {
+            return false
+        }
+        _ => {}
+    }
+    let body = (pc as i64 + offset) as usize..pc;
+    let summary = loops
+        .entry(pc)
+        .or_insert_with(|| Summary::new(prog, n, body, cond));
+    let Some(regs) = summary.as_ref().and_then(|s| s.solve(&cpu.regs)) else {
+        return false;
+    };
+    cpu.regs.copy_from_slice(&regs);
+    cpu.pc += 1;
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+    use super::*;
+    use crate::vm::{Flow, Status};
+
+    /// Assembunny-like instruction set with extra arithmetic.
+    #[derive(Copy, Clone, Debug)]
+    enum Op {
+        Set(char, Operand),
+        Add(char, Operand),
+        Sub(char, Operand),
+        Mul(char, Operand),
+        Jnz(Operand, Operand),
+        Tgl(char),
+    }
+
+    impl Op {
+        fn toggle(&mut self) {
+            *self = match *self {
+                Op::Set(r, a) => Op::Jnz(a, Operand::Reg(r)),
+                Op::Jnz(a, Operand::Reg(r)) => Op::Set(r, a),
+                Op::Jnz(a, b) => Op::Jnz(a, b),
+                Op::Add(r, a) => Op::Sub(r, a),
+                Op::Sub(r, a) => Op::Add(r, a),
+                Op::Mul(r, a) => Op::Add(r, a),
+                Op::Tgl(r) => Op::Sub(r, Operand::Int(1)),
+            }
+        }
+    }
+
+    impl InstructionSet for Op {
+        type Word = i64;
+
+        const REGISTERS: usize = 6;
+
+        fn exec(&self, cpu: &mut Cpu<Self>) -> Flow {
+            let regs = &mut cpu.regs;
+            match *self {
+                Op::Set(r, a) => regs[r] = a.val(regs),
+                Op::Add(r, a) => regs[r] = regs[r].wrapping_add(a.val(regs)),
+                Op::Sub(r, a) => regs[r] = regs[r].wrapping_sub(a.val(regs)),
+                Op::Mul(r, a) => regs[r] = regs[r].wrapping_mul(a.val(regs)),
+                Op::Jnz(a, b) => {
+                    if a.val(regs) != 0 {
+                        return Flow::Jump(b.val(regs));
+                    }
+                }
+                Op::Tgl(r) => {
+                    let i = cpu.pc + cpu.regs[r];
+                    if i >= 0 && i < cpu.prog.len() as i64 {
+                        cpu.prog[i as usize].toggle();
+                    }
+                }
+            }
+            Flow::Next
+        }
+
+        fn micro(&self) -> Micro {
+            match *self {
+                Op::Set(r, a) => Micro::Set(r, a),
+                Op::Add(r, a) => Micro::apply(r, BinOp::Add, a),
+                Op::Sub(r, a) => Micro::apply(r, BinOp::Sub, a),
+                Op::Mul(r, a) => Micro::apply(r, BinOp::Mul, a),
+                Op::Jnz(a, b) => Micro::Jnz(a, b),
+                Op::Tgl(_) => Micro::Opaque,
+            }
+        }
+    }
+
+    fn parse(src: &str) -> Vec<Op> {
+        src.lines()
+            .map(|line| {
+                let words: Vec<&str> = line.split_whitespace().collect();
+                let r = || words[1].chars().next().unwrap();
+                let a = |i: usize| words[i].parse::<Operand>().unwrap();
+                match words[0] {
+                    "set" => Op::Set(r(), a(2)),
+                    "add" => Op::Add(r(), a(2)),
+                    "sub" => Op::Sub(r(), a(2)),
+                    "mul" => Op::Mul(r(), a(2)),
+                    "jnz" => Op::Jnz(a(1), a(2)),
+                    "tgl" => Op::Tgl(r()),
+                    _ => panic!("bad op {line}"),
+                }
+            })
+            .collect()
+    }
+
+    /// Run program with and without optimization for at most `limit` steps,
+    /// return final registers and step counts if both runs finished.
+    fn compare(
+        prog: &[Op],
+        regs: &[i64],
+        limit: u64,
+    ) -> Option<(Vec<i64>, u64, u64)> {
+        let mut plain = Cpu::new(prog).with_regs(regs.iter().copied());
+        let mut fast =
+            Cpu::new(prog).with_regs(regs.iter().copied()).optimize();
+        for cpu in [&mut plain, &mut fast] {
+            while cpu.steps < limit && cpu.step() == Status::Running {}
+        }
+        if !plain.is_halted() {
+            return None;
+        }
+        assert!(fast.is_halted(), "{prog:?}");
+        assert_eq!(plain.regs, fast.regs, "{prog:?} {regs:?}");
+        assert_eq!(plain.prog.len(), fast.prog.len());
+        Some((fast.regs.0, plain.steps, fast.steps))
+    }
+
+    #[test]
+    fn multiply() {
+        let prog = parse(
+            "\
+set a 0
+set c b
+add a 1
+sub c 1
+jnz c -2
+sub d 1
+jnz d -5",
+        );
+        let (regs, slow, fast) =
+            compare(&prog, &[0, 123, 0, 45], 100_000).unwrap();
+        assert_eq!(regs[0], 123 * 45);
+        assert!(fast < 20);
+        assert!(slow > 10_000);
+
+        // Inner loop starting at zero wraps around, don't optimize that.
+        let mut cpu = Cpu::new(prog).with_regs([0, 0, 0, 2]).optimize();
+        for _ in 0..1000 {
+            cpu.step();
+        }
+        assert!(!cpu.is_halted());
+    }
+
+    #[test]
+    fn search() {
+        // Set f to 0 if some e in 2..b satisfies d * e == b.
+        let prog = parse(
+            "\
+set f 1
+set e 2
+set c d
+mul c e
+sub c b
+jnz c 2
+set f 0
+add e 1
+set c e
+sub c b
+jnz c -8",
+        );
+        for (b, d, f) in [(91, 7, 0), (91, 6, 1), (91, 13, 0), (97, 2, 1)] {
+            let (regs, _, fast) =
+                compare(&prog, &[0, b, 0, d, 0, 0], 10_000).unwrap();
+            assert_eq!(regs[5], f, "{b} {d}");
+            assert!(fast < 20);
+        }
+    }
+
+    #[test]
+    fn nested_search() {
+        // Set f to 0 if some d, e in 2..b satisfy d * e == b.
+        let prog = parse(
+            "\
+set f 1
+set d 2
+set e 2
+set c d
+mul c e
+sub c b
+jnz c 2
+set f 0
+add e 1
+set c e
+sub c b
+jnz c -8
+add d 1
+set c d
+sub c b
+jnz c -13",
+        );
+        for (b, f) in [(91, 0), (97, 1), (49, 0)] {
+            let (regs, _, fast) =
+                compare(&prog, &[0, b, 0, 0, 0, 0], 200_000).unwrap();
+            assert_eq!(regs[5], f, "{b}");
+            assert!(fast < 50);
+        }
+
+        for (b, f) in [(104_729, 1), (104_730, 0)] {
+            let mut cpu = Cpu::new(prog.clone()).with_regs([0, b]).optimize();
+            cpu.run();
+            assert_eq!(cpu.regs[5], f, "{b}");
+            assert!(cpu.steps < 50);
+        }
+    }
+
+    #[test]
+    fn toggle() {
+        // Toggling turns the countdown into a count-up that only terminates
+        // by wrapping. The optimizer must see the toggled loop.
+        let prog = parse(
+            "\
+set b 5
+tgl b
+set a 100
+add a 2
+sub a 1
+jnz a -2
+add c 1",
+        );
+        let mut cpu = Cpu::new(prog.clone()).optimize();
+        for _ in 0..1000 {
+            cpu.step();
+        }
+        assert!(!cpu.is_halted());
+
+        // Toggle the add loop into a subtract loop.
+        let prog = parse(
+            "\
+set b 3
+tgl b
+set a 100
+add a 1
+add c 1
+sub a 2
+jnz a -3",
+        );
+        let (regs, _, fast) = compare(&prog, &[0; 6], 1000).unwrap();
+        assert_eq!(regs[2], -100);
+        assert!(fast < 10);
+    }
+
+    #[test]
+    fn matches_plain_execution() {
+        let mut rng = SmallRng::seed_from_u64(123);
+        let mut optimized = 0;
+        for _ in 0..5000 {
+            let len = rng.gen_range(3..10);
+            let reg = |rng: &mut SmallRng| (b'a' + rng.gen_range(0..4)) as char;
+            let arg = |rng: &mut SmallRng| {
+                if rng.gen_bool(0.5) {
+                    Operand::Int(rng.gen_range(-3..4))
+                } else {
+                    Operand::Reg(reg(rng))
+                }
+            };
+            // Bias towards counting loops.
+            let count = |rng: &mut SmallRng| {
+                if rng.gen_bool(0.5) {
+                    Operand::Int(1)
+                } else {
+                    arg(rng)
+                }
+            };
+            let prog: Vec<Op> = (0..len)
+                .map(|_| match rng.gen_range(0..8) {
+                    0 => Op::Set(reg(&mut rng), arg(&mut rng)),
+                    1 | 2 => Op::Add(reg(&mut rng), count(&mut rng)),
+                    3 | 4 => Op::Sub(reg(&mut rng), count(&mut rng)),
+                    5 => Op::Mul(reg(&mut rng), arg(&mut rng)),
+                    6 => Op::Tgl(reg(&mut rng)),
+                    _ => Op::Jnz(
+                        Operand::Reg(reg(&mut rng)),
+                        Operand::Int(rng.gen_range(-4..3)),
+                    ),
+                })
+                .collect();
+            let regs: Vec<i64> =
+                (0..4).map(|_| rng.gen_range(-20..20)).collect();
+            if let Some((_, slow, fast)) = compare(&prog, &regs, 2000) {
+                if fast < slow {
+                    optimized += 1;
+                }
+            }
+        }
+        assert!(optimized > 20, "{optimized}");
+    }
+}
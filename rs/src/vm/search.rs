@@ -0,0 +1,127 @@
+//! Input search for programs that consume their input a few bits at a time.
+//!
+//! A common puzzle program shape is a loop that outputs a value computed from
+//! an input register and then shifts a fixed number of bits off the register
+//! until it hits zero. Every output depends only on the input bits that are
+//! still left at that point, so the last output only depends on the highest
+//! bits. The input can be built from the most significant end, fixing a
+//! chunk of bits at a time and checking that the partial input reproduces the
+//! end of the wanted output.
+
+/// Iterator over inputs that make a chunk-consuming program produce the
+/// target output, in ascending order.
+///
+/// `step(x)` is the output the program produces when the input register has
+/// value `x`, ie. the first output of the program started with `x`. An input
+/// `x` is a solution when `step(x >> (bits * i)) == target[i]` for every
+/// output `i` and `x` has exactly as many chunks of `bits` bits as there are
+/// outputs.
+///
+/// Take the first item for the smallest solution.
+pub fn bit_search<W: PartialEq, F: FnMut(u64) -> W>(
+    bits: u32,
+    target: &[W],
+    step: F,
+) -> BitSearch<'_, W, F> {
+    assert!(
+        (1..64).contains(&bits) && bits as usize * target.len() <= 64,
+        "bit_search: input doesn't fit in 64 bits"
+    );
+    BitSearch {
+        bits,
+        target,
+        step,
+        chosen: Vec::new(),
+        // The highest chunk can't be zero, the program would stop early.
+        next: (target.len() > 1) as u64,
+    }
+}
+
+pub struct BitSearch<'a, W, F> {
+    bits: u32,
+    target: &'a [W],
+    step: F,
+    /// Chunks fixed so far, most significant first.
+    chosen: Vec<u64>,
+    /// Next chunk to try after the fixed ones.
+    next: u64,
+}
+
+impl<W: PartialEq, F: FnMut(u64) -> W> Iterator for BitSearch<'_, W, F> {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let n = self.target.len();
+        if n == 0 {
+            return None;
+        }
+        loop {
+            if self.next >> self.bits != 0 {
+                // Ran out of chunks at this level, backtrack.
+                self.next = self.chosen.pop()? + 1;
+                continue;
+            }
+
+            let x = self
+                .chosen
+                .iter()
+                .chain(Some(&self.next))
+                .fold(0, |acc, c| (acc << self.bits) | c);
+            let depth = self.chosen.len();
+
+            if (self.step)(x) != self.target[n - 1 - depth] {
+                self.next += 1;
+            } else if depth + 1 == n {
+                self.next += 1;
+                return Some(x);
+            } else {
+                self.chosen.push(self.next);
+                self.next = 0;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Brute force solutions for comparison.
+    fn brute(bits: u32, target: &[u64], step: impl Fn(u64) -> u64) -> Vec<u64> {
+        let n = target.len() as u32;
+        let lo = if n > 1 { 1 << (bits * (n - 1)) } else { 0 };
+        (lo..1 << (bits * n))
+            .filter(|&x| {
+                (0..n).all(|i| step(x >> (bits * i)) == target[i as usize])
+            })
+            .collect()
+    }
+
+    #[test]
+    fn matches_brute_force() {
+        // Output mixes low bits with higher bits.
+        let step = |x: u64| (x & 7) ^ 5 ^ ((x >> ((x & 7) ^ 1)) & 7);
+
+        for seed in [512, 777, 1666, 2024, 4095] {
+            let target: Vec<u64> =
+                (0..4).map(|i| step(seed >> (3 * i))).collect();
+            let expected = brute(3, &target, step);
+            assert!(expected.contains(&seed));
+            assert_eq!(
+                bit_search(3, &target, step).collect::<Vec<_>>(),
+                expected
+            );
+        }
+
+        // Targets without solutions.
+        let step = |x: u64| x & 1;
+        assert!(brute(2, &[1, 2], step).is_empty());
+        assert_eq!(bit_search(2, &[1, 2], step).next(), None);
+
+        assert_eq!(bit_search(4, &[], step).next(), None);
+        assert_eq!(
+            bit_search(4, &[0], step).collect::<Vec<_>>(),
+            brute(4, &[0], step)
+        );
+    }
+}